@@ -0,0 +1,37 @@
+#[macro_use]
+extern crate prlg;
+
+use prlg::{dot, World};
+
+fn main() {
+    let mut world = World::new(rules![
+        (grandparent {x} {z}) {
+            (parent {x} {y})
+            (parent {y} {z})
+        }
+        (ancestor {x} {y}) {
+            (parent {x} {y})
+        }
+        (ancestor {x} {z}) {
+            (parent {x} {y})
+            (ancestor {y} {z})
+        }
+        (related {x} {y}) {
+            (par (ancestor {x} {z}) (ancestor {y} {z}))
+            (sibling {x} {y})
+        }
+    ]);
+    world
+        .load_facts(
+            "parent",
+            [["alice", "bob"], ["bob", "carol"], ["bob", "dave"]],
+        )
+        .unwrap();
+
+    // Render with e.g. `dot -Tsvg`.
+    print!(
+        "{}",
+        dot::search_tree(&world, &[data! {(grandparent alice {who})}], 100).unwrap()
+    );
+    print!("{}", dot::call_graph(&world));
+}
@@ -0,0 +1,38 @@
+#[macro_use]
+extern crate prlg;
+
+use prlg::World;
+
+fn main() {
+    let mut world = World::new(rules![
+        (pokemon {x}) {
+            (isa {x} pokemon)
+        }
+    ]);
+
+    let s = std::time::Instant::now();
+    let n = world
+        .load_facts(
+            "isa",
+            (0..1_000_000).map(|i| {
+                let kind = if i % 100_000 == 0 { "pokemon" } else { "digimon" };
+                [format!("m{}", i), kind.to_owned()]
+            }),
+        )
+        .unwrap();
+    dbg!(n, s.elapsed());
+
+    let s = std::time::Instant::now();
+    world.run(&[data! {(pokemon {x})}], |c| {
+        for d in c {
+            println!("{}", d);
+        }
+    }).unwrap();
+    println!();
+    world.run(&[data! {(isa m42 {x})}], |c| {
+        for d in c {
+            println!("{}", d);
+        }
+    }).unwrap();
+    dbg!(s.elapsed());
+}
@@ -55,7 +55,7 @@ fn main() {
     let world = World::new(rules);
 
     let data_slice = &[data! {(zebra {h} {w} {z})}];
-    let goals = VariableScope::new().new_data_vec(data_slice, &mut |s| world.symbol_pool.get(s));
+    let goals = VariableScope::new().new_data_vec(data_slice);
 
     InteractiveRuntime::new(&world).run(goals);
 }
@@ -0,0 +1,59 @@
+#[macro_use]
+extern crate prlg;
+
+use std::{thread, time::Duration};
+
+use prlg::{
+    limits::{CancelToken, Limits},
+    World,
+};
+
+fn main() {
+    let world = World::new(rules![
+        (loop) {
+            (loop)
+        }
+        (color red)
+        (color green)
+        (color blue)
+    ]);
+
+    let inferences = Limits {
+        inferences: Some(10_000),
+        ..Default::default()
+    };
+    let result = world.run_limited(&[data! {(loop)}], &inferences, |_| ());
+    println!("budget: {}", result.unwrap_err());
+
+    let answers = Limits {
+        answers: Some(2),
+        ..Default::default()
+    };
+    let result = world.run_limited(&[data! {(color {c})}], &answers, |c| println!("  {}", c[0]));
+    println!("answers: {}", result.unwrap_err());
+
+    let timeout = Limits {
+        timeout: Some(Duration::from_millis(50)),
+        ..Default::default()
+    };
+    let result = world.run_limited(&[data! {(loop)}], &timeout, |_| ());
+    println!("timeout: {}", result.unwrap_err());
+
+    // Another thread cancels the query through a clone of its token.
+    let token = CancelToken::new();
+    let cancel = Limits {
+        cancel: Some(token.clone()),
+        ..Default::default()
+    };
+    thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        token.cancel();
+    });
+    let result = world.run_limited(&[data! {(loop)}], &cancel, |_| ());
+    println!("cancel: {}", result.unwrap_err());
+
+    // The world is still usable afterwards.
+    let mut colors = 0;
+    world.run(&[data! {(color {c})}], |_| colors += 1).unwrap();
+    println!("colors: {}", colors);
+}
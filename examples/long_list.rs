@@ -0,0 +1,45 @@
+#[macro_use]
+extern crate prlg;
+
+use prlg::World;
+
+fn main() {
+    let rules = rules![
+        (append nil {xs} {xs})
+        (append [{x} . {xs}] {ys} [{x} . {zs}]) {
+            (append {xs} {ys} {zs})
+        }
+
+        (double {xs} {ys}) {
+            (append {xs} {xs} {ys})
+        }
+
+        (eq {x} {x})
+
+        (long_list {l}) {
+            (double [a] {l1}) (double {l1} {l2}) (double {l2} {l3}) (double {l3} {l4})
+            (double {l4} {l5}) (double {l5} {l6}) (double {l6} {l7}) (double {l7} {l8})
+            (double {l8} {l9}) (double {l9} {l10}) (double {l10} {l11}) (double {l11} {l12})
+            (double {l12} {l13}) (double {l13} {l14}) (double {l14} {l15}) (double {l15} {l16})
+            (double {l16} {l17}) (double {l17} {l18}) (double {l18} {l19}) (double {l19} {l20})
+            (eq {l20} {l})
+        }
+    ];
+    let world = World::new(rules);
+
+    // 2^20 elements built by append, copied out of the bindings, unified, printed and dropped.
+    let s = std::time::Instant::now();
+    let mut count = 0;
+    world
+        .run(&[data! {(long_list {l})}, data! {(long_list {m})}, data! {(eq {l} {m})}], |c| {
+            for d in c {
+                let printed = d.to_string();
+                assert_eq!(printed.matches('a').count() % (1 << 20), 0);
+                assert_eq!(d.clone().to_string(), printed);
+            }
+            count += 1;
+        })
+        .unwrap();
+    assert_eq!(count, 1);
+    dbg!(s.elapsed());
+}
@@ -58,30 +58,30 @@ fn main() {
         for d in c {
             println!("{}", d);
         }
-    });
+    }).unwrap();
     println!();
     world.run(&[data! {(append (cons a nil) (cons b nil) {nyan})}], |c| {
         for d in c {
             println!("{}", d);
         }
-    });
+    }).unwrap();
     println!();
     world.run(&[data! {(delete [a b c d] c {})}], |c| {
         for d in c {
             println!("{}", d);
         }
-    });
+    }).unwrap();
     println!();
     world.run(&[data! {(my_list {nyan})}], |c| {
         for d in c {
             println!("{}", d);
         }
-    });
+    }).unwrap();
     println!();
     world.run(&[data! {(perm {nyan})}], |c| {
         for d in c {
             println!("{}", d);
         }
-    });
+    }).unwrap();
     println!();
 }
@@ -41,7 +41,7 @@ fn main() {
         for d in c {
             println!("{}", d)
         }
-    });
+    }).unwrap();
     println!();
     world.run(
         &[data! {(mul (s (s (s zero))) (s (s (s (s zero)))) {})}],
@@ -50,7 +50,7 @@ fn main() {
                 println!("{}", d)
             }
         },
-    );
+    ).unwrap();
     println!();
     world.run(
         &[
@@ -62,6 +62,6 @@ fn main() {
                 println!("{}", d)
             }
         },
-    );
+    ).unwrap();
     println!();
 }
@@ -0,0 +1,75 @@
+#[macro_use]
+extern crate prlg;
+
+use std::{sync::Arc, time::Instant};
+
+use prlg::{parallel::Parallel, World};
+
+fn main() {
+    let rules = rules![
+        (select {x} (cons {x} {t}) {t})
+        (select {x} (cons {h} {t}) (cons {h} {r})) {
+            (select {x} {t} {r})
+        }
+        (perm nil nil)
+        (perm {l} (cons {x} {p})) {
+            (select {x} {l} {r})
+            (perm {r} {p})
+        }
+        // Both permutations are searched at once when `{a}` and `{b}` share no variables.
+        (two_perms {a} {b} {p} {q}) {
+            (par (perm {a} {p}) (perm {b} {q}))
+        }
+    ];
+    let world = Arc::new(World::new(rules));
+    let query = [
+        data! {(perm (cons a (cons b (cons c (cons d (cons e (cons f (cons g (cons h nil)))))))) {p})},
+    ];
+
+    let start = Instant::now();
+    let mut sequential = vec![];
+    world
+        .run(&query, |c| sequential.push(c[0].to_string()))
+        .unwrap();
+    println!(
+        "sequential: {} answers in {:?}",
+        sequential.len(),
+        start.elapsed()
+    );
+
+    let start = Instant::now();
+    let mut unordered: Vec<_> = world
+        .run_parallel(&query, Parallel::default())
+        .map(|c| c.unwrap()[0].to_string())
+        .collect();
+    println!(
+        "parallel: {} answers in {:?}",
+        unordered.len(),
+        start.elapsed()
+    );
+
+    // Ordered answers come out as the sequential search finds them.
+    let options = Parallel {
+        ordered: true,
+        ..Parallel::default()
+    };
+    let ordered: Vec<_> = world
+        .run_parallel(&query, options)
+        .map(|c| c.unwrap()[0].to_string())
+        .collect();
+    assert_eq!(ordered, sequential);
+    unordered.sort();
+    sequential.sort();
+    assert_eq!(unordered, sequential);
+    println!("first: {}", ordered[0]);
+
+    let mut joined = 0;
+    world
+        .run(
+            &[data! {(two_perms (cons a (cons b (cons c nil))) (cons d (cons e nil)) {p} {q})}],
+            |_| joined += 1,
+        )
+        .unwrap();
+    assert_eq!(joined, 6 * 2);
+    println!("joined: {} answers", joined);
+}
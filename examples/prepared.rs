@@ -0,0 +1,31 @@
+#[macro_use]
+extern crate prlg;
+
+use prlg::World;
+
+fn main() {
+    let rules = rules![
+        (add zero {y} {y})
+        (add (s {x}) {y} (s {z})) {
+            (add {x} {y} {z})
+        }
+    ];
+    let world = World::new(rules);
+
+    let query = world.prepare(&[data! {(add {x} {y} {z})}]);
+    let mut n = data! {zero};
+    for _ in 0..3 {
+        query
+            .run(&[("x", data! {(s zero)}), ("y", n.clone())], |c| {
+                println!("{}", c[0])
+            })
+            .unwrap();
+        n = term![sym!(s), n];
+    }
+
+    // Unbound parameters stay variables.
+    query
+        .run(&[("z", data! {(s (s zero))})], |c| println!("{}", c[0]))
+        .unwrap();
+    assert!(query.run(&[("w", data! {zero})], |_| {}).is_err());
+}
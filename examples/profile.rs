@@ -0,0 +1,40 @@
+#[macro_use]
+extern crate prlg;
+
+use prlg::World;
+
+fn main() {
+    let world = World::new(rules![
+        (sel {x} (cons {x} {t}) {t})
+        (sel {x} (cons {h} {t}) (cons {h} {r})) {
+            (sel {x} {t} {r})
+        }
+        (perm nil nil)
+        (perm {l} (cons {x} {p})) {
+            (sel {x} {l} {r})
+            (perm {r} {p})
+        }
+        (sorted nil)
+        (sorted (cons {x} nil))
+        (sorted (cons {x} (cons {y} {t}))) {
+            (le {x} {y})
+            (sorted (cons {y} {t}))
+        }
+        (le z {x})
+        (le (s {x}) (s {y})) {
+            (le {x} {y})
+        }
+        (slow_sort {l} {s}) {
+            (perm {l} {s})
+            (sorted {s})
+        }
+    ]);
+
+    let profile = world
+        .run_profiled(
+            &[data! {(slow_sort [(s (s (s z))) (s z) (s (s (s (s z)))) z (s (s z)) (s (s (s (s (s z)))))] {s})}],
+            |answer| println!("{}\n", answer[0]),
+        )
+        .unwrap();
+    print!("{}", profile);
+}
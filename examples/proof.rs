@@ -0,0 +1,35 @@
+#[macro_use]
+extern crate prlg;
+
+use prlg::World;
+
+fn main() {
+    let world = World::new(rules![
+        (parent alice bob)
+        (parent bob carol)
+        (parent bob dave)
+        (ancestor {x} {y}) {
+            (parent {x} {y})
+        }
+        (ancestor {x} {z}) {
+            (parent {x} {y})
+            (ancestor {y} {z})
+        }
+    ]);
+
+    world
+        .run_with_proofs(&[data! {(ancestor alice {Who})}], |answer, proofs| {
+            println!("=> {}", answer[0]);
+            for proof in proofs {
+                print!("{}", proof);
+            }
+        })
+        .unwrap();
+
+    // The same proof as a term, e.g. to query it further.
+    world
+        .run_with_proofs(&[data! {(ancestor alice carol)}], |_, proofs| {
+            println!("{}", proofs[0].to_term());
+        })
+        .unwrap();
+}
@@ -0,0 +1,44 @@
+#[macro_use]
+extern crate prlg;
+
+use prlg::{limits::Limits, snapshot::Snapshot, World};
+
+fn main() {
+    let world = World::new(rules![
+        (sel {x} (cons {x} {t}) {t})
+        (sel {x} (cons {h} {t}) (cons {h} {r})) {
+            (sel {x} {t} {r})
+        }
+        (perm nil nil)
+        (perm {l} (cons {x} {p})) {
+            (sel {x} {l} {r})
+            (perm {r} {p})
+        }
+    ]);
+
+    // Each session searches for a while, then saves what is left as bytes, e.g. to a file.
+    let limits = Limits {
+        inferences: Some(40),
+        ..Default::default()
+    };
+    let mut bytes = Snapshot::query(&[data! {(perm [a b c] {p})}]).to_bytes();
+    for session in 1.. {
+        let snapshot = Snapshot::from_bytes(&bytes).unwrap();
+        if snapshot.is_empty() {
+            break;
+        }
+        let mut rt = snapshot.resume(&world, &limits).unwrap();
+        println!("session {}:", session);
+        loop {
+            match rt.next(&world) {
+                Ok(Some(answer)) => println!("  {}", answer[0]),
+                Ok(None) => break,
+                Err(e) => {
+                    println!("  suspended: {}", e);
+                    break;
+                }
+            }
+        }
+        bytes = rt.snapshot().unwrap().to_bytes();
+    }
+}
@@ -0,0 +1,66 @@
+#[macro_use]
+extern crate prlg;
+
+use std::{sync::mpsc, thread};
+
+use prlg::{strategy::SearchStrategy, world::ClauseOrder, World};
+
+fn main() {
+    let strategies = [
+        ("breadth-first", SearchStrategy::BreadthFirst),
+        (
+            "iterative deepening",
+            SearchStrategy::IterativeDeepening { start: 2, step: 2 },
+        ),
+        // Fewer goals left is closer to an answer.
+        (
+            "best-first",
+            SearchStrategy::best_first(|goals| goals.len() as u64),
+        ),
+        ("interleaving", SearchStrategy::Interleaving { steps: 4 }),
+    ];
+    for (name, strategy) in strategies {
+        // The first clause of `loopy` recurses forever, which hangs depth-first search.
+        let mut world = World::new(rules![
+            (loopy {x}) {
+                (loopy {x})
+            }
+            (loopy a)
+            (nat z)
+            (nat (s {x})) {
+                (nat {x})
+            }
+        ]);
+        world.strategy = strategy;
+
+        // The search never ends, so it runs on its own thread and only a few answers are read.
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            world.run(&[data! {(loopy {x})}, data! {(nat {n})}], |c| {
+                let _ = sender.send(format!("{} {}", c[1], c[0]));
+            })
+        });
+        println!("{}:", name);
+        for answer in receiver.iter().take(3) {
+            println!("  {}", answer);
+        }
+    }
+
+    // Random clause order gives varied answers, the same ones again for the same seed.
+    let generate = |seed| {
+        let mut world = World::new(rules![
+            (color red)
+            (color green)
+            (color blue)
+        ]);
+        world.clause_order = ClauseOrder::Random { seed };
+        let mut colors = vec![];
+        world
+            .run(&[data! {(color {c})}], |c| colors.push(c[0].to_string()))
+            .unwrap();
+        colors.join(" ")
+    };
+    assert_eq!(generate(1), generate(1));
+    println!("seed 1: {}", generate(1));
+    println!("seed 2: {}", generate(2));
+}
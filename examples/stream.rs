@@ -0,0 +1,103 @@
+#[macro_use]
+extern crate prlg;
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+    thread,
+    time::Duration,
+};
+
+use prlg::{foreign::block_on, Atom, World};
+
+/// Resolves on another thread after `delay`, like a request to an external service.
+struct Delayed {
+    delay: Duration,
+    state: Arc<Mutex<(bool, Option<Waker>)>>,
+    started: bool,
+}
+
+impl Future for Delayed {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.state.lock().unwrap();
+        if state.0 {
+            return Poll::Ready(());
+        }
+        state.1 = Some(cx.waker().clone());
+        drop(state);
+        if !self.started {
+            self.started = true;
+            let (state, delay) = (self.state.clone(), self.delay);
+            thread::spawn(move || {
+                thread::sleep(delay);
+                let mut state = state.lock().unwrap();
+                state.0 = true;
+                if let Some(waker) = state.1.take() {
+                    waker.wake();
+                }
+            });
+        }
+        Poll::Pending
+    }
+}
+
+fn sleep(delay: Duration) -> Delayed {
+    Delayed {
+        delay,
+        state: Default::default(),
+        started: false,
+    }
+}
+
+fn main() {
+    let mut world = World::new(rules![
+        (nat z)
+        (nat (s {x})) {
+            (nat {x})
+        }
+        (even z)
+        (even (s (s {x}))) {
+            (even {x})
+        }
+        (pet {name} {kind}) {
+            (owner {name})
+            (kind_of {name} {kind})
+        }
+        (owner alice)
+        (owner bob)
+    ]);
+    // Looks up each pet's kind in a slow "service".
+    world.register_foreign("kind_of", 2, |args| async move {
+        sleep(Duration::from_millis(10)).await;
+        let kind = match args[0].to_string().as_str() {
+            "alice" => "cat",
+            _ => "dog",
+        };
+        vec![vec![Atom::new(&args[0].to_string()), Atom::new(kind)]]
+    });
+
+    let mut pets = world.stream(&[data! {(pet {name} {kind})}], 100);
+    block_on(async {
+        while let Some(answer) = pets.next_answer().await {
+            println!("{}", answer.unwrap()[0]);
+        }
+    });
+
+    // An endless search still returns control to the executor between inferences.
+    let mut nats = world.stream(&[data! {(nat {n})}, data! {(even {n})}], 1);
+    let mut polls = 0;
+    let waker = Waker::noop();
+    let mut cx = Context::from_waker(waker);
+    let mut answers = vec![];
+    while answers.len() < 3 {
+        polls += 1;
+        if let Poll::Ready(answer) = Pin::new(&mut nats).poll_next(&mut cx) {
+            answers.push(answer.unwrap().unwrap()[0].to_string());
+        }
+    }
+    println!("{} in {} polls", answers.join(", "), polls);
+}
@@ -0,0 +1,40 @@
+#[macro_use]
+extern crate prlg;
+
+use std::{sync::Arc, thread};
+
+use prlg::World;
+
+fn main() {
+    let rules = rules![
+        (add zero {y} {y})
+        (add (s {x}) {y} (s {z})) {
+            (add {x} {y} {z})
+        }
+    ];
+    let world = Arc::new(World::new(rules));
+
+    // One rule base shared by several threads, each running its own queries.
+    let handles: Vec<_> = (0..4)
+        .map(|i| {
+            let world = world.clone();
+            thread::spawn(move || {
+                let mut n = data! {zero};
+                for _ in 0..i {
+                    n = term![sym!(s), n];
+                }
+                let query = world.prepare(&[data! {(add {x} {y} {z})}]);
+                let mut answers = vec![];
+                query
+                    .run(&[("z", n)], |c| answers.push(c[0].to_string()))
+                    .unwrap();
+                answers
+            })
+        })
+        .collect();
+    for (i, handle) in handles.into_iter().enumerate() {
+        let answers = handle.join().unwrap();
+        assert_eq!(answers.len(), i + 1);
+        println!("{}", answers.join(" "));
+    }
+}
@@ -0,0 +1,33 @@
+#[macro_use]
+extern crate prlg;
+
+use std::io;
+
+use prlg::{trace::PrettyTracer, World};
+
+fn main() {
+    let world = World::new(rules![
+        (parent alice bob)
+        (parent bob carol)
+        (parent bob dave)
+        (grandparent {x} {z}) {
+            (parent {x} {y})
+            (parent {y} {z})
+        }
+    ]);
+
+    let query = [data! {(grandparent {Who} dave)}];
+    let mut tracer = PrettyTracer::new(&query, io::stdout());
+    world
+        .run_traced(&query, &mut tracer, |answer| println!("=> {}", answer[0]))
+        .unwrap();
+
+    // With a spy point, only calls of `grandparent/2` are shown.
+    println!();
+    let query = [data! {(grandparent alice {Grandchild})}];
+    let mut tracer = PrettyTracer::new(&query, io::stdout());
+    tracer.spy("grandparent", 2);
+    world
+        .run_traced(&query, &mut tracer, |answer| println!("=> {}", answer[0]))
+        .unwrap();
+}
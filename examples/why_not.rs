@@ -0,0 +1,31 @@
+#[macro_use]
+extern crate prlg;
+
+use prlg::World;
+
+fn main() {
+    let mut world = World::new(rules![
+        (grandparent {x} {z}) {
+            (parent {x} {y})
+            (parent {y} {z})
+        }
+        (parent alice bob)
+        (parent bob carol)
+        (likes (pair {x} {y}) {x})
+    ]);
+    world
+        .load_facts("age", [["alice", "52"], ["bob", "27"]])
+        .unwrap();
+
+    for query in [
+        data! {(grandparent alice dave)},
+        data! {(likes (pair bob carol) carol)},
+        data! {(age bob 72)},
+        data! {(sibling alice {who})},
+    ] {
+        for failure in world.why_not(&[query]).unwrap() {
+            print!("{}", failure);
+        }
+        println!();
+    }
+}
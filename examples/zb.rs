@@ -0,0 +1,182 @@
+#[macro_use]
+extern crate prlg;
+
+use prlg::{world::Mode, World};
+
+// Poor man's sampling profiler: SIGPROF handler records RIP.
+mod sampler {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    const N: usize = 1 << 20;
+    static mut SAMPLES: [usize; N] = [0; N];
+    static mut RETS: [usize; N] = [0; N];
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    #[repr(C)]
+    struct SigAction {
+        handler: usize,
+        flags: u64,
+        restorer: usize,
+        mask: [u64; 16],
+    }
+    #[repr(C)]
+    struct Timeval {
+        sec: i64,
+        usec: i64,
+    }
+    #[repr(C)]
+    struct Itimer {
+        interval: Timeval,
+        value: Timeval,
+    }
+    extern "C" {
+        fn sigaction(sig: i32, act: *const SigAction, old: *mut SigAction) -> i32;
+        fn setitimer(which: i32, new: *const Itimer, old: *mut Itimer) -> i32;
+    }
+    extern "C" fn handler(_sig: i32, _info: usize, ctx: *const u8) {
+        unsafe {
+            let rip = *(ctx.add(168) as *const usize);
+            let i = COUNT.fetch_add(1, Ordering::Relaxed);
+            if i < N {
+                SAMPLES[i] = rip;
+                let rsp = *(ctx.add(160) as *const usize);
+                RETS[i] = *(rsp as *const usize);
+            }
+        }
+    }
+    pub fn start() {
+        unsafe {
+            let act = SigAction {
+                handler: handler as usize,
+                flags: 4 /*SA_SIGINFO*/ | 0x10000000, /*RESTART*/
+                restorer: 0,
+                mask: [0; 16],
+            };
+            sigaction(27, &act, std::ptr::null_mut());
+            let t = Itimer {
+                interval: Timeval { sec: 0, usec: 200 },
+                value: Timeval { sec: 0, usec: 200 },
+            };
+            setitimer(2, &t, std::ptr::null_mut());
+        }
+    }
+    pub fn stop() {
+        unsafe {
+            let t = Itimer {
+                interval: Timeval { sec: 0, usec: 0 },
+                value: Timeval { sec: 0, usec: 0 },
+            };
+            setitimer(2, &t, std::ptr::null_mut());
+            let maps = std::fs::read_to_string("/proc/self/maps").unwrap();
+            let exe = std::fs::read_link("/proc/self/exe").unwrap();
+            let base = maps
+                .lines()
+                .find(|l| l.ends_with(exe.to_str().unwrap()))
+                .map(|l| usize::from_str_radix(l.split('-').next().unwrap(), 16).unwrap())
+                .unwrap();
+            let n = COUNT.load(Ordering::Relaxed).min(N);
+            let mut out = String::new();
+            for i in 0..n {
+                out += &format!("{:x}\n", SAMPLES[i].wrapping_sub(base));
+            }
+            std::fs::write("/tmp/samples.txt", out).unwrap();
+            std::fs::write("/tmp/maps.txt", &maps).unwrap();
+            let mut rets = String::new();
+            for i in 0..n {
+                rets += &format!("{:x} {:x}\n", SAMPLES[i], RETS[i].wrapping_sub(base));
+            }
+            std::fs::write("/tmp/rets.txt", rets).unwrap();
+        }
+    }
+}
+
+fn main() {
+    let rules = rules![
+        (eq {x} {x})
+
+        (member {x} (cons {x} {}))
+        (member {x} (cons {} {xs})) {
+            (member {x} {xs})
+        }
+
+        (nextto {x} {y} {list}) {
+            (iright {x} {y} {list})
+        }
+        (nextto {x} {y} {list}) {
+            (iright {y} {x} {list})
+        }
+
+        (iright {left} {right} (cons {left} (cons {right} {})))
+        (iright {left} {right} (cons {} {rest})) {
+            (iright {left} {right} {rest})
+        }
+
+        (zebra {h} {w} {z}) {
+            (eq {h} [
+                (house norwegian {} {} {} {})
+                {}
+                (house {} {} {} milk {})
+                {}
+                {}
+            ])
+            (member (house englishman {} {} {} red) {h})
+            (member (house spaniard dog {} {} {}) {h})
+            (member (house {} {} {} coffee green) {h})
+            (member (house ukrainian {} {} tea {}) {h})
+            (iright (house {} {} {} {} ivory)
+                    (house {} {} {} {} green) {h})
+            (member (house {} snails winston {} {}) {h})
+            (member (house {} {} kools {} yellow) {h})
+            (nextto (house {} {} chesterfield {} {})
+                    (house {} fox {} {} {}) {h})
+            (nextto (house {} {} kools {} {})
+                    (house {} horse {} {} {}) {h})
+            (member (house {} {} luckystrike oj {}) {h})
+            (member (house japanese {} parliaments {} {}) {h})
+            (nextto (house norwegian {} {} {} {})
+                    (house {} {} {} {} blue) {h})
+            (member (house {w} {} {} water {}) {h})
+            (member (house {z} zebra {} {} {}) {h})
+        }
+    ];
+    let mode = if std::env::args().nth(1).as_deref() == Some("c") {
+        Mode::Compiled
+    } else {
+        Mode::Interpreted
+    };
+    let world = World::with_mode(rules, mode);
+    let prep = world.prepare(&[data! {(zebra {h} {w} {z})}]);
+    if std::env::var("PROF").is_ok() {
+        sampler::start();
+    }
+    if std::env::var("DIST").is_ok() {
+        let mut v = vec![];
+        let t = std::time::Instant::now();
+        for _ in 0..1000 {
+            let s = std::time::Instant::now();
+            let _ = world.run(&[data! {(zebra {h} {w} {z})}], |_| {});
+            v.push(s.elapsed().as_micros());
+        }
+        let total = t.elapsed();
+        v.sort();
+        println!(
+            "total {:?} min {} med {} p90 {} p99 {} max {}",
+            total, v[0], v[500], v[900], v[990], v[999]
+        );
+        return;
+    }
+    let mut best = std::time::Duration::MAX;
+    for _ in 0..(if std::env::var("CNT").is_ok() { 1 } else { 20 }) {
+        let s = std::time::Instant::now();
+        for _ in 0..(if std::env::var("CNT").is_ok() { 1 } else { 50 }) {
+            if std::env::var("PREP").is_ok() {
+                prep.run(&[], |_| {}).unwrap();
+            } else {
+                world.run(&[data! {(zebra {h} {w} {z})}], |_| {}).unwrap();
+            }
+        }
+        best = best.min(s.elapsed());
+    }
+    if std::env::var("PROF").is_ok() {
+        sampler::stop();
+    }
+    println!("{:?} {:?}", mode, best);
+}
@@ -1,7 +1,7 @@
 #[macro_use]
 extern crate prlg;
 
-use prlg::World;
+use prlg::{world::Mode, World};
 
 fn main() {
     let rules = rules![
@@ -52,13 +52,21 @@ fn main() {
             (member (house {z} zebra {} {} {}) {h})
         }
     ];
-    let world = World::new(rules);
+    let mut answers = vec![];
+    for mode in [Mode::Interpreted, Mode::Compiled] {
+        let world = World::with_mode(rules.clone(), mode);
 
-    world.run(&[data! {(zebra {h} {w} {z})}],|c| for d in c {println!("{}", d)});
+        let mut answer = String::new();
+        world.run(&[data! {(zebra {h} {w} {z})}],|c| for d in c {answer += &d.to_string()}).unwrap();
+        println!("{}", answer);
+        answers.push(answer);
 
-    let s = std::time::Instant::now();
-    for _ in 0..1000 {
-        world.run(&[data! {(zebra {h} {w} {z})}],|_| {});
+        let query = world.prepare(&[data! {(zebra {h} {w} {z})}]);
+        let s = std::time::Instant::now();
+        for _ in 0..1000 {
+            query.run(&[], |_| {}).unwrap();
+        }
+        dbg!(mode, s.elapsed());
     }
-    dbg!(s.elapsed());
+    assert_eq!(answers[0], answers[1]);
 }
@@ -0,0 +1,207 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, OnceLock, RwLock,
+    },
+};
+
+use crate::data::Data;
+
+/// Interned symbol. Atoms are shared by every `World` in the process, so the same name
+/// always maps to the same id and comparing symbols is an integer compare. The table is
+/// never freed: names read from untrusted input are capped at `MAX_UNTRUSTED_ATOMS`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Atom(u32);
+
+/// New names that snapshots and fact files may add to the table, over the whole process.
+pub const MAX_UNTRUSTED_ATOMS: usize = 1 << 20;
+
+/// Atoms with fixed ids, in id order.
+const PREDEFINED: &[&str] = &[
+    "nil",
+    "cons",
+    "cut",
+    "@",
+    "=",
+    "unify_with_occurs_check",
+    "par",
+];
+
+impl Atom {
+    pub const NIL: Atom = Atom(0);
+    pub const CONS: Atom = Atom(1);
+    pub const CUT: Atom = Atom(2);
+    pub const AT: Atom = Atom(3);
+    pub const EQ: Atom = Atom(4);
+    pub const UNIFY_WITH_OCCURS_CHECK: Atom = Atom(5);
+    pub const PAR: Atom = Atom(6);
+
+    /// Returns the atom named `name`, adding it to the table if needed.
+    pub fn new(name: &str) -> Atom {
+        if let Some(&id) = table().ids.read().unwrap().get(name) {
+            return Atom(id);
+        }
+        table().intern(name)
+    }
+
+    /// Like `new`, for names read from untrusted input. Returns `None` if the name is new
+    /// and `MAX_UNTRUSTED_ATOMS` names were added this way already.
+    pub(crate) fn untrusted(name: &str) -> Option<Atom> {
+        if let Some(&id) = table().ids.read().unwrap().get(name) {
+            return Some(Atom(id));
+        }
+        table().intern_untrusted(name, MAX_UNTRUSTED_ATOMS)
+    }
+
+    pub fn id(self) -> u32 {
+        self.0
+    }
+
+    pub fn name(self) -> Arc<str> {
+        table().entry(self).name.clone()
+    }
+
+    /// The atom as a symbol that variables can be bound to. Like names, these live as long
+    /// as the process.
+    pub(crate) fn data(self) -> &'static Data {
+        &table().entry(self).symbol
+    }
+}
+
+impl std::fmt::Display for Atom {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl std::fmt::Debug for Atom {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.name())
+    }
+}
+
+/// Atoms in the first chunk of the table; each further chunk is twice as large as the last.
+const FIRST_CHUNK: usize = 64;
+
+/// Enough chunks for every `u32` id.
+const CHUNKS: usize = 27;
+
+struct Entry {
+    name: Arc<str>,
+    symbol: Data,
+}
+
+/// Append-only table of atoms. Entries never move once added, so they are read without
+/// taking a lock; only interning a new name does.
+struct Table {
+    chunks: [OnceLock<Box<[OnceLock<Entry>]>>; CHUNKS],
+    ids: RwLock<HashMap<Arc<str>, u32>>,
+    /// Names added by `intern_untrusted`.
+    untrusted: AtomicUsize,
+}
+
+impl Table {
+    fn new() -> Self {
+        Table {
+            chunks: std::array::from_fn(|_| OnceLock::new()),
+            ids: RwLock::default(),
+            untrusted: AtomicUsize::new(0),
+        }
+    }
+
+    /// Chunk holding `id`, and its index there.
+    fn position(id: u32) -> (usize, usize) {
+        let n = id as usize / FIRST_CHUNK + 1;
+        let chunk = n.ilog2() as usize;
+        (chunk, id as usize - FIRST_CHUNK * ((1 << chunk) - 1))
+    }
+
+    fn entry(&self, atom: Atom) -> &Entry {
+        let (chunk, i) = Self::position(atom.0);
+        // An atom only exists once its entry was added.
+        self.chunks[chunk].get().and_then(|c| c[i].get()).unwrap()
+    }
+
+    fn intern(&self, name: &str) -> Atom {
+        let mut ids = self.ids.write().unwrap();
+        if let Some(&id) = ids.get(name) {
+            return Atom(id);
+        }
+        self.add(&mut ids, name)
+    }
+
+    /// Interns `name` unless it is new and `max` names were added this way already.
+    fn intern_untrusted(&self, name: &str, max: usize) -> Option<Atom> {
+        let mut ids = self.ids.write().unwrap();
+        if let Some(&id) = ids.get(name) {
+            return Some(Atom(id));
+        }
+        if self.untrusted.load(Ordering::Relaxed) >= max {
+            return None;
+        }
+        self.untrusted.fetch_add(1, Ordering::Relaxed);
+        Some(self.add(&mut ids, name))
+    }
+
+    fn add(&self, ids: &mut HashMap<Arc<str>, u32>, name: &str) -> Atom {
+        let id = ids.len() as u32;
+        let name: Arc<str> = name.into();
+        let (chunk, i) = Self::position(id);
+        let chunk = self.chunks[chunk].get_or_init(|| {
+            let len = FIRST_CHUNK << chunk;
+            (0..len).map(|_| OnceLock::new()).collect()
+        });
+        let entry = Entry {
+            name: name.clone(),
+            symbol: Data::Symbol(Atom(id)),
+        };
+        if chunk[i].set(entry).is_err() {
+            unreachable!("atom {id} added twice");
+        }
+        ids.insert(name, id);
+        Atom(id)
+    }
+}
+
+fn table() -> &'static Table {
+    static TABLE: OnceLock<Table> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let table = Table::new();
+        for name in PREDEFINED {
+            table.intern(name);
+        }
+        table
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn positions_fill_chunks_in_order() {
+        let mut expected = (0, 0);
+        for id in 0..100_000 {
+            assert_eq!(Table::position(id), expected, "id {id}");
+            expected.1 += 1;
+            if expected.1 == FIRST_CHUNK << expected.0 {
+                expected = (expected.0 + 1, 0);
+            }
+        }
+        assert_eq!(Table::position(u32::MAX).0, CHUNKS - 1);
+    }
+
+    #[test]
+    fn untrusted_names_are_capped() {
+        let table = Table::new();
+        let a = table.intern("a");
+        assert_eq!(table.intern_untrusted("b", 2), Some(Atom(1)));
+        assert_eq!(table.intern_untrusted("c", 2), Some(Atom(2)));
+        assert_eq!(table.intern_untrusted("d", 2), None);
+        // Names already interned are found past the cap.
+        assert_eq!(table.intern_untrusted("a", 2), Some(a));
+        assert_eq!(table.intern_untrusted("b", 2), Some(Atom(1)));
+        assert_eq!(table.intern("d"), Atom(3));
+    }
+}
@@ -1,6 +1,16 @@
-use std::rc::Rc;
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    sync::RwLock,
+};
 
-use crate::data::Data;
+use crate::{atom::Atom, data::Data};
+
+/// Depth after which walks over two terms start remembering visited pairs to stop on cyclic terms.
+pub(crate) const CYCLE_CHECK_DEPTH: usize = 64;
+
+/// Identity of an instance: the address of its data and its base.
+type InstanceKey = (usize, usize);
 
 #[derive(Debug, Clone, Copy)]
 pub struct Instance<'a> {
@@ -13,39 +23,90 @@ impl<'a> Instance<'a> {
         Instance { data, base }
     }
 
-    pub fn data(&self) -> &Data {
+    pub fn data(&self) -> &'a Data {
         self.data
     }
 
-    pub fn eq(&self, other: &Instance) -> bool {
-        self.base == other.base && self.data as *const Data == other.data
+    pub fn base(&self) -> usize {
+        self.base
     }
+
+    fn key(&self) -> InstanceKey {
+        (self.data as *const Data as usize, self.base)
+    }
+}
+
+impl<'a> PartialEq for Instance<'a> {
+    fn eq(&self, other: &Instance) -> bool {
+        self.base == other.base && std::ptr::eq(self.data, other.data)
+    }
+}
+
+/// How unification treats binding a variable to a term that contains it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OccursCheck {
+    /// Bind anyway, creating a cyclic term.
+    #[default]
+    False,
+    /// Fail the unification.
+    True,
+    /// Abort with the offending term.
+    Error,
+}
+
+/// Bindings made on a snapshot of another store, see `Bindings::delta`.
+#[derive(Clone)]
+pub(crate) struct Delta<'a> {
+    /// Size of the store the snapshot was taken from.
+    len: usize,
+    /// Variables of that store bound since.
+    old: Vec<(usize, Instance<'a>)>,
+    /// Variables allocated since.
+    new: Vec<Option<Instance<'a>>>,
 }
 
 /// Bindings keeps bound variables and enables rewinding to a previous state.
+#[derive(Default)]
 pub struct Bindings<'a> {
     bindings: Vec<Option<Instance<'a>>>,
     indices: Vec<usize>,
     stack: Vec<(usize, usize)>,
+    pending: Vec<Pending<'a>>,
+}
+
+/// Arguments of two terms still to be unified pairwise.
+#[derive(Clone, Copy)]
+struct Pending<'a> {
+    left: &'a [Data],
+    right: &'a [Data],
+    left_base: usize,
+    right_base: usize,
+    depth: usize,
 }
 
 impl<'a> Bindings<'a> {
     pub fn new() -> Self {
-        Self {
-            bindings: Vec::new(),
-            indices: Vec::new(),
-            stack: Vec::new(),
-        }
+        Default::default()
     }
 
     pub fn push(&mut self, size: usize) {
-        let bindings_len = self.bindings.len();
-        self.stack.push((bindings_len, self.indices.len()));
-        self.bindings.resize(bindings_len + size, None);
+        self.mark();
+        self.alloc(size);
     }
 
     pub fn pop(&mut self) {
-        if let Some((bindings_len, indices_len)) = self.stack.pop() {
+        self.undo();
+        self.stack.pop();
+    }
+
+    /// Records the current state so that `undo` and `pop` can rewind to it.
+    pub fn mark(&mut self) {
+        self.stack.push((self.bindings.len(), self.indices.len()));
+    }
+
+    /// Rewinds to the last mark, keeping it.
+    pub fn undo(&mut self) {
+        if let Some(&(bindings_len, indices_len)) = self.stack.last() {
             for idx in &self.indices[indices_len..] {
                 self.bindings[*idx] = None;
             }
@@ -54,6 +115,75 @@ impl<'a> Bindings<'a> {
         }
     }
 
+    /// Forgets the last mark without rewinding, keeping only the trail the mark below needs.
+    pub fn discard(&mut self) {
+        if let Some((_, indices_len)) = self.stack.pop() {
+            let boundary = self.boundary();
+            let mut i = indices_len;
+            for j in indices_len..self.indices.len() {
+                if self.indices[j] < boundary {
+                    self.indices[i] = self.indices[j];
+                    i += 1;
+                }
+            }
+            self.indices.truncate(i);
+        }
+    }
+
+    /// Forgets all marks; bindings made so far become permanent.
+    pub fn commit(&mut self) {
+        self.stack.clear();
+        self.indices.clear();
+    }
+
+    pub(crate) fn marks(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// Allocates `size` unbound variables and returns their base.
+    pub fn alloc(&mut self, size: usize) -> usize {
+        let base = self.bindings.len();
+        self.bindings.resize(base + size, None);
+        base
+    }
+
+    /// Variables below this index were allocated before the last mark and must be trailed.
+    fn boundary(&self) -> usize {
+        self.stack
+            .last()
+            .map_or(0, |(bindings_len, _)| *bindings_len)
+    }
+
+    /// Builds `data` in the store out of shared templates, its variable `n` standing for
+    /// the one at `base + n`, so that the instance returned does not borrow `data`.
+    pub(crate) fn build(&mut self, data: &Data, base: usize) -> Instance<'a> {
+        let mut tasks = vec![];
+        let root = self.build_node(data, base, &mut tasks);
+        while let Some((idx, data)) = tasks.pop() {
+            let instance = self.build_node(data, base, &mut tasks);
+            self.bind(idx, instance);
+        }
+        root
+    }
+
+    /// Builds the outer node of `data`, adding the variables for its arguments to `tasks`.
+    fn build_node<'d>(
+        &mut self,
+        data: &'d Data,
+        base: usize,
+        tasks: &mut Vec<(usize, &'d Data)>,
+    ) -> Instance<'a> {
+        match data {
+            Data::Variable(n) => Instance::new(variable(*n), base),
+            Data::Symbol(s) => Instance::new(s.data(), 0),
+            Data::Term(v) => {
+                let args = self.alloc(v.len());
+                tasks.extend(v.iter().enumerate().map(|(i, arg)| (args + i, arg)));
+                Instance::new(template(v.len()), args)
+            }
+        }
+    }
+
     pub fn instance(&self, data: &'a Data) -> Instance<'a> {
         Instance::new(
             data,
@@ -63,41 +193,211 @@ impl<'a> Bindings<'a> {
         )
     }
 
-    pub fn unify(&mut self, mut left: Instance<'a>, mut right: Instance<'a>) -> bool {
-        left = self.resolve(left);
-        right = self.resolve(right);
+    pub fn unify(&mut self, left: Instance<'a>, right: Instance<'a>) -> bool {
+        matches!(self.unify_with(left, right, OccursCheck::False), Ok(true))
+    }
 
-        if left.eq(&right) {
-            return true;
-        }
+    pub fn unify_with_occurs_check(&mut self, left: Instance<'a>, right: Instance<'a>) -> bool {
+        matches!(self.unify_with(left, right, OccursCheck::True), Ok(true))
+    }
 
-        match (&left.data, &right.data) {
-            (Data::Variable(n), _) => {
-                self.bind(left.base + n, right);
-                true
+    /// Unifies under the given occurs check mode; `OccursCheck::Error` fails with the cyclic term.
+    pub fn unify_with(
+        &mut self,
+        left: Instance<'a>,
+        right: Instance<'a>,
+        occurs_check: OccursCheck,
+    ) -> Result<bool, Data> {
+        let mut pending = std::mem::take(&mut self.pending);
+        pending.clear();
+        let top = Pending {
+            left: std::slice::from_ref(left.data),
+            right: std::slice::from_ref(right.data),
+            left_base: left.base,
+            right_base: right.base,
+            depth: 0,
+        };
+        let result = match occurs_check {
+            OccursCheck::False => self.unify_pending::<false>(top, &mut pending, occurs_check),
+            _ => self.unify_pending::<true>(top, &mut pending, occurs_check),
+        };
+        self.pending = pending;
+        result.map_err(|cyclic| self.data(cyclic))
+    }
+
+    /// Unifies the arguments of `top`, then those left in `pending`.
+    fn unify_pending<const CHECK: bool>(
+        &mut self,
+        mut top: Pending<'a>,
+        pending: &mut Vec<Pending<'a>>,
+        occurs_check: OccursCheck,
+    ) -> Result<bool, Instance<'a>> {
+        let mut visited = None;
+        // The arguments being unified are kept out of `pending`; those after a pair of terms
+        // are only put back if any are left, so that the tails of lists do not pile up.
+        loop {
+            let mut i = 0;
+            while i < top.left.len() {
+                let left = self.resolve(Instance::new(&top.left[i], top.left_base));
+                let right = self.resolve(Instance::new(&top.right[i], top.right_base));
+                i += 1;
+                if left == right {
+                    continue;
+                }
+
+                match (&left.data, &right.data) {
+                    // The younger variable is bound to the older, so that chains do not grow
+                    // through the frames of recursive calls.
+                    (Data::Variable(l), Data::Variable(r)) => {
+                        let (l, r) = (left.base + l, right.base + r);
+                        match l < r {
+                            true => self.bind(r, left),
+                            false => self.bind(l, right),
+                        }
+                    }
+                    (Data::Variable(n), _) if !CHECK => self.bind(left.base + n, right),
+                    (_, Data::Variable(n)) if !CHECK => self.bind(right.base + n, left),
+                    (Data::Variable(n), _) => {
+                        if !self.bind_checked(left.base + n, right, occurs_check)? {
+                            return Ok(false);
+                        }
+                    }
+                    (_, Data::Variable(n)) => {
+                        if !self.bind_checked(right.base + n, left, occurs_check)? {
+                            return Ok(false);
+                        }
+                    }
+
+                    (Data::Symbol(l), Data::Symbol(r)) => {
+                        if l != r {
+                            return Ok(false);
+                        }
+                    }
+
+                    (Data::Term(l), Data::Term(r)) => {
+                        if l.len() != r.len() {
+                            return Ok(false);
+                        }
+                        // Pairs met again on cyclic terms are assumed to unify.
+                        if top.depth >= CYCLE_CHECK_DEPTH
+                            && !visited
+                                .get_or_insert_with(HashSet::new)
+                                .insert((left.key(), right.key()))
+                        {
+                            continue;
+                        }
+                        if i < top.left.len() {
+                            pending.push(Pending {
+                                left: &top.left[i..],
+                                right: &top.right[i..],
+                                ..top
+                            });
+                        }
+                        top = Pending {
+                            left: l,
+                            right: r,
+                            left_base: left.base,
+                            right_base: right.base,
+                            depth: top.depth + 1,
+                        };
+                        i = 0;
+                    }
+
+                    (Data::Symbol(_), Data::Term(_)) => return Ok(false),
+                    (Data::Term(_), Data::Symbol(_)) => return Ok(false),
+                }
             }
-            (_, Data::Variable(n)) => {
-                self.bind(right.base + n, left);
-                true
+            match pending.pop() {
+                Some(next) => top = next,
+                None => return Ok(true),
             }
+        }
+    }
 
-            (Data::Symbol(l), Data::Symbol(r)) => Rc::ptr_eq(l, r),
+    pub(crate) fn bind_checked(
+        &mut self,
+        idx: usize,
+        instance: Instance<'a>,
+        occurs_check: OccursCheck,
+    ) -> Result<bool, Instance<'a>> {
+        if occurs_check != OccursCheck::False && self.occurs(idx, instance) {
+            return match occurs_check {
+                OccursCheck::Error => Err(instance),
+                _ => Ok(false),
+            };
+        }
+        self.bind(idx, instance);
+        Ok(true)
+    }
 
-            (Data::Term(l), Data::Term(r)) => {
-                if l.len() != r.len() {
-                    return false;
+    fn occurs(&self, idx: usize, instance: Instance<'a>) -> bool {
+        let mut visited = HashSet::new();
+        let mut stack = vec![instance];
+        while let Some(instance) = stack.pop() {
+            let instance = self.resolve(instance);
+            match instance.data {
+                Data::Variable(n) => {
+                    if instance.base + n == idx {
+                        return true;
+                    }
+                }
+                Data::Symbol(_) => {}
+                Data::Term(ds) => {
+                    if visited.insert(instance.key()) {
+                        stack.extend(ds.iter().map(|d| Instance::new(d, instance.base)));
+                    }
                 }
-                l.iter().zip(r.iter()).all(|(l, r)| {
-                    self.unify(Instance::new(l, left.base), Instance::new(r, right.base))
-                })
+            }
+        }
+        false
+    }
+
+    /// Compares in the standard order of terms: variables, then symbols, then terms by length.
+    /// Cyclic terms with the same infinite unfolding compare equal.
+    pub fn compare(&self, left: Instance<'a>, right: Instance<'a>) -> Ordering {
+        let mut visited = None;
+        let mut pairs = vec![(left, right, 0)];
+        while let Some((left, right, depth)) = pairs.pop() {
+            let left = self.resolve(left);
+            let right = self.resolve(right);
+            if left == right {
+                continue;
             }
 
-            (Data::Symbol(_), Data::Term(_)) => false,
-            (Data::Term(_), Data::Symbol(_)) => false,
+            let ordering = match (&left.data, &right.data) {
+                (Data::Variable(l), Data::Variable(r)) => (left.base + l).cmp(&(right.base + r)),
+                (Data::Variable(_), _) => Ordering::Less,
+                (_, Data::Variable(_)) => Ordering::Greater,
+                (Data::Symbol(l), Data::Symbol(r)) if l == r => Ordering::Equal,
+                (Data::Symbol(l), Data::Symbol(r)) => l.name().cmp(&r.name()),
+                (Data::Symbol(_), Data::Term(_)) => Ordering::Less,
+                (Data::Term(_), Data::Symbol(_)) => Ordering::Greater,
+                (Data::Term(l), Data::Term(r)) => {
+                    if l.len() == r.len()
+                        && (depth < CYCLE_CHECK_DEPTH
+                            || visited
+                                .get_or_insert_with(HashSet::new)
+                                .insert((left.key(), right.key())))
+                    {
+                        pairs.extend(l.iter().zip(r.iter()).rev().map(|(l, r)| {
+                            (
+                                Instance::new(l, left.base),
+                                Instance::new(r, right.base),
+                                depth + 1,
+                            )
+                        }));
+                    }
+                    l.len().cmp(&r.len())
+                }
+            };
+            if ordering.is_ne() {
+                return ordering;
+            }
         }
+        Ordering::Equal
     }
 
-    fn resolve(&self, mut instance: Instance<'a>) -> Instance<'a> {
+    pub(crate) fn resolve(&self, mut instance: Instance<'a>) -> Instance<'a> {
         loop {
             if let Data::Variable(n) = instance.data {
                 if let Some(i) = self.bindings[instance.base + n] {
@@ -110,6 +410,19 @@ impl<'a> Bindings<'a> {
         instance
     }
 
+    /// Index of the unbound variable that variable `idx` ends at, `None` if it is bound to
+    /// a term or symbol.
+    pub(crate) fn unbound_index(&self, idx: usize) -> Option<usize> {
+        let Some(bound) = self.bindings[idx] else {
+            return Some(idx);
+        };
+        let bound = self.resolve(bound);
+        match bound.data {
+            Data::Variable(n) => Some(bound.base + n),
+            _ => None,
+        }
+    }
+
     pub fn size(&self) -> usize {
         self.bindings.len()
     }
@@ -118,26 +431,389 @@ impl<'a> Bindings<'a> {
         self.bindings[idx].as_ref().map(|&i| self.data(i))
     }
 
+    /// Copies `instance` out of the bindings.
+    /// A cyclic term comes out as `(@ Template [(= {n} Term) ...])`, where each `{n}` stands
+    /// for a subterm that contains itself.
     pub fn data(&self, instance: Instance<'a>) -> Data {
-        match &instance.data {
-            Data::Variable(n) => {
-                if let Some(i) = self.bindings[instance.base + n] {
-                    self.data(i)
-                } else {
-                    instance.data.clone()
+        self.copy_out(instance, false)
+    }
+
+    /// Like `data`, numbering unbound variables by their index in the store, so that
+    /// variables of different instances stay apart.
+    pub(crate) fn data_indexed(&self, instance: Instance<'a>) -> Data {
+        self.copy_out(instance, true)
+    }
+
+    fn copy_out(&self, instance: Instance<'a>, indexed: bool) -> Data {
+        enum Task<'a> {
+            Copy(Instance<'a>),
+            Build(usize, Option<InstanceKey>),
+        }
+
+        let mut cycles = Cycles {
+            next_var: self.size(),
+            ..Default::default()
+        };
+        let mut tasks = vec![Task::Copy(instance)];
+        let mut done: Vec<Data> = Vec::new();
+        while let Some(task) = tasks.pop() {
+            match task {
+                Task::Copy(instance) => match &instance.data {
+                    Data::Variable(n) => {
+                        let Some(bound) = self.bindings[instance.base + n] else {
+                            done.push(match indexed {
+                                true => Data::Variable(instance.base + n),
+                                false => instance.data.clone(),
+                            });
+                            continue;
+                        };
+                        let bound = self.resolve(bound);
+                        let Data::Term(ds) = bound.data else {
+                            tasks.push(Task::Copy(bound));
+                            continue;
+                        };
+                        let key = bound.key();
+                        if let Some(var) = cycles.visit(key) {
+                            done.push(Data::Variable(var));
+                            continue;
+                        }
+                        tasks.push(Task::Build(ds.len(), Some(key)));
+                        tasks.extend(
+                            ds.iter()
+                                .rev()
+                                .map(|d| Task::Copy(Instance::new(d, bound.base))),
+                        );
+                    }
+                    Data::Term(ds) => {
+                        tasks.push(Task::Build(ds.len(), None));
+                        // The arguments of a built term stand for the terms they are bound to.
+                        let built = is_template(instance.data);
+                        tasks.extend(ds.iter().enumerate().rev().map(|(i, d)| {
+                            let arg = built.then(|| self.bindings[instance.base + i]).flatten();
+                            Task::Copy(arg.unwrap_or(Instance::new(d, instance.base)))
+                        }));
+                    }
+                    _ => done.push(instance.data.clone()),
+                },
+                Task::Build(len, key) => {
+                    let data = Data::Term(done.split_off(done.len() - len).into());
+                    done.push(match key {
+                        Some(key) => cycles.leave(key, data),
+                        None => data,
+                    });
+                }
+            }
+        }
+        let data = done.pop().unwrap();
+
+        if cycles.substitutions.is_empty() {
+            return data;
+        }
+        let substitutions = cycles
+            .substitutions
+            .into_iter()
+            .rev()
+            .fold(Data::Symbol(Atom::NIL), |list, d| {
+                Data::Term(vec![Data::Symbol(Atom::CONS), d, list].into())
+            });
+        Data::Term(vec![Data::Symbol(Atom::AT), data, substitutions].into())
+    }
+
+    /// Copies `instances` out of the store as currently bound, or as bound when the `mark`th
+    /// mark was made, numbering their unbound variables jointly from 0.
+    /// Returns `None` if one is cyclic.
+    pub(crate) fn copy(
+        &self,
+        mark: Option<usize>,
+        instances: &[Instance<'a>],
+    ) -> Option<Vec<Data>> {
+        enum Task<'a> {
+            Copy(Instance<'a>),
+            Build(usize, InstanceKey),
+        }
+
+        // Variables older than the mark are trailed when bound after it.
+        let (bindings_len, indices_len) = match mark {
+            Some(mark) => self.stack[mark],
+            None => (self.bindings.len(), self.indices.len()),
+        };
+        let later: HashSet<_> = self.indices[indices_len..].iter().collect();
+        let resolve = |mut instance: Instance<'a>| {
+            while let Data::Variable(n) = instance.data {
+                let idx = instance.base + n;
+                match self.bindings[idx] {
+                    Some(bound) if idx < bindings_len && !later.contains(&idx) => instance = bound,
+                    _ => break,
+                }
+            }
+            instance
+        };
+        let mut vars = HashMap::new();
+        let mut path = HashSet::new();
+        let mut tasks: Vec<_> = instances.iter().rev().map(|&i| Task::Copy(i)).collect();
+        let mut done = Vec::new();
+        while let Some(task) = tasks.pop() {
+            match task {
+                Task::Copy(instance) => {
+                    let instance = resolve(instance);
+                    match instance.data {
+                        Data::Variable(n) => {
+                            let next = vars.len();
+                            let var = *vars.entry(instance.base + n).or_insert(next);
+                            done.push(Data::Variable(var));
+                        }
+                        Data::Term(ds) => {
+                            let key = instance.key();
+                            if !path.insert(key) {
+                                return None;
+                            }
+                            tasks.push(Task::Build(ds.len(), key));
+                            tasks.extend(
+                                ds.iter()
+                                    .rev()
+                                    .map(|d| Task::Copy(Instance::new(d, instance.base))),
+                            );
+                        }
+                        data => done.push(data.clone()),
+                    }
+                }
+                Task::Build(len, key) => {
+                    path.remove(&key);
+                    let data = Data::Term(done.split_off(done.len() - len).into());
+                    done.push(data);
+                }
+            }
+        }
+        Some(done)
+    }
+
+    /// Moves the variables reachable from `roots` into a fresh region and rewrites the bases
+    /// of `roots` to match, dropping everything else. Bound variables are rebound to the end
+    /// of their chain, so the variables the chain went through are dropped too.
+    /// Only valid when no mark could rewind to an older state, i.e. the trail is empty.
+    pub(crate) fn compact(&mut self, roots: &mut [&mut Instance<'a>]) {
+        debug_assert!(self.indices.is_empty());
+        for root in roots.iter_mut() {
+            **root = self.resolve(**root);
+        }
+        // One more than the extent of every live frame, indexed by its base.
+        let mut extents = vec![0; self.bindings.len() + 1];
+        let mut visited = vec![false; self.bindings.len()];
+        let mut instances: Vec<Instance<'a>> = roots.iter().map(|i| **i).collect();
+        let mut stack = Vec::new();
+        while let Some(instance) = instances.pop() {
+            let extent = &mut extents[instance.base];
+            *extent = (*extent).max(1);
+            stack.push(instance.data);
+            while let Some(data) = stack.pop() {
+                match data {
+                    Data::Variable(n) => {
+                        *extent = (*extent).max(n + 2);
+                        let idx = instance.base + n;
+                        if !visited[idx] {
+                            visited[idx] = true;
+                            if let Some(bound) = self.bindings[idx] {
+                                let end = self.resolve(bound);
+                                self.bindings[idx] = Some(end);
+                                instances.push(end);
+                            }
+                        }
+                    }
+                    Data::Symbol(_) => {}
+                    Data::Term(v) => stack.extend(v.iter()),
+                }
+            }
+        }
+
+        // Live frames keep their order, so the standard order of variables is preserved.
+        let mut relocation = extents;
+        let mut bindings = Vec::new();
+        for (base, slot) in relocation.iter_mut().enumerate() {
+            let Some(extent) = slot.checked_sub(1) else {
+                continue;
+            };
+            *slot = bindings.len();
+            bindings.extend((base..base + extent).map(|i| self.bindings[i].filter(|_| visited[i])));
+        }
+        let relocate = |i: Instance<'a>| Instance::new(i.data, relocation[i.base]);
+        for binding in bindings.iter_mut().flatten() {
+            *binding = relocate(*binding);
+        }
+        for root in roots.iter_mut() {
+            **root = relocate(**root);
+        }
+        self.bindings = bindings;
+        for (bindings_len, _) in self.stack.iter_mut() {
+            *bindings_len = 0;
+        }
+    }
+
+    /// Copies the variables into a store without marks, for searching on its own.
+    pub(crate) fn snapshot(&self) -> Self {
+        Bindings {
+            bindings: self.bindings.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Bindings made since the first mark of a snapshot, i.e. a solution found on it.
+    pub(crate) fn delta(&self) -> Delta<'a> {
+        let (len, indices_len) = self.stack[0];
+        Delta {
+            len,
+            old: self.indices[indices_len..]
+                .iter()
+                .filter(|&&idx| idx < len)
+                .map(|&idx| (idx, self.bindings[idx].unwrap()))
+                .collect(),
+            new: self.bindings[len..].to_vec(),
+        }
+    }
+
+    /// Makes the bindings of `delta` here, moving the variables it allocated to the top.
+    pub(crate) fn apply(&mut self, delta: &Delta<'a>) {
+        let base = self.alloc(delta.new.len());
+        let relocate = |i: Instance<'a>| match i.base < delta.len {
+            true => i,
+            false => Instance::new(i.data, i.base - delta.len + base),
+        };
+        for (i, instance) in delta.new.iter().enumerate() {
+            self.bindings[base + i] = instance.map(relocate);
+        }
+        for &(idx, instance) in &delta.old {
+            self.bind(idx, relocate(instance));
+        }
+    }
+
+    /// Adds the unbound variables reachable from `instance` to `vars`.
+    pub(crate) fn unbound_vars(&self, instance: Instance<'a>, vars: &mut HashSet<usize>) {
+        let mut instances = vec![instance];
+        let mut seen = HashSet::new();
+        while let Some(instance) = instances.pop() {
+            let instance = self.resolve(instance);
+            match instance.data {
+                Data::Variable(n) => {
+                    vars.insert(instance.base + n);
+                }
+                Data::Term(ds) if seen.insert(instance.key()) => {
+                    instances.extend(ds.iter().map(|d| Instance::new(d, instance.base)))
                 }
+                _ => {}
             }
-            Data::Term(ds) => Data::Term(
-                ds.iter()
-                    .map(|d| self.data(Instance::new(d, instance.base)))
-                    .collect(),
-            ),
-            _ => instance.data.clone(),
         }
     }
 
-    fn bind(&mut self, idx: usize, instance: Instance<'a>) {
+    pub(crate) fn bind(&mut self, idx: usize, instance: Instance<'a>) {
         self.bindings[idx] = Some(instance);
-        self.indices.push(idx);
+        if idx < self.boundary() {
+            self.indices.push(idx);
+        }
+    }
+
+    /// Unifies variable `idx` with `instance`, binding it directly if it is unbound.
+    pub(crate) fn unify_variable(&mut self, idx: usize, instance: Instance<'a>) -> bool {
+        match self.bindings[idx] {
+            Some(bound) => self.unify(bound, instance),
+            None => {
+                self.bind(idx, instance);
+                true
+            }
+        }
+    }
+}
+
+/// State of `Bindings::data` for detecting terms that contain themselves.
+#[derive(Default)]
+struct Cycles {
+    path: HashSet<InstanceKey>,
+    markers: HashMap<InstanceKey, usize>,
+    done: HashMap<InstanceKey, usize>,
+    substitutions: Vec<Data>,
+    next_var: usize,
+}
+
+impl Cycles {
+    /// Enters the bound term `key`, or returns the variable standing for it if it is cyclic.
+    fn visit(&mut self, key: InstanceKey) -> Option<usize> {
+        if let Some(&var) = self.done.get(&key) {
+            return Some(var);
+        }
+        if self.path.insert(key) {
+            return None;
+        }
+        let next_var = &mut self.next_var;
+        Some(*self.markers.entry(key).or_insert_with(|| {
+            *next_var += 1;
+            *next_var - 1
+        }))
+    }
+
+    /// Leaves the bound term `key` copied as `data`.
+    fn leave(&mut self, key: InstanceKey, data: Data) -> Data {
+        self.path.remove(&key);
+        let Some(var) = self.markers.remove(&key) else {
+            return data;
+        };
+        self.substitutions.push(Data::Term(
+            vec![Data::Symbol(Atom::EQ), Data::Variable(var), data].into(),
+        ));
+        self.done.insert(key, var);
+        Data::Variable(var)
+    }
+}
+
+/// The term `({0} {1} ... {len - 1})`, made once for each length and kept for good.
+pub(crate) fn template(len: usize) -> &'static Data {
+    leaked(&TEMPLATES, len, || {
+        Data::Term((0..len).map(Data::Variable).collect())
+    })
+}
+
+static TEMPLATES: Leaked = RwLock::new(Vec::new());
+
+fn is_template(data: &Data) -> bool {
+    let Data::Term(ds) = data else { return false };
+    matches!(ds.first(), Some(Data::Variable(0)))
+        && matches!(TEMPLATES.read().unwrap().get(ds.len()), Some(Some(t)) if std::ptr::eq(*t, data))
+}
+
+/// `Data::Variable(n)`, kept so that `build` numbers variables as `data` does.
+pub(crate) fn variable(n: usize) -> &'static Data {
+    static VARIABLES: Leaked = RwLock::new(Vec::new());
+    leaked(&VARIABLES, n, || Data::Variable(n))
+}
+
+type Leaked = RwLock<Vec<Option<&'static Data>>>;
+
+fn leaked(cache: &Leaked, idx: usize, make: impl FnOnce() -> Data) -> &'static Data {
+    if let Some(&Some(data)) = cache.read().unwrap().get(idx) {
+        return data;
+    }
+    let mut cache = cache.write().unwrap();
+    if cache.len() <= idx {
+        cache.resize(idx + 1, None);
+    }
+    cache[idx].get_or_insert_with(|| Box::leak(Box::new(make())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::atom::Atom;
+
+    #[test]
+    fn compact_collapses_chains() {
+        let (var, a) = (Data::Variable(0), Data::Symbol(Atom::new("a")));
+        let root = Data::Term([Data::Symbol(Atom::new("f")), var.clone()].into());
+        let mut bindings = Bindings::new();
+        // A variable in each of three frames, each bound to the one in the frame before.
+        let bases: Vec<_> = (0..3).map(|_| bindings.alloc(1)).collect();
+        bindings.bind(bases[0], Instance::new(&a, bases[0]));
+        bindings.bind(bases[1], Instance::new(&var, bases[0]));
+        bindings.bind(bases[2], Instance::new(&var, bases[1]));
+        let mut root = Instance::new(&root, bases[2]);
+        bindings.compact(&mut [&mut root]);
+        assert_eq!(bindings.size(), 1);
+        assert_eq!(bindings.data(root).to_string(), "(f a)");
     }
 }
@@ -1,80 +1,185 @@
-use std::rc::Rc;
+use std::collections::HashMap;
 
-#[derive(Debug, Clone)]
+use crate::atom::Atom;
+
+#[derive(Debug)]
 pub enum Data {
     Variable(usize),
-    Symbol(Rc<String>),
+    Symbol(Atom),
     Term(Box<[Data]>),
 }
 
 impl Data {
-    pub(crate) fn max_var(&self) -> usize {
+    /// One more than the highest variable number in `self`, or 0 if it has none.
+    pub fn max_var(&self) -> usize {
+        let mut max = 0;
+        let mut stack = vec![self];
+        while let Some(data) = stack.pop() {
+            match data {
+                Data::Variable(n) => max = max.max(*n + 1),
+                Data::Symbol(_) => {}
+                Data::Term(v) => stack.extend(v.iter()),
+            }
+        }
+        max
+    }
+
+    /// Renumbers the variables found in `names`.
+    pub(crate) fn rename_vars(&mut self, names: &HashMap<usize, usize>) {
+        let mut stack = vec![self];
+        while let Some(data) = stack.pop() {
+            match data {
+                Data::Variable(n) => *n = names.get(n).copied().unwrap_or(*n),
+                Data::Symbol(_) => {}
+                Data::Term(v) => stack.extend(v.iter_mut()),
+            }
+        }
+    }
+
+    pub fn as_symbol(&self) -> Option<Atom> {
         match self {
-            Data::Variable(n) => *n + 1,
-            Data::Symbol(_) => 0,
-            Data::Term(v) => v.iter().map(|x| x.max_var()).max().unwrap_or(0),
+            Data::Symbol(s) => Some(*s),
+            _ => None,
         }
     }
 
-    pub fn as_symbol(&self) -> Option<&Rc<String>> {
+    fn is_symbol(&self, atom: Atom) -> bool {
+        matches!(self, Data::Symbol(s) if *s == atom)
+    }
+
+    /// Returns the head and tail of a `(cons head tail)` cell.
+    fn as_cons(&self) -> Option<(&Data, &Data)> {
         match self {
-            Data::Symbol(s) => Some(s),
+            Data::Term(v) if v.len() == 3 && v[0].is_symbol(Atom::CONS) => Some((&v[1], &v[2])),
             _ => None,
         }
     }
 }
 
-impl std::fmt::Display for Data {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Data::Variable(n) => write!(f, "{{{}}}", n),
-            Data::Symbol(s) => write!(f, "{}", s),
-            Data::Term(v) => {
-                if v.first()
-                    .map(|d| {
-                        if let Data::Symbol(s) = d {
-                            s.as_str() == "cons"
-                        } else {
-                            false
-                        }
-                    })
-                    .unwrap_or(false)
-                {
-                    fn g(
-                        f: &mut std::fmt::Formatter<'_>,
-                        d: &Data,
-                        first: bool,
-                    ) -> std::fmt::Result {
-                        if let Data::Symbol(s) = d {
-                            if s.as_str() == "nil" {
-                                return write!(f, "]");
-                            }
-                        }
-                        if !first {
-                            write!(f, " ")?;
-                        }
-                        if let Data::Term(v) = d {
-                            if let Some(Data::Symbol(s)) = v.first() {
-                                if v.len() == 3 && s.as_str() == "cons" {
-                                    write!(f, "{}", &v[1])?;
-                                    return g(f, &v[2], false);
-                                }
-                            }
+impl Clone for Data {
+    fn clone(&self) -> Self {
+        enum Task<'a> {
+            Clone(&'a Data),
+            Build(usize),
+        }
+
+        let mut tasks = vec![Task::Clone(self)];
+        let mut done: Vec<Data> = Vec::new();
+        while let Some(task) = tasks.pop() {
+            match task {
+                Task::Clone(Data::Variable(n)) => done.push(Data::Variable(*n)),
+                Task::Clone(Data::Symbol(s)) => done.push(Data::Symbol(*s)),
+                Task::Clone(Data::Term(v)) => {
+                    tasks.push(Task::Build(v.len()));
+                    tasks.extend(v.iter().rev().map(Task::Clone));
+                }
+                Task::Build(len) => {
+                    let v = done.split_off(done.len() - len);
+                    done.push(Data::Term(v.into()));
+                }
+            }
+        }
+        done.pop().unwrap()
+    }
+}
+
+impl Drop for Data {
+    fn drop(&mut self) {
+        // Detach nested terms onto a heap stack so that dropping long lists does not recurse.
+        let Data::Term(v) = self else {
+            return;
+        };
+        if !v.iter().any(|d| matches!(d, Data::Term(_))) {
+            return;
+        }
+        let mut stack = vec![std::mem::take(v)];
+        while let Some(mut v) = stack.pop() {
+            for d in v.iter_mut() {
+                if let Data::Term(v) = d {
+                    stack.push(std::mem::take(v));
+                }
+            }
+        }
+    }
+}
+
+impl Data {
+    /// Displays the data with each variable written by `var`.
+    pub fn display_with<'d, F>(&'d self, var: F) -> impl std::fmt::Display + 'd
+    where
+        F: Fn(&mut std::fmt::Formatter<'_>, usize) -> std::fmt::Result + 'd,
+    {
+        struct Display<'d, F>(&'d Data, F);
+
+        impl<F> std::fmt::Display for Display<'_, F>
+        where
+            F: Fn(&mut std::fmt::Formatter<'_>, usize) -> std::fmt::Result,
+        {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                self.0.write(f, &self.1)
+            }
+        }
+
+        Display(self, var)
+    }
+
+    fn write(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        var: &dyn Fn(&mut std::fmt::Formatter<'_>, usize) -> std::fmt::Result,
+    ) -> std::fmt::Result {
+        enum Task<'a> {
+            Data(&'a Data),
+            List(&'a Data, bool),
+            Text(&'static str),
+        }
+
+        let mut tasks = vec![Task::Data(self)];
+        while let Some(task) = tasks.pop() {
+            match task {
+                Task::Data(Data::Variable(n)) => var(f, *n)?,
+                Task::Data(Data::Symbol(s)) => write!(f, "{}", s)?,
+                Task::Data(d @ Data::Term(v)) => {
+                    if d.as_cons().is_some() {
+                        write!(f, "[")?;
+                        tasks.push(Task::List(d, true));
+                        continue;
+                    }
+                    write!(f, "(")?;
+                    tasks.push(Task::Text(")"));
+                    for (i, d) in v.iter().enumerate().rev() {
+                        tasks.push(Task::Data(d));
+                        if i != 0 {
+                            tasks.push(Task::Text(" "));
                         }
-                        write!(f, ". {}]", d)
                     }
-                    write!(f, "[")?;
-                    return g(f, self, true);
                 }
-                write!(f, "(")?;
-                if let Some(d) = v.first() {
-                    write!(f, "{}", d)?;
-                    for d in &v[1..] {
-                        write!(f, " {}", d)?;
+                Task::List(d, first) => {
+                    if d.is_symbol(Atom::NIL) {
+                        write!(f, "]")?;
+                        continue;
+                    }
+                    if !first {
+                        write!(f, " ")?;
+                    }
+                    if let Some((head, tail)) = d.as_cons() {
+                        tasks.push(Task::List(tail, false));
+                        tasks.push(Task::Data(head));
+                    } else {
+                        write!(f, ". ")?;
+                        tasks.push(Task::Text("]"));
+                        tasks.push(Task::Data(d));
                     }
                 }
-                write!(f, ")")
+                Task::Text(s) => write!(f, "{}", s)?,
             }
         }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Data {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.write(f, &|f, n| write!(f, "{{{}}}", n))
     }
 }
@@ -0,0 +1,181 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+};
+
+use crate::{
+    atom::Atom,
+    data::Data,
+    rng::Rng,
+    runtime::Error,
+    strategy::{self, Choice, State},
+    trace::predicate,
+    user_data::UserData,
+    world::{ClauseOrder, VariableScope, World},
+};
+
+/// Writes the SLD tree of `data_slice` as a Graphviz digraph. Each node is a resolvent's
+/// goal list, each edge the clause, fact row, foreign answer or builtin resolving its first
+/// goal. Success leaves show the answer and failure leaves are filled red. Resolvents are
+/// expanded in the order depth-first search takes, at most `max_steps` of them; those left
+/// are drawn dashed. Cut does not prune the tree.
+pub fn search_tree(
+    world: &World,
+    data_slice: &[UserData],
+    max_steps: usize,
+) -> Result<String, Error> {
+    let goals = VariableScope::new().new_data_vec(data_slice);
+    let mut rng = match world.clause_order {
+        ClauseOrder::Source => None,
+        ClauseOrder::Random { seed } => Some(Rng::new(seed)),
+    };
+    let mut out = String::from("digraph search_tree {\n    node [shape=box];\n");
+    let mut stack = vec![(0, State::query(&goals)?)];
+    let (mut next_id, mut steps) = (1, 0);
+    while let Some((id, state)) = stack.pop() {
+        if state.goals.is_empty() {
+            let answer = join(state.template.iter().rev());
+            let label = escape(&format!("yes: {}", answer));
+            let _ = writeln!(
+                out,
+                "    n{} [label=\"{}\", style=filled, fillcolor=palegreen];",
+                id, label
+            );
+            continue;
+        }
+        let label = escape(&join(&state.goals));
+        if steps == max_steps {
+            let _ = writeln!(out, "    n{} [label=\"{}\", style=dashed];", id, label);
+            continue;
+        }
+        steps += 1;
+        let mut children = vec![];
+        strategy::expand(world, &state, &mut rng, |choice, s| {
+            children.push((choice, s))
+        })?;
+        if children.is_empty() {
+            let _ = writeln!(
+                out,
+                "    n{} [label=\"{}\", style=filled, fillcolor=lightpink];",
+                id, label
+            );
+            continue;
+        }
+        let _ = writeln!(out, "    n{} [label=\"{}\"];", id, label);
+        let len = stack.len();
+        for (choice, child) in children {
+            let choice = match choice {
+                Choice::Rule(i) => format!("clause {}", i),
+                Choice::Row(row) => format!("fact row {}", row),
+                Choice::Foreign => "foreign".to_owned(),
+                Choice::Builtin => {
+                    predicate(&state.goals[0]).map_or(String::new(), |(name, _)| name.to_string())
+                }
+            };
+            let _ = writeln!(
+                out,
+                "    n{} -> n{} [label=\"{}\"];",
+                id,
+                next_id,
+                escape(&choice)
+            );
+            stack.push((next_id, child));
+            next_id += 1;
+        }
+        stack[len..].reverse();
+    }
+    out.push_str("}\n");
+    Ok(out)
+}
+
+/// How a predicate of a call graph is defined.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Definition {
+    Clauses,
+    Facts,
+    Foreign,
+    Undefined,
+}
+
+/// Writes the predicate call graph of `world` as a Graphviz digraph, with an edge from the
+/// predicate of each clause head to each predicate its body calls, also through `par`.
+/// Predicates defined by fact tables or foreign functions are drawn as such, and ones
+/// called without being defined are drawn dashed red.
+pub fn call_graph(world: &World) -> String {
+    let mut predicates: Vec<((Atom, usize), Definition)> = vec![];
+    let mut ids = HashMap::new();
+    let mut node = |predicate, definition| {
+        *ids.entry(predicate).or_insert_with(|| {
+            predicates.push((predicate, definition));
+            predicates.len() - 1
+        })
+    };
+    let mut facts: Vec<_> = world.facts.keys().map(|k| k.predicate()).collect();
+    let mut foreign: Vec<_> = world.foreign_keys().map(|k| k.predicate()).collect();
+    facts.sort_by_key(|&(name, arity)| (name.name(), arity));
+    foreign.sort_by_key(|&(name, arity)| (name.name(), arity));
+    for rule in &world.rules {
+        if let Some(p) = predicate(&rule.head) {
+            node(p, Definition::Clauses);
+        }
+    }
+    for p in facts {
+        node(p, Definition::Facts);
+    }
+    for p in foreign {
+        node(p, Definition::Foreign);
+    }
+
+    let mut edges = vec![];
+    let mut seen = HashSet::new();
+    for rule in &world.rules {
+        let Some(caller) = predicate(&rule.head) else {
+            continue;
+        };
+        let caller = node(caller, Definition::Clauses);
+        // Bodies are stored last goal first.
+        let mut goals: Vec<_> = rule.body.iter().collect();
+        while let Some(goal) = goals.pop() {
+            match predicate(goal) {
+                Some((Atom::CUT, 0)) | Some((Atom::UNIFY_WITH_OCCURS_CHECK, 2)) | None => {}
+                Some((Atom::PAR, n)) if n > 0 => {
+                    let Data::Term(v) = goal else { unreachable!() };
+                    goals.extend(v[1..].iter().rev());
+                }
+                Some(callee) => {
+                    let callee = node(callee, Definition::Undefined);
+                    if seen.insert((caller, callee)) {
+                        edges.push((caller, callee));
+                    }
+                }
+            }
+        }
+    }
+
+    let mut out = String::from("digraph call_graph {\n");
+    for (id, ((name, arity), definition)) in predicates.iter().enumerate() {
+        let style = match definition {
+            Definition::Clauses => "",
+            Definition::Facts => ", shape=cylinder",
+            Definition::Foreign => ", shape=component",
+            Definition::Undefined => ", style=dashed, color=red",
+        };
+        let label = escape(&format!("{}/{}", name.name(), arity));
+        let _ = writeln!(out, "    p{} [label=\"{}\"{}];", id, label, style);
+    }
+    for (caller, callee) in edges {
+        let _ = writeln!(out, "    p{} -> p{};", caller, callee);
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn join<'d>(data: impl IntoIterator<Item = &'d Data>) -> String {
+    let data: Vec<_> = data.into_iter().map(|d| d.to_string()).collect();
+    data.join(", ")
+}
+
+/// Escapes `text` for a quoted DOT string.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
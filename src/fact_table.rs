@@ -0,0 +1,410 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    io::{self, BufRead},
+    sync::OnceLock,
+};
+
+use crate::{
+    atom::Atom,
+    bindings::{Bindings, Instance},
+    data::Data,
+    rng::Rng,
+    rule_map::{Key, KeyMap},
+};
+
+/// Tables with fewer rows than this are scanned instead of indexed.
+const INDEX_MIN_ROWS: usize = 8;
+
+/// Rows of a table grouped by the fact symbol id in one column.
+type ColumnIndex = HashMap<u32, Vec<u32>>;
+
+/// Ground facts of one predicate stored column by column as fact symbol ids.
+pub struct FactTable {
+    columns: Box<[Vec<u32>]>,
+    indexes: Box<[OnceLock<ColumnIndex>]>,
+    /// Number of rows, kept apart from the columns for tables without any.
+    len: usize,
+}
+
+impl FactTable {
+    fn new(arity: usize) -> Self {
+        FactTable {
+            columns: (0..arity).map(|_| Vec::new()).collect(),
+            indexes: (0..arity).map(|_| OnceLock::new()).collect(),
+            len: 0,
+        }
+    }
+
+    pub fn arity(&self) -> usize {
+        self.columns.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn push(&mut self, ids: &[u32]) {
+        for (column, &id) in self.columns.iter_mut().zip(ids) {
+            column.push(id);
+        }
+        self.len += 1;
+        for index in self.indexes.iter_mut() {
+            index.take();
+        }
+    }
+
+    /// Drops the rows from `len` on.
+    fn truncate(&mut self, len: usize) {
+        for column in self.columns.iter_mut() {
+            column.truncate(len);
+        }
+        self.len = len;
+        for index in self.indexes.iter_mut() {
+            index.take();
+        }
+    }
+
+    fn index(&self, column: usize) -> &ColumnIndex {
+        self.indexes[column].get_or_init(|| {
+            let mut index = ColumnIndex::new();
+            for (row, &id) in self.columns[column].iter().enumerate() {
+                index.entry(id).or_default().push(row as u32);
+            }
+            index
+        })
+    }
+}
+
+/// Fact tables of a `World` and the symbols their cells refer to.
+#[derive(Default)]
+pub struct FactBase {
+    symbols: Vec<Data>,
+    ids: HashMap<Atom, u32>,
+    tables: KeyMap<FactTable>,
+}
+
+impl FactBase {
+    pub fn table(&self, name: Atom, arity: usize) -> Option<&FactTable> {
+        self.tables.get(&Key::term(name, arity + 1))
+    }
+
+    pub(crate) fn keys(&self) -> impl Iterator<Item = Key> + '_ {
+        self.tables.keys().copied()
+    }
+
+    /// The cell of `table`, one of this base's, at `row` and `column`.
+    pub(crate) fn cell(&self, table: &FactTable, row: usize, column: usize) -> &Data {
+        &self.symbols[table.columns[column][row] as usize]
+    }
+
+    fn intern(&mut self, atom: Atom) -> u32 {
+        *self.ids.entry(atom).or_insert_with(|| {
+            self.symbols.push(Data::Symbol(atom));
+            self.symbols.len() as u32 - 1
+        })
+    }
+
+    /// Appends rows to the table `name`, returning the number of rows loaded. On error no
+    /// row is loaded. Cells are interned by `atom`, which may refuse them.
+    pub(crate) fn load<I, R, S>(
+        &mut self,
+        name: &str,
+        rows: I,
+        atom: fn(&str) -> Option<Atom>,
+    ) -> io::Result<usize>
+    where
+        I: IntoIterator<Item = io::Result<R>>,
+        R: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let name = Atom::new(name);
+        // The table the rows go to, with its length before them.
+        let mut loading: Option<(Key, usize)> = None;
+        let mut count = 0;
+        let mut ids = Vec::new();
+        let load = |facts: &mut Self, loading: &mut Option<(Key, usize)>| {
+            for row in rows {
+                ids.clear();
+                for cell in row? {
+                    let Some(atom) = atom(cell.as_ref()) else {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("row {} has too many new symbols", count),
+                        ));
+                    };
+                    ids.push(facts.intern(atom));
+                }
+                let &mut (key, _) = loading.get_or_insert_with(|| {
+                    let key = Key::term(name, ids.len() + 1);
+                    (key, facts.tables.get(&key).map_or(0, |t| t.len()))
+                });
+                let arity = key.predicate().1;
+                if ids.len() != arity {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("row {} has {} fields, expected {}", count, ids.len(), arity),
+                    ));
+                }
+                facts
+                    .tables
+                    .entry(key)
+                    .or_insert_with(|| FactTable::new(arity))
+                    .push(&ids);
+                count += 1;
+            }
+            Ok(count)
+        };
+        let result = load(self, &mut loading);
+        if let (Err(_), Some((key, len))) = (&result, loading) {
+            match len {
+                0 => self.tables.remove(&key),
+                len => {
+                    self.tables.get_mut(&key).unwrap().truncate(len);
+                    None
+                }
+            };
+        }
+        result
+    }
+
+    /// Up to `max` rows of `table` to compare with a goal of arguments `args`, first those
+    /// that match the longest run of its leading symbol arguments, found through the column
+    /// indexes.
+    pub(crate) fn nearest_rows(&self, table: &FactTable, args: &[Data], max: usize) -> Vec<usize> {
+        if table.len() < INDEX_MIN_ROWS.max(max) {
+            return (0..table.len()).collect();
+        }
+        // Rows matching the first one, two, ... symbol arguments, each list filtered from
+        // the shorter of the one before and the column's index.
+        let mut bound = vec![];
+        let mut levels: Vec<Cow<[u32]>> = vec![];
+        for (column, arg) in args.iter().enumerate() {
+            let id = match arg {
+                Data::Variable(_) => continue,
+                Data::Symbol(s) => self.ids.get(s).copied(),
+                Data::Term(_) => None,
+            };
+            let Some(id) = id else {
+                break;
+            };
+            bound.push((column, id));
+            let indexed = table
+                .index(column)
+                .get(&id)
+                .map_or(&[][..], |v| v.as_slice());
+            let rows = match levels.last() {
+                None => Cow::Borrowed(indexed),
+                Some(last) => {
+                    let shorter = if last.len() < indexed.len() {
+                        last
+                    } else {
+                        indexed
+                    };
+                    let matching = |&&row: &&u32| {
+                        bound
+                            .iter()
+                            .all(|&(c, id)| table.columns[c][row as usize] == id)
+                    };
+                    Cow::Owned(shorter.iter().filter(matching).copied().collect())
+                }
+            };
+            if rows.is_empty() {
+                break;
+            }
+            levels.push(rows);
+        }
+        let mut seen = HashSet::new();
+        levels
+            .iter()
+            .rev()
+            .flat_map(|rows| rows.iter().map(|&row| row as usize))
+            .chain(0..table.len())
+            .filter(|&row| seen.insert(row))
+            .take(max)
+            .collect()
+    }
+
+    /// Returns the rows that may match `goal` of predicate `key`, using the most selective
+    /// bound column.
+    pub(crate) fn get<'a>(
+        &'a self,
+        bindings: &Bindings<'a>,
+        goal: Instance<'a>,
+        key: Option<Key>,
+    ) -> Rows<'a> {
+        if self.tables.is_empty() {
+            return Rows::empty();
+        }
+        let goal = bindings.resolve(goal);
+        let table = match (key, goal.data()) {
+            (Some(key), Data::Term(_)) => self.tables.get(&key),
+            _ => None,
+        };
+        let Some(table) = table else {
+            return Rows::empty();
+        };
+        let Data::Term(args) = goal.data() else {
+            unreachable!()
+        };
+
+        let mut rows = RowIter::All(0..table.len() as u32);
+        for (column, arg) in args[1..].iter().enumerate() {
+            let arg = bindings.resolve(Instance::new(arg, goal.base()));
+            let id = match arg.data() {
+                Data::Variable(_) => continue,
+                Data::Symbol(s) => self.ids.get(s),
+                Data::Term(_) => None,
+            };
+            let Some(id) = id else {
+                return Rows::empty();
+            };
+            if table.len() < INDEX_MIN_ROWS {
+                break;
+            }
+            let selected = table
+                .index(column)
+                .get(id)
+                .map_or(&[][..], |v| v.as_slice());
+            if selected.len() < rows.len() {
+                rows = RowIter::Selected(selected.iter());
+            }
+        }
+        Rows {
+            table: Some(table),
+            rows,
+        }
+    }
+}
+
+enum RowIter<'a> {
+    All(std::ops::Range<u32>),
+    Selected(std::slice::Iter<'a, u32>),
+    /// Rows in an order of their own, last one first.
+    Ordered(Vec<u32>),
+}
+
+impl<'a> RowIter<'a> {
+    fn len(&self) -> usize {
+        match self {
+            RowIter::All(r) => r.len(),
+            RowIter::Selected(i) => i.len(),
+            RowIter::Ordered(v) => v.len(),
+        }
+    }
+}
+
+/// Candidate rows of a fact table for one goal.
+pub struct Rows<'a> {
+    table: Option<&'a FactTable>,
+    rows: RowIter<'a>,
+}
+
+impl<'a> Rows<'a> {
+    pub(crate) fn empty() -> Self {
+        Rows {
+            table: None,
+            rows: RowIter::All(0..0),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.len() == 0
+    }
+
+    pub(crate) fn next_row(&mut self) -> Option<usize> {
+        let row = match &mut self.rows {
+            RowIter::All(r) => r.next()?,
+            RowIter::Selected(i) => *i.next()?,
+            RowIter::Ordered(v) => v.pop()?,
+        };
+        Some(row as usize)
+    }
+
+    /// The rows left, in the order they are taken.
+    pub(crate) fn remaining(&self) -> Vec<u32> {
+        match &self.rows {
+            RowIter::All(r) => r.clone().collect(),
+            RowIter::Selected(i) => i.clone().copied().collect(),
+            RowIter::Ordered(v) => v.iter().rev().copied().collect(),
+        }
+    }
+
+    /// Takes `rows`, in that order, instead of the rows left.
+    pub(crate) fn set_remaining(&mut self, rows: &[u32]) {
+        self.rows = RowIter::Ordered(rows.iter().rev().copied().collect());
+    }
+
+    /// Puts the rows left in a random order.
+    pub(crate) fn shuffle(&mut self, rng: &mut Rng) {
+        let mut rows = self.remaining();
+        rng.shuffle(&mut rows);
+        self.rows = RowIter::Ordered(rows);
+    }
+
+    /// Unifies the arguments of `goal` with `row`, whose symbols `facts` keeps.
+    pub(crate) fn unify(
+        &self,
+        facts: &'a FactBase,
+        bindings: &mut Bindings<'a>,
+        goal: Instance<'a>,
+        row: usize,
+    ) -> bool {
+        let Some(table) = self.table else {
+            return false;
+        };
+        let goal = bindings.resolve(goal);
+        let Data::Term(args) = goal.data() else {
+            return false;
+        };
+        table.columns.iter().zip(&args[1..]).all(|(column, arg)| {
+            let cell = Instance::new(&facts.symbols[column[row] as usize], 0);
+            bindings.unify(Instance::new(arg, goal.base()), cell)
+        })
+    }
+}
+
+/// Splits a CSV line, honouring double-quoted fields.
+pub(crate) fn split_csv(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                fields.last_mut().unwrap().push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            c => fields.last_mut().unwrap().push(c),
+        }
+    }
+    fields
+}
+
+pub(crate) fn split_tsv(line: &str) -> Vec<String> {
+    line.split('\t').map(|s| s.to_owned()).collect()
+}
+
+/// Reads the non-empty lines of `reader` as rows split by `split`.
+pub(crate) fn read_rows(
+    reader: impl BufRead,
+    split: fn(&str) -> Vec<String>,
+) -> impl Iterator<Item = io::Result<Vec<String>>> {
+    reader.lines().filter_map(move |line| match line {
+        Ok(line) => {
+            let line = line.trim_end_matches('\r');
+            (!line.is_empty()).then(|| Ok(split(line)))
+        }
+        Err(e) => Some(Err(e)),
+    })
+}
@@ -0,0 +1,38 @@
+use std::{
+    future::Future,
+    pin::{pin, Pin},
+    sync::Arc,
+    task::{Context, Poll, Wake, Waker},
+    thread::{self, Thread},
+};
+
+use crate::{atom::Atom, data::Data};
+
+/// Answers of a foreign predicate: rows of symbols, one per argument, each unified with the
+/// arguments of the call in turn, as fact rows are.
+pub type ForeignFuture = Pin<Box<dyn Future<Output = Vec<Vec<Atom>>> + Send>>;
+
+/// A predicate implemented in Rust, called with the arguments of a goal as bound so far.
+pub(crate) type Foreign = Box<dyn Fn(Vec<Data>) -> ForeignFuture + Send + Sync>;
+
+struct Unpark(Thread);
+
+impl Wake for Unpark {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Runs `future` to completion on the current thread, parking it while the future is
+/// pending. Blocking runs await foreign predicates with this.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let waker = Waker::from(Arc::new(Unpark(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+        thread::park();
+    }
+}
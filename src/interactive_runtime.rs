@@ -37,14 +37,11 @@ impl<'a> InteractiveRuntime<'a> {
             .filter_map(|rule| {
                 bindings.push(rule.var_num);
                 let right = bindings.instance(&rule.head);
-                if bindings.unify(left.clone(), right) {
+                if bindings.unify(left, right) {
                     let body: Vec<_> = rule.body.iter().map(|d| bindings.instance(d)).collect();
                     let subgoals: Vec<_> =
                         body.into_iter().rev().map(|i| bindings.data(i)).collect();
-                    let rest_goals = rest_goals
-                        .iter()
-                        .map(|i| bindings.data(i.clone()))
-                        .collect();
+                    let rest_goals = rest_goals.iter().map(|&i| bindings.data(i)).collect();
                     Some((subgoals, rest_goals))
                 } else {
                     None
@@ -61,7 +58,7 @@ impl<'a> InteractiveRuntime<'a> {
         }
         let n = get_number(&mut stdin, candidates.len());
         let (subgoals, rest_goals) = candidates.remove(n);
-        self.run(subgoals.into_iter().chain(rest_goals.into_iter()).collect())
+        self.run(subgoals.into_iter().chain(rest_goals).collect())
     }
 }
 
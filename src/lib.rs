@@ -1,10 +1,28 @@
+#![forbid(unsafe_code)]
+
+pub mod atom;
 pub mod bindings;
 pub mod data;
+pub mod dot;
+pub mod fact_table;
+pub mod foreign;
 pub mod interactive_runtime;
+pub mod limits;
+pub mod machine;
 pub mod macros;
+pub mod parallel;
+pub mod prepared_query;
+pub mod profile;
+pub mod proof;
+mod rng;
 pub mod rule_map;
 pub mod runtime;
+pub mod snapshot;
+pub mod strategy;
+pub mod stream;
+pub mod trace;
 pub mod user_data;
+pub mod why_not;
 pub mod world;
 
-pub use crate::world::World;
+pub use crate::{atom::Atom, world::World};
@@ -0,0 +1,110 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::runtime::Error;
+
+/// Inferences made between looks at the cancel token and the clock.
+const CHECK_INTERVAL: u64 = 1024;
+
+/// Resources a query may use before it is aborted with `Error::LimitExceeded`.
+/// `None` leaves a resource unlimited.
+#[derive(Debug, Clone, Default)]
+pub struct Limits {
+    /// Goals called, counting builtins.
+    pub inferences: Option<u64>,
+    /// Goals waiting to be resolved at once.
+    pub depth: Option<usize>,
+    /// Variables in the binding store.
+    pub bindings: Option<usize>,
+    /// Answers delivered; the limit is only reported once the query has another answer.
+    pub answers: Option<usize>,
+    /// Aborts the query with `Error::Cancelled` once cancelled.
+    pub cancel: Option<CancelToken>,
+    /// Wall-clock time before the query is aborted with `Error::TimedOut`.
+    pub timeout: Option<Duration>,
+}
+
+impl Limits {
+    pub(crate) fn is_unlimited(&self) -> bool {
+        matches!(
+            self,
+            Limits {
+                inferences: None,
+                depth: None,
+                bindings: None,
+                answers: None,
+                cancel: None,
+                timeout: None,
+            }
+        )
+    }
+
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        self.timeout.map(|t| Instant::now() + t)
+    }
+
+    /// Checks for cancellation and the deadline every `CHECK_INTERVAL` inferences, as of
+    /// the first one.
+    pub(crate) fn interrupt(
+        &self,
+        inferences: u64,
+        deadline: Option<Instant>,
+    ) -> Result<(), Error> {
+        if inferences % CHECK_INTERVAL != 1 {
+            return Ok(());
+        }
+        if self.cancel.as_ref().is_some_and(|c| c.is_cancelled()) {
+            return Err(Error::Cancelled);
+        }
+        if deadline.is_some_and(|d| Instant::now() >= d) {
+            return Err(Error::TimedOut);
+        }
+        Ok(())
+    }
+}
+
+/// Cancels the queries it was given to, from any thread. Clones share the same state.
+/// A query looks at the token only every 1024 inferences, so a foreign predicate blocked
+/// inside `block_on` keeps it waiting until the call returns.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// The limit a query ran into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Inferences,
+    Depth,
+    Bindings,
+    Answers,
+}
+
+impl std::fmt::Display for Limit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Limit::Inferences => "inference",
+            Limit::Depth => "depth",
+            Limit::Bindings => "binding",
+            Limit::Answers => "answer",
+        };
+        write!(f, "{} limit", name)
+    }
+}
@@ -0,0 +1,1266 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    atom::Atom,
+    bindings::{template, variable, Bindings, Instance, OccursCheck, CYCLE_CHECK_DEPTH},
+    data::Data,
+    prepared_query::Param,
+    rule_map::{Key, KeyMap, RuleMap},
+    runtime::{builtin, Builtin, Error},
+    world::Rule,
+};
+
+/// Where answers are delivered once the query's last goal has succeeded.
+const ANSWER: usize = 0;
+/// Where a conjunction called through a variable goes on to its next conjunct.
+const CONJUNCTION: usize = 1;
+const FAIL: usize = 2;
+
+/// Cell of the machine's heap and registers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Cell {
+    /// Unbound variable, named `{n}` as in the clause or query that made it.
+    Var(usize),
+    /// The cell at this address.
+    Ref(usize),
+    Atom(Atom),
+    /// Term whose `Len` cell is at this address, its elements following it.
+    Str(usize),
+    /// Number of elements of the term that follows.
+    Len(usize),
+}
+
+/// Where a clause keeps a variable: in an X register, or in a Y slot of its environment
+/// if the variable is needed after a call.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Reg {
+    X(u32),
+    Y(u32),
+}
+
+/// Instruction of a compiled clause or query. Terms are matched and built depth-first, in
+/// the order `Bindings::unify` visits them, and variables are made in the order the
+/// interpreter allocates them, so both bind the same variables the same way.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Instruction {
+    /// Keeps argument `arg` in the register of a variable at its first occurrence.
+    GetVariable(Reg, u32),
+    /// Unifies argument `arg` with a variable seen before.
+    GetValue(Reg, u32),
+    GetAtom(Atom, u32),
+    /// Matches argument `arg` with a term of `len` elements, which the `Unify` instructions
+    /// that follow match in turn: reading those of a term, or writing those of a new term
+    /// the argument is bound to if it is an unbound variable.
+    GetStructure(u32, u32),
+    /// First occurrence of a variable in a term; makes the variable `{name}` when writing.
+    UnifyVariable(Reg, u32),
+    UnifyValue(Reg),
+    UnifyAtom(Atom),
+    /// Variable occurring only here, made as `{name}` when writing.
+    UnifyVoid(u32),
+    /// Matches the next element with a term of `len` elements, as `GetStructure` does.
+    UnifyStructure(u32),
+    /// Leaves the term entered by the matching `UnifyStructure`.
+    Pop,
+    /// Ends the head, checking the terms it bound variables to under the occurs check.
+    Neck,
+    /// Makes the variable `{name}` that first occurs in the body.
+    PutVariable(Reg, u32),
+    PutValue(Reg, u32),
+    PutAtom(Atom, u32),
+    /// Sets argument `arg` to a new term of `len` elements, written by the `Unify`
+    /// instructions that follow.
+    PutStructure(u32, u32),
+    /// Makes an environment for `len` variables, for a clause calling more than one goal.
+    Allocate(u32),
+    Deallocate,
+    /// Calls the predicate at this address, then goes on with the next instruction.
+    Call(usize),
+    /// Calls the predicate at this address as the clause's last goal.
+    Execute(usize),
+    /// Calls the goal in argument 0, as the clause's last goal if `true`.
+    CallGoal(bool),
+    Proceed,
+    /// Enters the clause at this address, leaving a choicepoint that keeps `arity`
+    /// arguments for the next instruction, `Retry` or `Trust`.
+    Try(usize, u32),
+    Retry(usize),
+    Trust(usize),
+    /// Jumps to the clauses whose first argument may match argument 0.
+    Switch(u32),
+    /// Unifies the goal in argument 0 with the head of clause `n`, then enters the clause.
+    Head(u32),
+    Cut,
+    UnifyWithOccursCheck,
+    /// Calls the next conjunct of a `par` goal called through a variable.
+    Conjunction,
+    Answer,
+    Fail,
+}
+
+/// Targets of `Instruction::Switch` by the first argument: unbound, with a key of
+/// `keys`, or with any other key.
+struct Switch {
+    var: usize,
+    keys: KeyMap<usize>,
+    other: usize,
+}
+
+/// Clause head as `Instruction::Head` builds it: new variables as arguments, named after
+/// the head's variable arguments.
+struct Head {
+    entry: usize,
+    key: Key,
+    names: Box<[usize]>,
+}
+
+/// Rules compiled to a WAM-style program, run by `Program::run` in `Mode::Compiled`.
+pub(crate) struct Program {
+    code: Vec<Instruction>,
+    predicates: KeyMap<usize>,
+    switches: Vec<Switch>,
+    heads: Vec<Head>,
+    /// Clauses tried for an unbound goal, and for a term goal without a symbol functor by
+    /// its length.
+    any: usize,
+    by_len: HashMap<usize, usize>,
+    /// Most X registers, arguments and variable names a clause uses.
+    xs: usize,
+    args: usize,
+    names: usize,
+}
+
+impl Program {
+    /// Compiles `rules`, or returns `None` if a clause head has no symbol functor.
+    pub(crate) fn compile(rules: &[Rule], rule_map: &RuleMap) -> Option<Program> {
+        let keys = rules
+            .iter()
+            .map(|rule| Key::of(&rule.head))
+            .collect::<Option<Vec<_>>>()?;
+        let mut program = Program {
+            code: vec![
+                Instruction::Answer,
+                Instruction::Conjunction,
+                Instruction::Fail,
+            ],
+            predicates: KeyMap::default(),
+            switches: Vec::new(),
+            heads: Vec::new(),
+            any: FAIL,
+            by_len: HashMap::new(),
+            xs: 0,
+            args: 0,
+            names: 0,
+        };
+
+        let mut calls = Vec::new();
+        let mut entries = Vec::new();
+        for rule in rules {
+            let clause = Clause::compile(args(&rule.head), rule.body.iter().rev(), rule.var_num);
+            let entry = program.code.len();
+            entries.push(entry);
+            calls.extend(clause.calls.iter().map(|&(at, key)| (entry + at, key)));
+            program.code.extend(clause.code);
+            program.xs = program.xs.max(rule.var_num);
+            program.args = program.args.max(clause.args);
+            program.names = program.names.max(rule.var_num);
+        }
+
+        for &key in &keys {
+            if program.predicates.contains_key(&key) {
+                continue;
+            }
+            let arity = key.predicate().1;
+            let entry = program.predicate(rules, rule_map.clauses(key), &entries, arity);
+            program.predicates.insert(key, entry);
+        }
+        let mut code = std::mem::take(&mut program.code);
+        program.link(&mut code, &calls);
+        program.code = code;
+
+        let mut stubs = Vec::new();
+        let mut by_len = HashMap::<usize, Vec<usize>>::new();
+        for (i, (rule, &key)) in rules.iter().zip(&keys).enumerate() {
+            let names = args(&rule.head)
+                .iter()
+                .map(|d| match d {
+                    Data::Variable(n) => *n,
+                    _ => 0,
+                })
+                .collect();
+            program.heads.push(Head {
+                entry: entries[i],
+                key,
+                names,
+            });
+            let stub = program.code.len();
+            program.code.push(Instruction::Head(i as u32));
+            stubs.push(stub);
+            if let Key::Term(_, len) = key {
+                by_len.entry(len).or_default().push(stub);
+            }
+        }
+        program.any = program.chain(&stubs, 1);
+        for (len, stubs) in by_len {
+            let chain = program.chain(&stubs, 1);
+            program.by_len.insert(len, chain);
+        }
+        Some(program)
+    }
+
+    /// Points the calls of `code` at their predicates.
+    fn link(&self, code: &mut [Instruction], calls: &[(usize, Key)]) {
+        for &(at, key) in calls {
+            let entry = self.predicates.get(&key).copied().unwrap_or(FAIL);
+            match &mut code[at] {
+                Instruction::Call(to) | Instruction::Execute(to) => *to = entry,
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    /// Lays out the choice among `clauses` of a predicate, indexed by the first argument.
+    fn predicate(
+        &mut self,
+        rules: &[Rule],
+        clauses: &[usize],
+        entries: &[usize],
+        arity: usize,
+    ) -> usize {
+        let firsts: Vec<_> = clauses
+            .iter()
+            .map(|&c| args(&rules[c].head).first().and_then(Key::of))
+            .collect();
+        let select = |key: Option<Key>| -> Vec<usize> {
+            clauses
+                .iter()
+                .zip(&firsts)
+                .filter(|(_, first)| first.is_none() || **first == key)
+                .map(|(&c, _)| entries[c])
+                .collect()
+        };
+        let all: Vec<_> = clauses.iter().map(|&c| entries[c]).collect();
+        if clauses.len() < 2 || firsts.iter().all(Option::is_none) {
+            return self.chain(&all, arity);
+        }
+        let var = self.chain(&all, arity);
+        let other = self.chain(&select(None), arity);
+        let mut keys = KeyMap::default();
+        for &key in firsts.iter().flatten() {
+            if !keys.contains_key(&key) {
+                let chain = self.chain(&select(Some(key)), arity);
+                keys.insert(key, chain);
+            }
+        }
+        self.switches.push(Switch { var, keys, other });
+        self.code
+            .push(Instruction::Switch(self.switches.len() as u32 - 1));
+        self.code.len() - 1
+    }
+
+    /// Tries the clauses at `entries` in order.
+    fn chain(&mut self, entries: &[usize], arity: usize) -> usize {
+        match entries {
+            [] => FAIL,
+            [entry] => *entry,
+            [first, rest @ .., last] => {
+                let at = self.code.len();
+                self.code.push(Instruction::Try(*first, arity as u32));
+                self.code
+                    .extend(rest.iter().map(|&entry| Instruction::Retry(entry)));
+                self.code.push(Instruction::Trust(*last));
+                at
+            }
+        }
+    }
+
+    /// Runs `goals` as `Runtime::run_with` does, with each variable of `params` bound first.
+    pub(crate) fn run<F: FnMut(&[Data])>(
+        &self,
+        goals: &[Data],
+        params: &[(Param, Data)],
+        occurs_check: OccursCheck,
+        mut resolved_fn: F,
+    ) -> Result<(), Error> {
+        // The query is a clause whose head arguments are its variables.
+        let vars = goals.iter().map(Data::max_var).max().unwrap_or(0);
+        let head: Vec<_> = (0..vars).map(Data::Variable).collect();
+        let mut query = Clause::compile(&head, goals.iter(), vars);
+        self.link(&mut query.code, &query.calls);
+        let mut machine = Machine::new(self, &query, occurs_check);
+        machine.heap.extend((0..vars).map(Cell::Var));
+        machine.args[..vars]
+            .iter_mut()
+            .enumerate()
+            .for_each(|(i, arg)| *arg = Cell::Ref(i));
+        for (param, value) in params {
+            machine.names = machine.names.max(value.max_var());
+            if !machine.bind_param(param.0, value)? {
+                return Ok(());
+            }
+        }
+        machine.run(goals, vars, &mut resolved_fn)
+    }
+}
+
+/// Head arguments of a clause.
+fn args(head: &Data) -> &[Data] {
+    match head {
+        Data::Term(v) => &v[1..],
+        _ => &[],
+    }
+}
+
+/// Goal of a clause body, with `par` conjunctions flattened.
+enum Goal<'d> {
+    Cut,
+    /// `unify_with_occurs_check`, with its two arguments.
+    Unify(&'d [Data]),
+    Call(Key, &'d [Data]),
+    /// Goal without a symbol functor, called through `CallGoal`.
+    Dynamic(&'d Data),
+}
+
+impl<'d> Goal<'d> {
+    fn flatten(goal: &'d Data, goals: &mut Vec<Goal<'d>>) {
+        if goal.as_symbol() == Some(Atom::CUT) {
+            goals.push(Goal::Cut);
+            return;
+        }
+        let key = Key::of(goal);
+        match (goal, key, builtin(key)) {
+            (Data::Term(v), _, Some(Builtin::UnifyWithOccursCheck)) => {
+                goals.push(Goal::Unify(&v[1..]))
+            }
+            (Data::Term(v), _, Some(Builtin::Par)) => {
+                v[1..].iter().for_each(|g| Goal::flatten(g, goals))
+            }
+            (_, Some(key), None) => goals.push(Goal::Call(key, args(goal))),
+            _ => goals.push(Goal::Dynamic(goal)),
+        }
+    }
+
+    fn calls(&self) -> bool {
+        matches!(self, Goal::Call(..) | Goal::Dynamic(_))
+    }
+
+    fn args(&self) -> &'d [Data] {
+        match self {
+            Goal::Cut => &[],
+            Goal::Unify(args) | Goal::Call(_, args) => args,
+            Goal::Dynamic(goal) => std::slice::from_ref(goal),
+        }
+    }
+}
+
+/// Code of one clause; `calls` are the `Call` and `Execute` instructions still to be
+/// pointed at their predicates.
+struct Clause {
+    code: Vec<Instruction>,
+    calls: Vec<(usize, Key)>,
+    args: usize,
+    regs: Vec<Reg>,
+    seen: Vec<bool>,
+    count: Vec<usize>,
+}
+
+impl Clause {
+    fn compile<'d>(
+        head: &'d [Data],
+        body: impl Iterator<Item = &'d Data>,
+        var_num: usize,
+    ) -> Clause {
+        let mut goals = Vec::new();
+        body.for_each(|goal| Goal::flatten(goal, &mut goals));
+        let env = goals.iter().rev().skip(1).any(Goal::calls);
+
+        // Variables used after the first call are kept in the environment.
+        let mut count = vec![0; var_num];
+        let mut later = vec![false; var_num];
+        head.iter().for_each(|d| each_var(d, |n| count[n] += 1));
+        let mut chunk = 0;
+        for goal in &goals {
+            for d in goal.args() {
+                each_var(d, |n| {
+                    count[n] += 1;
+                    later[n] |= chunk > 0;
+                });
+            }
+            chunk += goal.calls() as usize;
+        }
+        let mut ys = 0;
+        let regs = (0..var_num)
+            .map(|n| match env && later[n] {
+                true => {
+                    ys += 1;
+                    Reg::Y(ys - 1)
+                }
+                false => Reg::X(n as u32),
+            })
+            .collect();
+
+        let mut clause = Clause {
+            code: Vec::new(),
+            calls: Vec::new(),
+            args: head.len(),
+            regs,
+            seen: vec![false; var_num],
+            count,
+        };
+        if env {
+            clause.code.push(Instruction::Allocate(ys));
+        }
+        let mut terms = false;
+        for (i, arg) in head.iter().enumerate() {
+            let i = i as u32;
+            match arg {
+                Data::Variable(n) if clause.count[*n] == 1 => clause.seen[*n] = true,
+                Data::Variable(n) => {
+                    let reg = clause.regs[*n];
+                    clause.code.push(match std::mem::replace(&mut clause.seen[*n], true) {
+                        true => Instruction::GetValue(reg, i),
+                        false => Instruction::GetVariable(reg, i),
+                    });
+                }
+                Data::Symbol(s) => clause.code.push(Instruction::GetAtom(*s, i)),
+                Data::Term(v) => {
+                    terms = true;
+                    clause
+                        .code
+                        .push(Instruction::GetStructure(v.len() as u32, i));
+                    clause.elements(v);
+                }
+            }
+        }
+        if terms {
+            clause.code.push(Instruction::Neck);
+        }
+        // Made together, as the interpreter allocates them with the head's.
+        for n in 0..var_num {
+            if !clause.seen[n] {
+                clause.seen[n] = true;
+                clause
+                    .code
+                    .push(Instruction::PutVariable(clause.regs[n], n as u32));
+            }
+        }
+
+        for (i, goal) in goals.iter().enumerate() {
+            let last = i + 1 == goals.len();
+            match goal {
+                Goal::Cut => clause.code.push(Instruction::Cut),
+                Goal::Unify(args) => {
+                    clause.put(&args[0], 0);
+                    clause.put(&args[1], 1);
+                    clause.code.push(Instruction::UnifyWithOccursCheck);
+                }
+                Goal::Call(key, args) => {
+                    for (i, arg) in args.iter().enumerate() {
+                        clause.put(arg, i as u32);
+                    }
+                    if last && env {
+                        clause.code.push(Instruction::Deallocate);
+                    }
+                    clause.calls.push((clause.code.len(), *key));
+                    clause.code.push(match last {
+                        true => Instruction::Execute(FAIL),
+                        false => Instruction::Call(FAIL),
+                    });
+                }
+                Goal::Dynamic(goal) => {
+                    clause.put(goal, 0);
+                    if last && env {
+                        clause.code.push(Instruction::Deallocate);
+                    }
+                    clause.code.push(Instruction::CallGoal(last));
+                }
+            }
+        }
+        if !goals.last().is_some_and(Goal::calls) {
+            if env {
+                clause.code.push(Instruction::Deallocate);
+            }
+            clause.code.push(Instruction::Proceed);
+        }
+        clause
+    }
+
+    /// Sets argument `arg` to `data`, all of whose variables have been made.
+    fn put(&mut self, data: &Data, arg: u32) {
+        self.args = self.args.max(arg as usize + 1);
+        match data {
+            Data::Variable(n) => self
+                .code
+                .push(Instruction::PutValue(self.regs[*n], arg)),
+            Data::Symbol(s) => self.code.push(Instruction::PutAtom(*s, arg)),
+            Data::Term(v) => {
+                self.code
+                    .push(Instruction::PutStructure(v.len() as u32, arg));
+                self.elements(v);
+            }
+        }
+    }
+
+    /// Matches or writes the elements of a term, depth-first.
+    fn elements(&mut self, v: &[Data]) {
+        enum Task<'a> {
+            Compile(&'a Data),
+            Pop,
+        }
+
+        let mut tasks: Vec<_> = v.iter().rev().map(Task::Compile).collect();
+        while let Some(task) = tasks.pop() {
+            let instruction = match task {
+                Task::Pop => Instruction::Pop,
+                Task::Compile(Data::Variable(n)) => {
+                    let reg = self.regs[*n];
+                    match (std::mem::replace(&mut self.seen[*n], true), self.count[*n]) {
+                        (true, _) => Instruction::UnifyValue(reg),
+                        (false, 1) => Instruction::UnifyVoid(*n as u32),
+                        (false, _) => Instruction::UnifyVariable(reg, *n as u32),
+                    }
+                }
+                Task::Compile(Data::Symbol(s)) => Instruction::UnifyAtom(*s),
+                Task::Compile(Data::Term(w)) => {
+                    tasks.push(Task::Pop);
+                    tasks.extend(w.iter().rev().map(Task::Compile));
+                    Instruction::UnifyStructure(w.len() as u32)
+                }
+            };
+            self.code.push(instruction);
+        }
+    }
+}
+
+fn each_var(data: &Data, mut f: impl FnMut(usize)) {
+    let mut stack = vec![data];
+    while let Some(data) = stack.pop() {
+        match data {
+            Data::Variable(n) => f(*n),
+            Data::Symbol(_) => {}
+            Data::Term(v) => stack.extend(v.iter()),
+        }
+    }
+}
+
+/// Environment of a clause calling more than one goal: where to continue after it, and
+/// its variables, at `ys` in `Machine::ys`.
+struct Frame {
+    ce: usize,
+    cp: usize,
+    ys: usize,
+    len: usize,
+}
+
+/// State to go back to when a call's next clause is tried.
+struct Choicepoint {
+    alt: usize,
+    /// Arguments of the call, at `args` in `Machine::saved`.
+    args: usize,
+    arity: usize,
+    e: usize,
+    cp: usize,
+    heap: usize,
+    trail: usize,
+    frames: usize,
+    ys: usize,
+}
+
+struct Machine<'p> {
+    program: &'p Program,
+    query: &'p [Instruction],
+    occurs_check: OccursCheck,
+    heap: Vec<Cell>,
+    args: Vec<Cell>,
+    xs: Vec<Cell>,
+    frames: Vec<Frame>,
+    ys: Vec<Cell>,
+    choicepoints: Vec<Choicepoint>,
+    saved: Vec<Cell>,
+    /// Bound cells older than the newest choicepoint, with what they held.
+    trail: Vec<(usize, Cell)>,
+    /// Heap length when the newest choicepoint was made.
+    hb: usize,
+    pc: usize,
+    cp: usize,
+    e: usize,
+    /// Next element of the term being matched or written, and whether it is written.
+    s: usize,
+    write: bool,
+    terms: Vec<(usize, bool)>,
+    /// Variables the head bound to new terms, with what they held, to check at `Neck`.
+    pending: Vec<(usize, Cell, usize)>,
+    /// Terms being unified, with the number of elements done and their depth.
+    pending_terms: Vec<(usize, usize, usize, usize)>,
+    /// Variable names are below this.
+    names: usize,
+}
+
+impl<'p> Machine<'p> {
+    fn new(program: &'p Program, query: &'p Clause, occurs_check: OccursCheck) -> Self {
+        Machine {
+            program,
+            query: &query.code,
+            occurs_check,
+            heap: Vec::new(),
+            args: vec![Cell::Len(0); program.args.max(query.args)],
+            xs: vec![Cell::Len(0); program.xs.max(query.regs.len())],
+            frames: vec![Frame {
+                ce: 0,
+                cp: ANSWER,
+                ys: 0,
+                len: 0,
+            }],
+            ys: Vec::new(),
+            choicepoints: Vec::new(),
+            saved: Vec::new(),
+            trail: Vec::new(),
+            hb: 0,
+            pc: program.code.len(),
+            cp: ANSWER,
+            e: 0,
+            s: 0,
+            write: false,
+            terms: Vec::new(),
+            pending: Vec::new(),
+            pending_terms: Vec::new(),
+            names: program.names.max(query.regs.len()),
+        }
+    }
+
+    fn run(
+        &mut self,
+        goals: &[Data],
+        vars: usize,
+        resolved_fn: &mut dyn FnMut(&[Data]),
+    ) -> Result<(), Error> {
+        loop {
+            let instruction = match self.program.code.get(self.pc) {
+                Some(&instruction) => instruction,
+                None => self.query[self.pc - self.program.code.len()],
+            };
+            self.pc += 1;
+            if !self.step(instruction, goals, vars, resolved_fn)? && !self.backtrack() {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Executes `instruction`, returning whether it succeeded.
+    fn step(
+        &mut self,
+        instruction: Instruction,
+        goals: &[Data],
+        vars: usize,
+        resolved_fn: &mut dyn FnMut(&[Data]),
+    ) -> Result<bool, Error> {
+        match instruction {
+            Instruction::GetVariable(reg, arg) => self.set(reg, self.args[arg as usize]),
+            Instruction::GetValue(reg, arg) => {
+                return self.unify(self.get(reg), self.args[arg as usize], self.occurs_check)
+            }
+            Instruction::GetAtom(atom, arg) => return Ok(self.unify_atom(self.args[arg as usize], atom)),
+            Instruction::GetStructure(len, arg) => {
+                self.terms.clear();
+                return Ok(self.enter(self.args[arg as usize], len as usize));
+            }
+            Instruction::UnifyVariable(reg, name) => {
+                let cell = match self.write {
+                    true => self.new_var(name as usize),
+                    false => self.heap[self.s],
+                };
+                self.heap[self.s] = cell;
+                self.s += 1;
+                self.set(reg, cell);
+            }
+            Instruction::UnifyValue(reg) => {
+                let cell = self.get(reg);
+                self.s += 1;
+                match self.write {
+                    true => self.heap[self.s - 1] = cell,
+                    false => return self.unify(self.heap[self.s - 1], cell, self.occurs_check),
+                }
+            }
+            Instruction::UnifyAtom(atom) => {
+                self.s += 1;
+                match self.write {
+                    true => self.heap[self.s - 1] = Cell::Atom(atom),
+                    false => return Ok(self.unify_atom(self.heap[self.s - 1], atom)),
+                }
+            }
+            Instruction::UnifyVoid(name) => {
+                if self.write {
+                    self.heap[self.s] = self.new_var(name as usize);
+                }
+                self.s += 1;
+            }
+            Instruction::UnifyStructure(len) => {
+                self.terms.push((self.s + 1, self.write));
+                let cell = self.heap[self.s];
+                return Ok(match self.write {
+                    true => {
+                        self.heap[self.s] = Cell::Str(self.new_term(len as usize));
+                        self.s = self.heap.len() - len as usize;
+                        true
+                    }
+                    false => self.enter(cell, len as usize),
+                });
+            }
+            Instruction::Pop => (self.s, self.write) = self.terms.pop().unwrap(),
+            Instruction::Neck => return self.check_pending(),
+            Instruction::PutVariable(reg, name) => {
+                let cell = self.new_var(name as usize);
+                self.set(reg, cell);
+            }
+            Instruction::PutValue(reg, arg) => self.args[arg as usize] = self.get(reg),
+            Instruction::PutAtom(atom, arg) => self.args[arg as usize] = Cell::Atom(atom),
+            Instruction::PutStructure(len, arg) => {
+                let p = self.new_term(len as usize);
+                self.args[arg as usize] = Cell::Str(p);
+                self.s = p + 1;
+                self.write = true;
+            }
+            Instruction::Allocate(len) => self.allocate(len as usize),
+            Instruction::Deallocate => self.deallocate(),
+            Instruction::Call(to) => {
+                self.cp = self.pc;
+                self.pc = to;
+            }
+            Instruction::Execute(to) => self.pc = to,
+            Instruction::CallGoal(last) => {
+                let cont = if last { self.cp } else { self.pc };
+                return self.call_goal(self.args[0], cont);
+            }
+            Instruction::Proceed => self.pc = self.cp,
+            Instruction::Try(to, arity) => {
+                let arity = arity as usize;
+                self.choicepoints.push(Choicepoint {
+                    alt: self.pc,
+                    args: self.saved.len(),
+                    arity,
+                    e: self.e,
+                    cp: self.cp,
+                    heap: self.heap.len(),
+                    trail: self.trail.len(),
+                    frames: self.frames.len(),
+                    ys: self.ys.len(),
+                });
+                self.saved.extend_from_slice(&self.args[..arity]);
+                self.hb = self.heap.len();
+                self.pc = to;
+            }
+            Instruction::Retry(to) => {
+                self.choicepoints.last_mut().unwrap().alt = self.pc;
+                self.pc = to;
+            }
+            Instruction::Trust(to) => {
+                let b = self.choicepoints.pop().unwrap();
+                self.saved.truncate(b.args);
+                self.hb = self.choicepoints.last().map_or(0, |b| b.heap);
+                self.pc = to;
+            }
+            Instruction::Switch(n) => {
+                let switch = &self.program.switches[n as usize];
+                let key = match self.deref(self.args[0]) {
+                    Cell::Atom(s) => Some(Key::Symbol(s)),
+                    Cell::Str(p) => match (self.heap[p], self.functor(p)) {
+                        (Cell::Len(len), Some(f)) => Some(Key::term(f, len)),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                self.pc = match key {
+                    Some(key) => switch.keys.get(&key).copied().unwrap_or(switch.other),
+                    None => switch.var,
+                };
+            }
+            Instruction::Head(n) => {
+                let program = self.program;
+                let head = &program.heads[n as usize];
+                let pattern = match head.key {
+                    Key::Symbol(s) => Cell::Atom(s),
+                    Key::Term(f, len) => {
+                        let vars = self.heap.len();
+                        self.heap.extend(head.names.iter().map(|&name| Cell::Var(name)));
+                        let p = self.new_term(len);
+                        self.heap[p + 1] = Cell::Atom(f);
+                        for i in 0..len - 1 {
+                            self.heap[p + 2 + i] = Cell::Ref(vars + i);
+                        }
+                        Cell::Str(p)
+                    }
+                };
+                if !self.unify(self.args[0], pattern, self.occurs_check)? {
+                    return Ok(false);
+                }
+                if let Cell::Str(p) = pattern {
+                    for i in 0..head.names.len() {
+                        self.args[i] = self.heap[p + 2 + i];
+                    }
+                }
+                self.pc = head.entry;
+            }
+            Instruction::Cut => {
+                self.choicepoints.clear();
+                self.saved.clear();
+                self.trail.clear();
+                self.hb = 0;
+            }
+            Instruction::UnifyWithOccursCheck => {
+                return self.unify(self.args[0], self.args[1], OccursCheck::True)
+            }
+            Instruction::Conjunction => {
+                let frame = &self.frames[self.e];
+                let (Cell::Str(p), Cell::Len(i)) = (self.ys[frame.ys], self.ys[frame.ys + 1]) else {
+                    unreachable!()
+                };
+                let goal = self.heap[p + 1 + i];
+                self.deallocate();
+                return self.conjunct(p, i, goal);
+            }
+            Instruction::Answer => {
+                let answer = self.answer(goals, vars);
+                resolved_fn(&answer);
+                return Ok(false);
+            }
+            Instruction::Fail => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn get(&self, reg: Reg) -> Cell {
+        match reg {
+            Reg::X(i) => self.xs[i as usize],
+            Reg::Y(i) => self.ys[self.frames[self.e].ys + i as usize],
+        }
+    }
+
+    fn set(&mut self, reg: Reg, cell: Cell) {
+        match reg {
+            Reg::X(i) => self.xs[i as usize] = cell,
+            Reg::Y(i) => {
+                let ys = self.frames[self.e].ys;
+                self.ys[ys + i as usize] = cell;
+            }
+        }
+    }
+
+    /// Symbol functor of the term at `p`, if it has one.
+    fn functor(&self, p: usize) -> Option<Atom> {
+        match (self.heap[p], self.heap.get(p + 1).map(|&f| self.deref(f))) {
+            (Cell::Len(1..), Some(Cell::Atom(f))) => Some(f),
+            _ => None,
+        }
+    }
+
+    fn new_var(&mut self, name: usize) -> Cell {
+        self.heap.push(Cell::Var(name));
+        Cell::Ref(self.heap.len() - 1)
+    }
+
+    /// Makes a term of `len` elements still to be written, returning its address.
+    fn new_term(&mut self, len: usize) -> usize {
+        let p = self.heap.len();
+        self.heap.push(Cell::Len(len));
+        self.heap.resize(p + 1 + len, Cell::Len(0));
+        p
+    }
+
+    fn deref(&self, mut cell: Cell) -> Cell {
+        while let Cell::Ref(a) = cell {
+            match self.heap[a] {
+                Cell::Var(_) => break,
+                next => cell = next,
+            }
+        }
+        cell
+    }
+
+    fn bind(&mut self, addr: usize, cell: Cell) {
+        if addr < self.hb {
+            self.trail.push((addr, self.heap[addr]));
+        }
+        self.heap[addr] = cell;
+    }
+
+    fn unify_atom(&mut self, cell: Cell, atom: Atom) -> bool {
+        match self.deref(cell) {
+            Cell::Ref(a) => {
+                self.bind(a, Cell::Atom(atom));
+                true
+            }
+            cell => cell == Cell::Atom(atom),
+        }
+    }
+
+    /// Starts matching the elements of `cell` with a term of `len` elements, binding it to
+    /// a new term to write them in if it is an unbound variable.
+    fn enter(&mut self, cell: Cell, len: usize) -> bool {
+        match self.deref(cell) {
+            Cell::Ref(a) => {
+                let p = self.new_term(len);
+                if self.occurs_check != OccursCheck::False {
+                    self.pending.push((a, self.heap[a], p));
+                }
+                self.bind(a, Cell::Str(p));
+                self.s = p + 1;
+                self.write = true;
+                true
+            }
+            Cell::Str(p) if self.heap[p] == Cell::Len(len) => {
+                self.s = p + 1;
+                self.write = false;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn check_pending(&mut self) -> Result<bool, Error> {
+        for (a, held, p) in std::mem::take(&mut self.pending) {
+            if self.reaches(p, Cell::Str(p)) {
+                if self.occurs_check == OccursCheck::Error {
+                    self.heap[a] = held;
+                    return Err(Error::OccursCheck(self.data(Cell::Str(p))));
+                }
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Whether `target` is among the subterms of the term at `p`.
+    fn reaches(&self, p: usize, target: Cell) -> bool {
+        let mut visited = HashSet::new();
+        let mut stack = vec![p];
+        while let Some(p) = stack.pop() {
+            let Cell::Len(len) = self.heap[p] else {
+                unreachable!()
+            };
+            for &cell in &self.heap[p + 1..p + 1 + len] {
+                match self.deref(cell) {
+                    cell if cell == target => return true,
+                    Cell::Str(q) if visited.insert(q) => stack.push(q),
+                    _ => {}
+                }
+            }
+        }
+        false
+    }
+
+    fn unify(&mut self, left: Cell, right: Cell, occurs_check: OccursCheck) -> Result<bool, Error> {
+        let (left, right) = (self.deref(left), self.deref(right));
+        match (left, right) {
+            (Cell::Str(p), Cell::Str(q)) if p != q => self.unify_terms(p, q, occurs_check),
+            _ => self.unify_cells(left, right, occurs_check),
+        }
+    }
+
+    /// Unifies two dereferenced cells that are not both terms, or the same term.
+    fn unify_cells(&mut self, left: Cell, right: Cell, occurs_check: OccursCheck) -> Result<bool, Error> {
+        match (left, right) {
+            _ if left == right => Ok(true),
+            // The younger variable is bound to the older, as in the interpreter.
+            (Cell::Ref(l), Cell::Ref(r)) => {
+                match l < r {
+                    true => self.bind(r, left),
+                    false => self.bind(l, right),
+                }
+                Ok(true)
+            }
+            (Cell::Ref(a), term) | (term, Cell::Ref(a)) => self.bind_checked(a, term, occurs_check),
+            _ => Ok(false),
+        }
+    }
+
+    /// Unifies the elements of the terms at `p` and `q` pairwise, depth-first. The elements
+    /// after a pair of terms are only put back on `pending` if any are left.
+    fn unify_terms(&mut self, p: usize, q: usize, occurs_check: OccursCheck) -> Result<bool, Error> {
+        let mut visited = None;
+        let mut pending = std::mem::take(&mut self.pending_terms);
+        pending.clear();
+        let mut top = (p, q, 0, 0);
+        let unified = 'unify: loop {
+            let (p, q, mut i, depth) = top;
+            let Cell::Len(len) = self.heap[p] else {
+                unreachable!()
+            };
+            if i == 0 && self.heap[q] != Cell::Len(len) {
+                break Ok(false);
+            }
+            while i < len {
+                i += 1;
+                let left = self.deref(self.heap[p + i]);
+                let right = self.deref(self.heap[q + i]);
+                match (left, right) {
+                    (Cell::Str(l), Cell::Str(r)) if l != r => {
+                        // Pairs met again on cyclic terms are assumed to unify.
+                        if depth >= CYCLE_CHECK_DEPTH
+                            && !visited.get_or_insert_with(HashSet::new).insert((l, r))
+                        {
+                            continue;
+                        }
+                        if i < len {
+                            pending.push((p, q, i, depth));
+                        }
+                        top = (l, r, 0, depth + 1);
+                        continue 'unify;
+                    }
+                    _ => match self.unify_cells(left, right, occurs_check) {
+                        Ok(true) => {}
+                        failed => break 'unify failed,
+                    },
+                }
+            }
+            match pending.pop() {
+                Some(next) => top = next,
+                None => break Ok(true),
+            }
+        };
+        self.pending_terms = pending;
+        unified
+    }
+
+    fn bind_checked(&mut self, a: usize, cell: Cell, occurs_check: OccursCheck) -> Result<bool, Error> {
+        if let (OccursCheck::True | OccursCheck::Error, Cell::Str(p)) = (occurs_check, cell) {
+            if self.reaches(p, Cell::Ref(a)) {
+                return match occurs_check {
+                    OccursCheck::Error => Err(Error::OccursCheck(self.data(cell))),
+                    _ => Ok(false),
+                };
+            }
+        }
+        self.bind(a, cell);
+        Ok(true)
+    }
+
+    fn allocate(&mut self, len: usize) {
+        let current = &self.frames[self.e];
+        let (mut frames, mut ys) = (self.e + 1, current.ys + current.len);
+        // Environments a choicepoint may go back to are kept.
+        if let Some(b) = self.choicepoints.last() {
+            frames = frames.max(b.frames);
+            ys = ys.max(b.ys);
+        }
+        self.frames.truncate(frames);
+        self.frames.push(Frame {
+            ce: self.e,
+            cp: self.cp,
+            ys,
+            len,
+        });
+        self.ys.truncate(ys);
+        self.ys.resize(ys + len, Cell::Len(0));
+        self.e = frames;
+    }
+
+    fn deallocate(&mut self) {
+        let frame = &self.frames[self.e];
+        self.cp = frame.cp;
+        self.e = frame.ce;
+    }
+
+    /// Calls `goal`, then goes on at `cont`.
+    fn call_goal(&mut self, goal: Cell, cont: usize) -> Result<bool, Error> {
+        self.cp = cont;
+        let goal = self.deref(goal);
+        let (key, p) = match goal {
+            Cell::Atom(s) => (Key::Symbol(s), None),
+            Cell::Str(p) => {
+                let Cell::Len(len) = self.heap[p] else {
+                    unreachable!()
+                };
+                match self.functor(p) {
+                    Some(f) => (Key::term(f, len), Some(p)),
+                    None => {
+                        self.args[0] = goal;
+                        self.pc = self.program.by_len.get(&len).copied().unwrap_or(FAIL);
+                        return Ok(true);
+                    }
+                }
+            }
+            _ => {
+                self.args[0] = goal;
+                self.pc = self.program.any;
+                return Ok(true);
+            }
+        };
+        match (builtin(Some(key)), p) {
+            (Some(Builtin::UnifyWithOccursCheck), Some(p)) => {
+                self.pc = cont;
+                self.unify(self.heap[p + 2], self.heap[p + 3], OccursCheck::True)
+            }
+            (Some(Builtin::Par), Some(p)) => self.conjunct(p, 1, self.heap[p + 2]),
+            _ => {
+                let Some(&entry) = self.program.predicates.get(&key) else {
+                    return Ok(false);
+                };
+                if let (Key::Term(_, len), Some(p)) = (key, p) {
+                    if self.args.len() < len - 1 {
+                        self.args.resize(len - 1, Cell::Len(0));
+                    }
+                    for i in 0..len - 1 {
+                        self.args[i] = self.heap[p + 2 + i];
+                    }
+                }
+                self.pc = entry;
+                Ok(true)
+            }
+        }
+    }
+
+    /// Calls conjunct `i` of the `par` term at `p`, which is `goal`, keeping the rest for
+    /// `Instruction::Conjunction`; the conjunction goes on at `cp`.
+    fn conjunct(&mut self, p: usize, i: usize, goal: Cell) -> Result<bool, Error> {
+        let Cell::Len(len) = self.heap[p] else {
+            unreachable!()
+        };
+        if i == len - 1 {
+            return self.call_goal(goal, self.cp);
+        }
+        self.allocate(2);
+        let ys = self.frames[self.e].ys;
+        self.ys[ys] = Cell::Str(p);
+        self.ys[ys + 1] = Cell::Len(i + 1);
+        self.call_goal(goal, CONJUNCTION)
+    }
+
+    fn backtrack(&mut self) -> bool {
+        let Some(b) = self.choicepoints.last() else {
+            return false;
+        };
+        for (a, cell) in self.trail.drain(b.trail..).rev() {
+            self.heap[a] = cell;
+        }
+        self.heap.truncate(b.heap);
+        self.args[..b.arity].copy_from_slice(&self.saved[b.args..b.args + b.arity]);
+        self.e = b.e;
+        self.cp = b.cp;
+        self.pc = b.alt;
+        self.terms.clear();
+        self.pending.clear();
+        true
+    }
+
+    /// Binds query variable `idx` to `value`, as `Runtime::with_params` does.
+    fn bind_param(&mut self, idx: usize, value: &Data) -> Result<bool, Error> {
+        let base = self.heap.len();
+        self.heap
+            .extend((0..value.max_var()).map(Cell::Var));
+        let mut tasks = Vec::new();
+        let value = self.put_data(value, base, &mut tasks);
+        while let Some((p, v)) = tasks.pop() {
+            for (i, data) in v.iter().enumerate() {
+                self.heap[p + 1 + i] = self.put_data(data, base, &mut tasks);
+            }
+        }
+        match self.heap[idx] {
+            Cell::Var(_) => {
+                self.heap[idx] = value;
+                Ok(true)
+            }
+            _ => self.unify(Cell::Ref(idx), value, OccursCheck::False),
+        }
+    }
+
+    /// Cell for `data`, whose variable `n` is at `base + n`; the elements of a term are
+    /// left to write for `tasks`.
+    fn put_data<'d>(
+        &mut self,
+        data: &'d Data,
+        base: usize,
+        tasks: &mut Vec<(usize, &'d [Data])>,
+    ) -> Cell {
+        match data {
+            Data::Variable(n) => Cell::Ref(base + n),
+            Data::Symbol(s) => Cell::Atom(*s),
+            Data::Term(v) => {
+                let p = self.new_term(v.len());
+                tasks.push((p, v));
+                Cell::Str(p)
+            }
+        }
+    }
+
+    /// The goals resolved, last one first as `Runtime` gives them.
+    fn answer(&self, goals: &[Data], vars: usize) -> Vec<Data> {
+        let mut render = Render::new(self, vars);
+        render.finish();
+        goals
+            .iter()
+            .rev()
+            .map(|goal| render.bindings.data(Instance::new(goal, 0)))
+            .collect()
+    }
+
+    fn data(&self, cell: Cell) -> Data {
+        let mut render = Render::new(self, 0);
+        let instance = render.instance(cell);
+        render.finish();
+        render.bindings.data(instance)
+    }
+}
+
+/// Copies heap cells into `Bindings`, to copy answers out as the interpreter does: query
+/// variable `n` keeps its index, and other cells get their own variables after the names
+/// any clause uses.
+struct Render<'a, 'm> {
+    machine: &'m Machine<'m>,
+    bindings: Bindings<'a>,
+    slots: HashMap<usize, usize>,
+    stack: Vec<usize>,
+}
+
+impl<'a, 'm> Render<'a, 'm> {
+    fn new(machine: &'m Machine<'m>, vars: usize) -> Self {
+        let mut bindings = Bindings::new();
+        bindings.alloc(vars + machine.names);
+        Render {
+            machine,
+            bindings,
+            slots: (0..vars).map(|a| (a, a)).collect(),
+            stack: (0..vars).collect(),
+        }
+    }
+
+    /// Binds the variable of every cell reached so far to what the cell holds.
+    fn finish(&mut self) {
+        while let Some(a) = self.stack.pop() {
+            if let Cell::Var(_) = self.machine.heap[a] {
+                continue;
+            }
+            let instance = self.instance(self.machine.heap[a]);
+            self.bindings.bind(self.slots[&a], instance);
+        }
+    }
+
+    fn instance(&mut self, cell: Cell) -> Instance<'a> {
+        match cell {
+            Cell::Ref(a) => {
+                let bindings = &mut self.bindings;
+                let stack = &mut self.stack;
+                let slot = *self.slots.entry(a).or_insert_with(|| {
+                    stack.push(a);
+                    bindings.alloc(1)
+                });
+                match self.machine.heap[a] {
+                    Cell::Var(n) => Instance::new(variable(n), slot - n),
+                    _ => Instance::new(variable(0), slot),
+                }
+            }
+            Cell::Atom(s) => Instance::new(s.data(), 0),
+            Cell::Str(p) => {
+                let Cell::Len(len) = self.machine.heap[p] else {
+                    unreachable!()
+                };
+                let base = self.bindings.alloc(len);
+                for i in 0..len {
+                    let element = self.instance(Cell::Ref(p + 1 + i));
+                    self.bindings.bind(base + i, element);
+                }
+                Instance::new(template(len), base)
+            }
+            Cell::Var(_) | Cell::Len(_) => unreachable!(),
+        }
+    }
+}
@@ -0,0 +1,243 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Receiver, SyncSender},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    atom::Atom,
+    data::Data,
+    limits::{CancelToken, Limits},
+    runtime::{Donor, Error, Runtime},
+    world::World,
+};
+
+/// How long an idle worker first sleeps before looking for work again, doubling up to
+/// `MAX_IDLE_SLEEP` while none turns up.
+const IDLE_SLEEP: Duration = Duration::from_micros(20);
+const MAX_IDLE_SLEEP: Duration = Duration::from_millis(1);
+
+/// Answers found ahead of the receiver before unordered workers wait for it.
+const ANSWER_BUFFER: usize = 1024;
+
+/// Options of `World::run_parallel`.
+#[derive(Debug, Clone, Copy)]
+pub struct Parallel {
+    /// Number of worker threads.
+    pub threads: usize,
+    /// Send answers in the order a sequential run finds them, once the whole search is done.
+    /// Until then all of them are kept in memory, so leave this off for queries with many
+    /// answers.
+    pub ordered: bool,
+}
+
+impl Default for Parallel {
+    fn default() -> Self {
+        Parallel {
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            ordered: false,
+        }
+    }
+}
+
+/// Goals split off a search, with the answer template and the path leading to them.
+#[derive(Clone)]
+pub(crate) struct Task {
+    pub(crate) template: Vec<Data>,
+    pub(crate) goals: Vec<Data>,
+    pub(crate) path: Vec<u32>,
+    /// Alternatives of the first goal left to try, if split off a choicepoint.
+    pub(crate) split: Option<Split>,
+}
+
+/// Alternatives left at a choicepoint.
+#[derive(Clone)]
+pub(crate) struct Split {
+    /// Ordinal of the first alternative left. For `par` goals, alternatives before it are
+    /// combinations already taken.
+    pub(crate) ordinal: u32,
+    pub(crate) rules: Vec<usize>,
+    /// Fact rows left, in the order they are taken.
+    pub(crate) rows: Vec<u32>,
+}
+
+struct Pool {
+    queues: Vec<Mutex<VecDeque<Task>>>,
+    /// Tasks queued or running; the search is done when it drops to zero.
+    pending: AtomicUsize,
+    queued: AtomicUsize,
+    idle: AtomicUsize,
+    /// Cancelled on an error or once the receiver is gone; also interrupts running searches.
+    stop: CancelToken,
+}
+
+impl Pool {
+    /// Takes the newest task of worker `id`, or steals the oldest task of another one.
+    fn take(&self, id: usize) -> Option<Task> {
+        let n = self.queues.len();
+        let task = (0..n).find_map(|i| {
+            let mut queue = self.queues[(id + i) % n].lock().unwrap();
+            match i {
+                0 => queue.pop_back(),
+                _ => queue.pop_front(),
+            }
+        })?;
+        self.queued.fetch_sub(1, Ordering::SeqCst);
+        Some(task)
+    }
+}
+
+struct Worker<'p> {
+    pool: &'p Pool,
+    id: usize,
+}
+
+impl Donor for Worker<'_> {
+    fn wants_work(&self) -> bool {
+        self.pool.idle.load(Ordering::Relaxed) > self.pool.queued.load(Ordering::Relaxed)
+    }
+
+    fn donate(&self, task: Task) {
+        self.pool.pending.fetch_add(1, Ordering::SeqCst);
+        self.pool.queued.fetch_add(1, Ordering::SeqCst);
+        self.pool.queues[self.id].lock().unwrap().push_back(task);
+    }
+}
+
+type Answer = Result<Vec<Data>, Error>;
+
+/// Answers of `World::run_parallel`, as they arrive. Dropping it stops the search.
+pub struct Answers {
+    receiver: Receiver<Answer>,
+    stop: CancelToken,
+}
+
+impl Iterator for Answers {
+    type Item = Answer;
+
+    fn next(&mut self) -> Option<Answer> {
+        self.receiver.recv().ok()
+    }
+}
+
+impl Drop for Answers {
+    fn drop(&mut self) {
+        self.stop.cancel();
+    }
+}
+
+/// Starts searching `goals` on a thread of its own, see `run`.
+pub(crate) fn spawn(world: Arc<World>, goals: Vec<Data>, options: Parallel) -> Answers {
+    let (sender, receiver) = mpsc::sync_channel(ANSWER_BUFFER);
+    let stop = CancelToken::new();
+    let token = stop.clone();
+    thread::spawn(move || run(&world, goals, options, sender, token));
+    Answers { receiver, stop }
+}
+
+/// Searches `goals` on a pool of threads, each splitting off clause alternatives of its
+/// choicepoints while other threads are idle. Queries with cut run on one thread, as cut
+/// prunes alternatives that may already have been handed to another.
+fn run(
+    world: &World,
+    goals: Vec<Data>,
+    options: Parallel,
+    sender: SyncSender<Answer>,
+    stop: CancelToken,
+) {
+    let uses_cut = world.uses_cut || goals.iter().any(|g| g.as_symbol() == Some(Atom::CUT));
+    let threads = match uses_cut {
+        true => 1,
+        false => options.threads.max(1),
+    };
+    let pool = Pool {
+        queues: (0..threads).map(|_| Mutex::default()).collect(),
+        pending: AtomicUsize::new(1),
+        queued: AtomicUsize::new(1),
+        idle: AtomicUsize::new(0),
+        stop,
+    };
+    pool.queues[0].lock().unwrap().push_back(Task {
+        template: goals.iter().rev().cloned().collect(),
+        goals,
+        path: vec![],
+        split: None,
+    });
+
+    let mut answers: Vec<_> = thread::scope(|s| {
+        let workers: Vec<_> = (0..threads)
+            .map(|id| {
+                let (pool, sender) = (&pool, sender.clone());
+                s.spawn(move || work(world, Worker { pool, id }, options.ordered, sender))
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|w| w.join().unwrap())
+            .collect()
+    });
+    if pool.stop.is_cancelled() {
+        return;
+    }
+    answers.sort_by(|(l, _), (r, _): &(Vec<u32>, _)| l.cmp(r));
+    for (_, answer) in answers {
+        if sender.send(Ok(answer)).is_err() {
+            break;
+        }
+    }
+}
+
+/// Runs tasks until none is left, sending answers or, if `ordered`, returning them with
+/// their paths.
+fn work(
+    world: &World,
+    worker: Worker,
+    ordered: bool,
+    sender: SyncSender<Answer>,
+) -> Vec<(Vec<u32>, Vec<Data>)> {
+    let pool = worker.pool;
+    let mut answers = vec![];
+    let mut sleep = IDLE_SLEEP;
+    let limits = Limits {
+        cancel: Some(pool.stop.clone()),
+        ..Limits::default()
+    };
+    while !pool.stop.is_cancelled() {
+        let Some(task) = pool.take(worker.id) else {
+            if pool.pending.load(Ordering::SeqCst) == 0 {
+                break;
+            }
+            pool.idle.fetch_add(1, Ordering::SeqCst);
+            thread::sleep(sleep);
+            pool.idle.fetch_sub(1, Ordering::SeqCst);
+            sleep = (sleep * 2).min(MAX_IDLE_SLEEP);
+            continue;
+        };
+        sleep = IDLE_SLEEP;
+        let mut rt = Runtime::from_task(&task, &worker, ordered, &limits);
+        while !pool.stop.is_cancelled() {
+            match rt.next(world) {
+                Ok(Some(answer)) if ordered => answers.push((rt.path().unwrap().to_vec(), answer)),
+                Ok(Some(answer)) => {
+                    if sender.send(Ok(answer)).is_err() {
+                        pool.stop.cancel();
+                    }
+                }
+                Ok(None) => break,
+                // Stopped from elsewhere.
+                Err(Error::Cancelled) if pool.stop.is_cancelled() => break,
+                Err(e) => {
+                    pool.stop.cancel();
+                    let _ = sender.send(Err(e));
+                }
+            }
+        }
+        pool.pending.fetch_sub(1, Ordering::SeqCst);
+    }
+    answers
+}
@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+
+use crate::{
+    data::Data,
+    limits::Limits,
+    rule_map::Key,
+    runtime::{Error, Runtime},
+    stream::Solutions,
+    user_data::UserData,
+    world::{VariableScope, World},
+};
+
+/// Query variable standing for a parameter, to bind it without looking up its name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Param(pub(crate) usize);
+
+/// Goals converted once and run many times, with named variables as parameters.
+pub struct PreparedQuery<'a> {
+    world: &'a World,
+    goals: Vec<Data>,
+    /// Predicate of each goal, `None` if it is a variable.
+    keys: Vec<Option<Key>>,
+    params: HashMap<String, Param>,
+}
+
+impl<'a> PreparedQuery<'a> {
+    pub(crate) fn new(world: &'a World, goals: &[UserData]) -> Self {
+        let mut scope = VariableScope::new();
+        let goals = scope.new_data_vec(goals);
+        let params = scope
+            .into_variables()
+            .filter_map(|(name, var)| match var {
+                Data::Variable(n) => Some((name, Param(n))),
+                _ => None,
+            })
+            .collect();
+        PreparedQuery {
+            world,
+            keys: goals.iter().map(Key::of).collect(),
+            goals,
+            params,
+        }
+    }
+
+    /// Names of the variables that can be bound when running.
+    pub fn params(&self) -> impl Iterator<Item = &str> {
+        self.params.keys().map(|s| s.as_str())
+    }
+
+    /// The parameter named `name`, if the goals have such a variable.
+    pub fn param(&self, name: &str) -> Option<Param> {
+        self.params.get(name).copied()
+    }
+
+    /// Runs the goals with the named variables bound to the given values.
+    pub fn run<F: FnMut(&[Data])>(
+        &self,
+        params: &[(&str, UserData)],
+        resolved_fn: F,
+    ) -> Result<(), Error> {
+        self.run_limited(params, &Limits::default(), resolved_fn)
+    }
+
+    /// Like `run`, aborting once the query uses up one of `limits`.
+    pub fn run_limited<F: FnMut(&[Data])>(
+        &self,
+        params: &[(&str, UserData)],
+        limits: &Limits,
+        resolved_fn: F,
+    ) -> Result<(), Error> {
+        let params = params
+            .iter()
+            .map(|(name, value)| {
+                let param = self
+                    .param(name)
+                    .ok_or_else(|| Error::UnknownParameter(name.to_string()))?;
+                Ok((param, VariableScope::new().new_data(value)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        self.run_with(&params, limits, resolved_fn)
+    }
+
+    /// Like `run_limited`, with parameters already looked up and values already converted.
+    /// Variables in a value are numbered apart from those of the goals.
+    pub fn run_with<F: FnMut(&[Data])>(
+        &self,
+        params: &[(Param, Data)],
+        limits: &Limits,
+        resolved_fn: F,
+    ) -> Result<(), Error> {
+        Runtime::run_with(
+            self.world,
+            &self.goals,
+            &self.keys,
+            params,
+            limits,
+            resolved_fn,
+        )
+    }
+
+    /// Answers as an async stream, with parameters as for `run_with`, yielding every
+    /// `yield_every` inferences, see `Solutions`. Always searches depth-first.
+    pub fn stream<'q>(
+        &'q self,
+        params: &'q [(Param, Data)],
+        limits: &Limits,
+        yield_every: u64,
+    ) -> Solutions<'q> {
+        let runtime = Runtime::with_params(&self.goals, &self.keys, params, limits);
+        Solutions::new(self.world, runtime, yield_every)
+    }
+}
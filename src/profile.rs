@@ -0,0 +1,320 @@
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    fmt,
+    time::{Duration, Instant},
+};
+
+use crate::{atom::Atom, data::Data, trace::predicate};
+
+/// What was counted of a predicate or clause. For a clause, calls are the calls that went
+/// on through it, and other ports those of such calls while they were in it.
+#[derive(Debug, Clone, Default)]
+pub struct Counts {
+    pub calls: u64,
+    pub exits: u64,
+    pub redos: u64,
+    pub fails: u64,
+    /// Clause heads, fact rows and foreign answers tried against the calls.
+    pub unifications: u64,
+    /// Time while a call was running, including the calls it made; recursive calls are
+    /// not counted twice.
+    pub inclusive: Duration,
+    /// Time while a call was running, excluding the calls it made.
+    pub exclusive: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct PredicateProfile {
+    pub name: Atom,
+    pub arity: usize,
+    pub counts: Counts,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClauseProfile {
+    /// Index of the clause in `World::rules`.
+    pub rule: usize,
+    pub counts: Counts,
+}
+
+/// Where a depth-first search spent its time, recorded by `Runtime::record_profile`.
+/// Predicates and clauses are sorted by inclusive time, most costly first.
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    pub predicates: Vec<PredicateProfile>,
+    pub clauses: Vec<ClauseProfile>,
+}
+
+/// Writes a table of the predicates, then one of the clauses.
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rows = self
+            .predicates
+            .iter()
+            .map(|p| (format!("{}/{}", p.name.name(), p.arity), &p.counts));
+        write_table(f, "Predicate", rows)?;
+        writeln!(f)?;
+        let rows = self
+            .clauses
+            .iter()
+            .map(|c| (format!("clause {}", c.rule), &c.counts));
+        write_table(f, "Clause", rows)
+    }
+}
+
+fn write_table<'c>(
+    f: &mut fmt::Formatter<'_>,
+    title: &str,
+    rows: impl Iterator<Item = (String, &'c Counts)>,
+) -> fmt::Result {
+    writeln!(
+        f,
+        "{:<24} {:>9} {:>9} {:>9} {:>9} {:>9} {:>12} {:>12}",
+        title, "Calls", "Exits", "Redos", "Fails", "Unify", "Incl (ms)", "Excl (ms)"
+    )?;
+    for (name, c) in rows {
+        writeln!(
+            f,
+            "{:<24} {:>9} {:>9} {:>9} {:>9} {:>9} {:>12.3} {:>12.3}",
+            name,
+            c.calls,
+            c.exits,
+            c.redos,
+            c.fails,
+            c.unifications,
+            c.inclusive.as_secs_f64() * 1000.0,
+            c.exclusive.as_secs_f64() * 1000.0,
+        )?;
+    }
+    Ok(())
+}
+
+/// Counts of a predicate or clause, with how many of its calls are running.
+#[derive(Default)]
+struct Entry {
+    counts: Counts,
+    running: usize,
+    /// When the first of the running calls started running.
+    since: Option<Instant>,
+}
+
+impl Entry {
+    fn start(&mut self, now: Instant) {
+        self.running += 1;
+        if self.running == 1 {
+            self.since = Some(now);
+        }
+    }
+
+    fn stop(&mut self, now: Instant) {
+        self.running -= 1;
+        if self.running == 0 {
+            self.counts.inclusive += now - self.since.take().unwrap();
+        }
+    }
+}
+
+/// A traced call, at the same index as the runtime's frame.
+struct Frame {
+    predicate: (Atom, usize),
+    clause: Option<usize>,
+    /// The call whose body made this one.
+    parent: Option<usize>,
+    /// Whether this call is the one running or one of its ancestors.
+    running: bool,
+}
+
+/// Follows the ports of a traced search, charging the time between them to the call
+/// running and its ancestors.
+pub(crate) struct Profiler {
+    frames: Vec<Frame>,
+    current: Option<usize>,
+    /// The call that was running when the clock was paused.
+    paused: Option<Option<usize>>,
+    last: Instant,
+    predicates: HashMap<(Atom, usize), Entry>,
+    clauses: HashMap<usize, Entry>,
+}
+
+impl Profiler {
+    pub(crate) fn new() -> Self {
+        Profiler {
+            frames: vec![],
+            current: None,
+            paused: None,
+            last: Instant::now(),
+            predicates: HashMap::new(),
+            clauses: HashMap::new(),
+        }
+    }
+
+    /// Counts a call of `goal` made from the body of the call `parent`.
+    pub(crate) fn call(&mut self, goal: &Data, parent: Option<usize>) {
+        let now = self.tick();
+        let predicate = predicate(goal).unwrap_or((Atom::NIL, 0));
+        self.predicates.entry(predicate).or_default().counts.calls += 1;
+        self.frames.push(Frame {
+            predicate,
+            clause: None,
+            parent,
+            running: false,
+        });
+        self.run(Some(self.frames.len() - 1), now);
+    }
+
+    /// Counts trying a clause head, or a fact row or foreign answer if `clause` is `None`.
+    pub(crate) fn unify(&mut self, f: usize, clause: Option<usize>) {
+        self.entries(f, clause, |c| c.unifications += 1);
+    }
+
+    /// Records that the call `f` went on through `clause`.
+    pub(crate) fn enter(&mut self, f: usize, clause: Option<usize>) {
+        let now = self.tick();
+        let frame = &mut self.frames[f];
+        let old = std::mem::replace(&mut frame.clause, clause);
+        if frame.running {
+            if let Some(old) = old {
+                self.clauses.get_mut(&old).unwrap().stop(now);
+            }
+            if let Some(clause) = clause {
+                self.clauses.entry(clause).or_default().start(now);
+            }
+        }
+        if let Some(clause) = clause {
+            self.clauses.entry(clause).or_default().counts.calls += 1;
+        }
+    }
+
+    pub(crate) fn exit(&mut self, f: usize) {
+        let now = self.tick();
+        let clause = self.frames[f].clause;
+        self.entries(f, clause, |c| c.exits += 1);
+        self.run(self.frames[f].parent, now);
+    }
+
+    pub(crate) fn redo(&mut self, f: usize) {
+        self.tick();
+        let clause = self.frames[f].clause;
+        self.entries(f, clause, |c| c.redos += 1);
+    }
+
+    /// Counts the failure of the newest call, and forgets it.
+    pub(crate) fn fail(&mut self, f: usize) {
+        let now = self.tick();
+        let clause = self.frames[f].clause;
+        self.entries(f, clause, |c| c.fails += 1);
+        if self.frames[f].running {
+            self.run(self.frames[f].parent, now);
+        }
+        self.frames.pop();
+    }
+
+    /// Records that backtracking resumed the call `f`, or the query itself if `None`.
+    pub(crate) fn resume(&mut self, f: Option<usize>) {
+        let now = self.tick();
+        self.run(f, now);
+    }
+
+    /// Stops the clock until `unpause`, as if no call were running.
+    pub(crate) fn pause(&mut self) {
+        let now = self.tick();
+        self.paused = Some(self.current);
+        self.run(None, now);
+    }
+
+    pub(crate) fn unpause(&mut self) {
+        let now = Instant::now();
+        self.last = now;
+        if let Some(f) = self.paused.take() {
+            self.run(f, now);
+        }
+    }
+
+    /// What was recorded so far, counting the time of the calls still running until now.
+    pub(crate) fn profile(&self) -> Profile {
+        let now = Instant::now();
+        let counts = |entry: &Entry| {
+            let mut counts = entry.counts.clone();
+            if let Some(since) = entry.since {
+                counts.inclusive += now - since;
+            }
+            counts
+        };
+        let mut predicates: Vec<_> = self
+            .predicates
+            .iter()
+            .map(|(&(name, arity), entry)| PredicateProfile {
+                name,
+                arity,
+                counts: counts(entry),
+            })
+            .collect();
+        let mut clauses: Vec<_> = self
+            .clauses
+            .iter()
+            .map(|(&rule, entry)| ClauseProfile {
+                rule,
+                counts: counts(entry),
+            })
+            .collect();
+        predicates.sort_by_key(|p| (Reverse(p.counts.inclusive), p.name.name(), p.arity));
+        clauses.sort_by_key(|c| (Reverse(c.counts.inclusive), c.rule));
+        Profile {
+            predicates,
+            clauses,
+        }
+    }
+
+    /// Charges the time since the last port to the running call, returning now.
+    fn tick(&mut self) -> Instant {
+        let now = Instant::now();
+        if let Some(f) = self.current {
+            let elapsed = now - self.last;
+            let clause = self.frames[f].clause;
+            self.entries(f, clause, |c| c.exclusive += elapsed);
+        }
+        self.last = now;
+        now
+    }
+
+    fn entries(&mut self, f: usize, clause: Option<usize>, count: impl Fn(&mut Counts)) {
+        let frame = &self.frames[f];
+        count(&mut self.predicates.get_mut(&frame.predicate).unwrap().counts);
+        if let Some(clause) = clause {
+            count(&mut self.clauses.entry(clause).or_default().counts);
+        }
+    }
+
+    /// Makes `f` the running call: its ancestors start running unless they are already,
+    /// and the calls running until now that are not among them stop.
+    fn run(&mut self, f: Option<usize>, now: Instant) {
+        let mut common = f;
+        while let Some(i) = common {
+            let frame = &mut self.frames[i];
+            if frame.running {
+                break;
+            }
+            frame.running = true;
+            self.predicates
+                .get_mut(&frame.predicate)
+                .unwrap()
+                .start(now);
+            if let Some(clause) = frame.clause {
+                self.clauses.entry(clause).or_default().start(now);
+            }
+            common = frame.parent;
+        }
+        let mut stopped = self.current;
+        while stopped != common {
+            let frame = &mut self.frames[stopped.unwrap()];
+            frame.running = false;
+            self.predicates.get_mut(&frame.predicate).unwrap().stop(now);
+            if let Some(clause) = frame.clause {
+                self.clauses.get_mut(&clause).unwrap().stop(now);
+            }
+            stopped = frame.parent;
+        }
+        self.current = f;
+    }
+}
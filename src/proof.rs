@@ -0,0 +1,129 @@
+use std::fmt;
+
+use crate::{atom::Atom, data::Data, world::Rule};
+
+/// How a goal of an answer was proved, recorded by `Runtime::record_proofs`. Unbound
+/// variables are numbered by their index in the bindings, as for a `Tracer`.
+#[derive(Debug)]
+pub struct Proof {
+    /// The goal as bound in the answer.
+    pub goal: Data,
+    /// Index in `World::rules` of the clause that resolved the goal, `None` for a fact row
+    /// or a foreign answer.
+    pub rule: Option<usize>,
+    /// Values of the clause's variables in the answer, by their number in the clause.
+    pub substitution: Vec<(usize, Data)>,
+    /// Proofs of the predicate calls of the clause body, in the order they were made.
+    /// Goals resolved by `par` on other threads are left out.
+    pub children: Vec<Proof>,
+}
+
+impl Proof {
+    pub(crate) fn new(goal: Data, rule: Option<usize>, substitution: Vec<(usize, Data)>) -> Self {
+        Proof {
+            goal,
+            rule,
+            substitution,
+            children: vec![],
+        }
+    }
+
+    /// The proof as `(proof Goal Rule [(= _0 Value) ...] [Child ...])`, where `Rule` is the
+    /// clause index, or `none`.
+    pub fn to_term(&self) -> Data {
+        enum Task<'p> {
+            Visit(&'p Proof),
+            Build(&'p Proof),
+        }
+
+        let mut tasks = vec![Task::Visit(self)];
+        let mut done: Vec<Data> = vec![];
+        while let Some(task) = tasks.pop() {
+            match task {
+                Task::Visit(proof) => {
+                    tasks.push(Task::Build(proof));
+                    tasks.extend(proof.children.iter().rev().map(Task::Visit));
+                }
+                Task::Build(proof) => {
+                    let children = list(done.split_off(done.len() - proof.children.len()));
+                    let rule = match proof.rule {
+                        Some(i) => Atom::new(&i.to_string()),
+                        None => Atom::new("none"),
+                    };
+                    let substitution = proof.substitution.iter().map(|(n, value)| {
+                        let var = Data::Symbol(Atom::new(&format!("_{}", n)));
+                        Data::Term([Data::Symbol(Atom::EQ), var, value.clone()].into())
+                    });
+                    done.push(Data::Term(
+                        [
+                            Data::Symbol(Atom::new("proof")),
+                            proof.goal.clone(),
+                            Data::Symbol(rule),
+                            list(substitution.collect()),
+                            children,
+                        ]
+                        .into(),
+                    ));
+                }
+            }
+        }
+        done.pop().unwrap()
+    }
+}
+
+/// Writes the proof as indented text, each goal followed by the clause and substitution
+/// that resolved it.
+impl fmt::Display for Proof {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut stack = vec![(self, 0)];
+        while let Some((proof, depth)) = stack.pop() {
+            write!(f, "{:indent$}{}", "", proof.goal, indent = depth * 2)?;
+            if let Some(rule) = proof.rule {
+                write!(f, "  [clause {}", rule)?;
+                for (i, (n, value)) in proof.substitution.iter().enumerate() {
+                    let sep = if i == 0 { ":" } else { "," };
+                    write!(f, "{} _{} = {}", sep, n, value)?;
+                }
+                write!(f, "]")?;
+            }
+            writeln!(f)?;
+            stack.extend(proof.children.iter().rev().map(|c| (c, depth + 1)));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Proof {
+    fn drop(&mut self) {
+        // Detach subproofs onto a heap stack so that dropping deep proofs does not recurse.
+        let mut stack = std::mem::take(&mut self.children);
+        while let Some(mut proof) = stack.pop() {
+            stack.append(&mut proof.children);
+        }
+    }
+}
+
+fn list(items: Vec<Data>) -> Data {
+    let nil = Data::Symbol(Atom::NIL);
+    items.into_iter().rev().fold(nil, |tail, head| {
+        Data::Term([Data::Symbol(Atom::CONS), head, tail].into())
+    })
+}
+
+/// The first occurrence of each of the clause's variables, by number.
+pub(crate) fn clause_variables(rule: &Rule) -> Vec<Option<&Data>> {
+    let mut vars = vec![None; rule.var_num];
+    let mut stack: Vec<_> = std::iter::once(&rule.head).chain(&*rule.body).collect();
+    while let Some(data) = stack.pop() {
+        match data {
+            Data::Variable(n) => {
+                if let Some(var) = vars.get_mut(*n) {
+                    var.get_or_insert(data);
+                }
+            }
+            Data::Symbol(_) => {}
+            Data::Term(v) => stack.extend(v.iter()),
+        }
+    }
+    vars
+}
@@ -1,51 +1,470 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    hash::{BuildHasherDefault, Hasher},
+    sync::OnceLock,
+};
 
-use crate::{bindings::Bindings, data::Data, world::Rule};
+use crate::{
+    atom::Atom,
+    bindings::{Bindings, Instance},
+    data::Data,
+    rng::Rng,
+    world::Rule,
+};
 
+/// Indexing key of a clause head or a goal: a bare symbol or a functor/arity pair.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Key {
+    Symbol(Atom),
+    Term(Atom, usize),
+}
+
+impl Key {
+    pub(crate) fn term(functor: Atom, len: usize) -> Key {
+        Key::Term(functor, len)
+    }
+
+    pub(crate) fn of(data: &Data) -> Option<Key> {
+        match data {
+            Data::Symbol(s) => Some(Key::Symbol(*s)),
+            Data::Term(v) => match v.first() {
+                Some(Data::Symbol(f)) => Some(Key::term(*f, v.len())),
+                _ => None,
+            },
+            Data::Variable(_) => None,
+        }
+    }
+
+    /// Name and arity of the predicate.
+    pub(crate) fn predicate(self) -> (Atom, usize) {
+        match self {
+            Key::Symbol(s) => (s, 0),
+            Key::Term(f, len) => (f, len - 1),
+        }
+    }
+
+    pub(crate) fn of_instance<'a>(bindings: &Bindings<'a>, instance: Instance<'a>) -> Option<Key> {
+        let instance = bindings.resolve(instance);
+        match instance.data() {
+            Data::Term(v) => match v.first() {
+                Some(f) => match bindings.resolve(Instance::new(f, instance.base())).data() {
+                    Data::Symbol(f) => Some(Key::term(*f, v.len())),
+                    _ => None,
+                },
+                None => None,
+            },
+            data => Key::of(data),
+        }
+    }
+}
+
+/// Multiply-rotate hasher for keys, which are a few machine words and never chosen by an
+/// attacker, so SipHash's strength is not worth its cost on every call.
+#[derive(Default)]
+pub(crate) struct FxHasher(u64);
+
+impl Hasher for FxHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.write_u64(b as u64);
+        }
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.0 = (self.0.rotate_left(5) ^ i).wrapping_mul(0x51_7c_c1_b7_27_22_0a_95);
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Map keyed by predicate.
+pub(crate) type KeyMap<V> = HashMap<Key, V, BuildHasherDefault<FxHasher>>;
+
+/// Predicates with fewer clauses than this are only indexed on their first argument.
+const JIT_MIN_CLAUSES: usize = 8;
+
+/// Hash index of a predicate's clauses on one argument position.
+struct ArgIndex {
+    by_key: KeyMap<Vec<usize>>,
+    unindexed: Vec<usize>,
+}
+
+impl ArgIndex {
+    fn new(clauses: &[usize], rules: &[Rule], position: usize) -> Self {
+        let mut by_key = KeyMap::<Vec<usize>>::default();
+        let mut unindexed = Vec::new();
+        for &i in clauses {
+            let key = match &rules[i].head {
+                Data::Term(v) => v.get(position + 1).and_then(Key::of),
+                _ => None,
+            };
+            match key {
+                Some(key) => by_key.entry(key).or_default().push(i),
+                None => unindexed.push(i),
+            }
+        }
+        ArgIndex { by_key, unindexed }
+    }
+
+    fn get(&self, key: Key) -> Candidates<'_> {
+        Candidates::new(
+            self.by_key.get(&key).map_or(&[], |v| v.as_slice()),
+            &self.unindexed,
+        )
+    }
+}
+
+/// Clauses of one predicate with per-argument indexes.
+/// The first-argument index is built eagerly, the others on the first call binding that argument.
+struct Predicate {
+    clauses: Vec<usize>,
+    indexes: Box<[OnceLock<ArgIndex>]>,
+}
+
+impl Predicate {
+    fn new(clauses: Vec<usize>, rules: &[Rule], arity: usize) -> Self {
+        let indexes: Box<[_]> = (1..arity).map(|_| OnceLock::new()).collect();
+        if let Some(first) = indexes.first() {
+            let _ = first.set(ArgIndex::new(&clauses, rules, 0));
+        }
+        Predicate { clauses, indexes }
+    }
+
+    /// Selects the most selective index among the bound arguments.
+    fn get<'a>(
+        &'a self,
+        rules: &[Rule],
+        bindings: &Bindings<'a>,
+        args: &'a [Data],
+        base: usize,
+    ) -> Candidates<'a> {
+        let mut best = Candidates::new(&self.clauses, &[]);
+        for (position, arg) in args.iter().enumerate() {
+            if position > 0 && self.clauses.len() < JIT_MIN_CLAUSES {
+                break;
+            }
+            let index = &self.indexes[position];
+            // No clause has a symbol or term at this position.
+            if index.get().is_some_and(|index| index.by_key.is_empty()) {
+                continue;
+            }
+            let Some(key) = Key::of_instance(bindings, Instance::new(arg, base)) else {
+                continue;
+            };
+            let candidates = index
+                .get_or_init(|| ArgIndex::new(&self.clauses, rules, position))
+                .get(key);
+            if candidates.len() < best.len() {
+                best = candidates;
+            }
+        }
+        best
+    }
+}
+
+/// Clause index keyed by functor/arity with just-in-time argument indexing.
+#[derive(Default)]
 pub struct RuleMap {
-    map: HashMap<*const Data, Vec<usize>>,
+    predicates: KeyMap<Predicate>,
+    by_arity: HashMap<usize, Vec<usize>>,
+    generic: HashMap<usize, Vec<usize>>,
+    var_heads: Vec<usize>,
     all: Vec<usize>,
 }
 
 impl RuleMap {
     pub fn new() -> Self {
-        RuleMap {
-            map: Default::default(),
-            all: Vec::new(),
-        }
+        Default::default()
     }
 
-    pub fn from_rules(rules: &Vec<Rule>) -> Self {
-        let mut map = HashMap::<*const Data, Vec<usize>>::new();
-        for rule in rules {
-            for sub_goal in rule.body.iter() {
-                let key = sub_goal as *const Data;
-                let mut rule_indices = Vec::new();
-                for (i, rule) in rules.iter().enumerate() {
-                    if unify(sub_goal, &rule.head) {
-                        rule_indices.push(i);
-                    }
-                }
-                map.insert(key, rule_indices);
+    pub fn from_rules(rules: &[Rule]) -> Self {
+        let mut own = HashMap::<Key, Vec<usize>>::new();
+        let mut by_arity = HashMap::<usize, Vec<usize>>::new();
+        let mut generic = HashMap::<usize, Vec<usize>>::new();
+        let mut var_heads = Vec::new();
+        for (i, rule) in rules.iter().enumerate() {
+            if let Data::Term(v) = &rule.head {
+                by_arity.entry(v.len()).or_default().push(i);
+            }
+            match Key::of(&rule.head) {
+                Some(key) => own.entry(key).or_default().push(i),
+                None => match &rule.head {
+                    Data::Term(v) => generic.entry(v.len()).or_default().push(i),
+                    _ => var_heads.push(i),
+                },
             }
         }
+
+        // Clauses whose head has a variable functor match every predicate of their arity.
+        let generic: HashMap<_, _> = generic
+            .into_iter()
+            .map(|(arity, v)| (arity, merge(&v, &var_heads)))
+            .collect();
+        let predicates = own
+            .into_iter()
+            .map(|(key, v)| {
+                let (shared, arity) = match key {
+                    Key::Term(_, arity) => (generic.get(&arity).unwrap_or(&var_heads), arity),
+                    Key::Symbol(_) => (&var_heads, 0),
+                };
+                (key, Predicate::new(merge(&v, shared), rules, arity))
+            })
+            .collect();
+        let by_arity = by_arity
+            .into_iter()
+            .map(|(arity, v)| (arity, merge(&v, &var_heads)))
+            .collect();
+
         RuleMap {
-            map,
+            predicates,
+            by_arity,
+            generic,
+            var_heads,
             all: (0..rules.len()).collect(),
         }
     }
 
-    #[inline]
-    pub fn get(&self, data: *const Data) -> &[usize] {
-        self.map.get(&data).unwrap_or(&self.all)
+    /// The clauses of predicate `key`, those whose heads have a variable functor included,
+    /// in source order.
+    pub(crate) fn clauses(&self, key: Key) -> &[usize] {
+        match (self.predicates.get(&key), key) {
+            (Some(predicate), _) => &predicate.clauses,
+            (None, Key::Term(_, arity)) => self.generic.get(&arity).unwrap_or(&self.var_heads),
+            (None, Key::Symbol(_)) => &self.var_heads,
+        }
+    }
+
+    /// Returns the clauses whose heads may unify with `goal` of predicate `key`, in source
+    /// order.
+    pub(crate) fn get<'a>(
+        &'a self,
+        rules: &[Rule],
+        bindings: &Bindings<'a>,
+        goal: Instance<'a>,
+        key: Option<Key>,
+    ) -> Candidates<'a> {
+        let goal = bindings.resolve(goal);
+        match (goal.data(), key) {
+            (Data::Variable(_), _) => Candidates::new(&self.all, &[]),
+            (Data::Term(v), Some(key @ Key::Term(_, arity))) => match self.predicates.get(&key) {
+                Some(predicate) => predicate.get(rules, bindings, &v[1..], goal.base()),
+                None => Candidates::new(self.generic.get(&arity).unwrap_or(&self.var_heads), &[]),
+            },
+            (Data::Term(v), _) => {
+                Candidates::new(self.by_arity.get(&v.len()).unwrap_or(&self.var_heads), &[])
+            }
+            (_, key) => Candidates::new(
+                key.and_then(|key| self.predicates.get(&key))
+                    .map_or(&self.var_heads, |p| &p.clauses),
+                &[],
+            ),
+        }
+    }
+}
+
+/// Iterator merging two ascending lists of clause indices, or giving them in an order set by
+/// `shuffle`.
+#[derive(Debug, Clone, Default)]
+pub struct Candidates<'a> {
+    left: &'a [usize],
+    right: &'a [usize],
+    /// Candidates in an order of their own, last one first; taken before the slices.
+    ordered: Vec<usize>,
+}
+
+impl<'a> Candidates<'a> {
+    fn new(left: &'a [usize], right: &'a [usize]) -> Self {
+        Candidates {
+            left,
+            right,
+            ordered: Vec::new(),
+        }
+    }
+
+    pub(crate) fn from_vec(mut rules: Vec<usize>) -> Self {
+        rules.reverse();
+        Candidates {
+            ordered: rules,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ordered.is_empty() && self.left.is_empty() && self.right.is_empty()
+    }
+
+    /// Puts the remaining candidates in a random order.
+    pub(crate) fn shuffle(&mut self, rng: &mut Rng) {
+        let mut rules: Vec<_> = self.by_ref().collect();
+        rng.shuffle(&mut rules);
+        self.ordered = rules;
     }
 }
 
-fn unify(left: &Data, right: &Data) -> bool {
-    let mut bindings = Bindings::new();
-    bindings.push(left.max_var());
-    let left = bindings.instance(left);
-    bindings.push(right.max_var());
-    let right = bindings.instance(right);
-    bindings.unify(left, right)
+impl<'a> Iterator for Candidates<'a> {
+    type Item = usize;
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.ordered.len() + self.left.len() + self.right.len();
+        (len, Some(len))
+    }
+
+    fn next(&mut self) -> Option<usize> {
+        if let Some(i) = self.ordered.pop() {
+            return Some(i);
+        }
+        match (self.left.split_first(), self.right.split_first()) {
+            (Some((&l, left)), Some((&r, _))) if l < r => {
+                self.left = left;
+                Some(l)
+            }
+            (_, Some((&r, right))) => {
+                self.right = right;
+                Some(r)
+            }
+            (Some((&l, left)), None) => {
+                self.left = left;
+                Some(l)
+            }
+            (None, None) => None,
+        }
+    }
+}
+
+impl<'a> ExactSizeIterator for Candidates<'a> {}
+
+fn merge(left: &[usize], right: &[usize]) -> Vec<usize> {
+    Candidates::new(left, right).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        user_data::UserData,
+        world::{VariableScope, World},
+    };
+
+    /// Reads `(f {x} a)` notation.
+    fn parse(text: &str) -> UserData {
+        let text = text.replace('(', " ( ").replace(')', " ) ");
+        let mut stack = vec![vec![]];
+        for token in text.split_whitespace() {
+            match token {
+                "(" => stack.push(vec![]),
+                ")" => {
+                    let term = UserData::Term(stack.pop().unwrap());
+                    stack.last_mut().unwrap().push(term);
+                }
+                _ => stack
+                    .last_mut()
+                    .unwrap()
+                    .push(match token.strip_prefix('{') {
+                        Some(name) => UserData::Variable(name.trim_end_matches('}').to_owned()),
+                        None => UserData::Symbol(token.to_owned()),
+                    }),
+            }
+        }
+        stack.pop().unwrap().pop().unwrap()
+    }
+
+    fn world(heads: &[String]) -> World {
+        World::new(heads.iter().map(|head| vec![parse(head)]).collect())
+    }
+
+    /// Checks that the candidates of `goal` are in source order and keep every clause a full
+    /// scan unifies with it. Returns how many there are.
+    fn check(world: &World, goal: &str) -> usize {
+        let goal = VariableScope::new().new_data(&parse(goal));
+        let unifies = |i: usize| {
+            let mut bindings = Bindings::new();
+            bindings.alloc(goal.max_var() + 1);
+            let base = bindings.alloc(world.rules[i].var_num);
+            bindings.unify(
+                Instance::new(&goal, 0),
+                Instance::new(&world.rules[i].head, base),
+            )
+        };
+        let mut bindings = Bindings::new();
+        bindings.alloc(goal.max_var() + 1);
+        let instance = Instance::new(&goal, 0);
+        let key = Key::of_instance(&bindings, instance);
+        let candidates: Vec<_> = world
+            .rule_map
+            .get(&world.rules, &bindings, instance, key)
+            .collect();
+        assert!(candidates.windows(2).all(|w| w[0] < w[1]), "{goal}");
+        let scan: Vec<_> = (0..world.rules.len()).filter(|&i| unifies(i)).collect();
+        let indexed: Vec<_> = candidates.iter().copied().filter(|&i| unifies(i)).collect();
+        assert_eq!(indexed, scan, "{goal}");
+        candidates.len()
+    }
+
+    #[test]
+    fn candidates_match_a_full_scan() {
+        let mut heads: Vec<_> = (0..12).map(|i| format!("(p a{} b{i})", i % 3)).collect();
+        heads.extend(
+            [
+                "(p {x} b5)",
+                "(p a0 {y})",
+                "(p (f c) b7)",
+                "({f} a0 b3)",
+                "{h}",
+                "(p a1)",
+                "(q a0 b0)",
+                "(q {x} b1)",
+                "p",
+            ]
+            .map(String::from),
+        );
+        let world = world(&heads);
+        for goal in [
+            "(p a0 {y})",
+            "(p {x} b5)",
+            "(p a1 b4)",
+            "(p {x} {y})",
+            "(p (f {z}) {y})",
+            "(p a9 b9)",
+            "({g} a0 b3)",
+            "({g} {x} {y})",
+            "(q a0 {y})",
+            "(q {x} b1)",
+            "(p)",
+            "(p a1)",
+            "p",
+            "q",
+            "(r a b)",
+            "{x}",
+        ] {
+            check(&world, goal);
+        }
+    }
+
+    #[test]
+    fn indexes_later_arguments_just_in_time() {
+        let heads: Vec<_> = (0..JIT_MIN_CLAUSES)
+            .map(|i| format!("(p a b{i})"))
+            .collect();
+        // Bound on the second argument only: the index on it picks the one clause.
+        assert_eq!(check(&world(&heads), "(p {x} b3)"), 1);
+        // With fewer clauses, only the first argument is indexed.
+        let few = &heads[..JIT_MIN_CLAUSES - 1];
+        assert_eq!(check(&world(few), "(p {x} b3)"), few.len());
+        // Variable heads are candidates whichever argument is indexed.
+        let mut heads = heads;
+        heads.insert(2, "(p a {y})".to_owned());
+        heads.insert(4, "{h}".to_owned());
+        heads.insert(6, "({f} {x} b3)".to_owned());
+        assert_eq!(check(&world(&heads), "(p {x} b3)"), 4);
+    }
 }
@@ -1,94 +1,1527 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::atomic::Ordering,
+    task::{Context, Poll, Waker},
+    thread,
+    time::Instant,
+};
+
 use crate::{
-    bindings::{Bindings, Instance},
+    atom::Atom,
+    bindings::{Bindings, Delta, Instance, OccursCheck},
     data::Data,
-    world::World,
+    fact_table::Rows,
+    foreign::{block_on, ForeignFuture},
+    limits::{Limit, Limits},
+    parallel::{Split, Task},
+    prepared_query::Param,
+    profile::{Profile, Profiler},
+    proof::{self, Proof},
+    rng::Rng,
+    rule_map::{Candidates, Key},
+    snapshot::Snapshot,
+    strategy::{self, SearchStrategy},
+    stream::Solutions,
+    trace::Tracer,
+    why_not::{Failure, Frontier},
+    world::{ClauseOrder, World},
 };
 
-pub struct Runtime<'a, F: FnMut(&[Data])> {
+/// Error aborting a query.
+#[derive(Debug, Clone)]
+pub enum Error {
+    /// Unification under `OccursCheck::Error` would have created this cyclic term.
+    OccursCheck(Data),
+    /// A prepared query was given a parameter its goals do not mention.
+    UnknownParameter(String),
+    /// A search strategy keeping several resolvents met this cyclic term, which it cannot copy.
+    CyclicTerm(Data),
+    /// The query used up one of its `Limits`.
+    LimitExceeded(Limit),
+    /// The query's `CancelToken` was cancelled.
+    Cancelled,
+    /// The query ran past its timeout.
+    TimedOut,
+    /// The search strategy cannot resolve this builtin.
+    Unsupported(&'static str),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::OccursCheck(d) => write!(f, "occurs check failed on {}", d),
+            Error::UnknownParameter(name) => write!(f, "unknown parameter {}", name),
+            Error::CyclicTerm(d) => write!(f, "cannot copy cyclic term {}", d),
+            Error::LimitExceeded(limit) => write!(f, "{} exceeded", limit),
+            Error::Cancelled => write!(f, "query cancelled"),
+            Error::TimedOut => write!(f, "query timed out"),
+            Error::Unsupported(name) => {
+                write!(f, "{} is not supported by this search strategy", name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Goal lists are linked through an arena so that choicepoints can share their tails.
+/// Nodes pushed after a choicepoint are freed when backtracking to it.
+struct GoalNode<'a> {
+    goal: Instance<'a>,
+    next: Option<usize>,
+    /// Length of the list starting here.
+    len: usize,
+}
+
+/// Deterministic runs compact the goal arena once it grows past this many nodes.
+const COMPACT_MIN_GOALS: usize = 1024;
+
+/// Deterministic runs compact the bindings once they grow past this many variables.
+const COMPACT_MIN_BINDINGS: usize = 1 << 16;
+
+/// Takes over the clause alternatives of new choicepoints, for searching them elsewhere.
+pub(crate) trait Donor: Send + Sync {
+    fn wants_work(&self) -> bool;
+    fn donate(&self, task: Task);
+}
+
+pub struct Runtime<'a> {
     initial_goals: Vec<Instance<'a>>,
-    resolved_fn: F,
-    goals: Vec<Instance<'a>>,
+    started: bool,
+    goals: Option<usize>,
+    arena: Vec<GoalNode<'a>>,
+    /// Predicates of the first nodes of `arena`, known before the search started.
+    keys: Vec<Option<Key>>,
+    compacted_len: usize,
     bindings: Bindings<'a>,
-    steps: Vec<Step<'a>>,
-    cut: std::rc::Rc<String>,
+    live_bindings: usize,
+    /// Variables below this index were copied from another runtime, which reads answers
+    /// back by index, so they are never compacted.
+    shared_len: usize,
+    choicepoints: Vec<Choicepoint<'a>>,
+    /// Ordinals of the alternatives taken at branching choicepoints, when recorded.
+    path: Option<Vec<u32>>,
+    donor: Option<&'a dyn Donor>,
+    /// Alternatives of the first goal handed over by the runtime this one was split from.
+    split: Option<Split>,
+    /// Tasks to search once this one is exhausted, unless a cut prunes them.
+    tasks: &'a [Task],
+    /// Shuffles clause alternatives under `ClauseOrder::Random`.
+    rng: Option<Rng>,
+    limits: Limits,
+    deadline: Option<Instant>,
+    inferences: u64,
+    answers: usize,
+    /// Whether an answer past the answers limit was found and held back.
+    held: bool,
+    /// Inferences left before `next` yields, when polled as a stream.
+    fuel: Option<u64>,
+    /// Whether `next` returned early to yield, and resumes without backtracking.
+    yielded: bool,
+    /// Wakes the stream polling this runtime once a pending foreign call can progress.
+    waker: Option<Waker>,
+    call: Option<Call>,
+    tracer: Option<Box<dyn Tracer + 'a>>,
+    /// Whether frames are kept for `proof`.
+    record_proofs: bool,
+    /// Goals failed without being resolved, when recorded for `why_not`.
+    frontier: Option<Frontier>,
+    profiler: Option<Profiler>,
+    /// Traced calls that have not failed yet, oldest first.
+    frames: Vec<Frame<'a>>,
+    /// The traced call whose body is being resolved, the parent of the next traced call.
+    trace_parent: Option<usize>,
+    /// Number of the query's variables, which come first in the bindings.
+    query_vars: usize,
 }
 
-struct Step<'a> {
+/// A traced call. Once a clause with a body is entered, a marker goal below the body
+/// reports the exit of the call.
+struct Frame<'a> {
     goal: Instance<'a>,
-    goal_index: usize,
-    rule_indices: std::slice::Iter<'a, usize>,
+    /// The goal as called, which is how it is reported failing.
+    called: Data,
+    /// The call whose body made this one.
+    parent: Option<usize>,
+    depth: usize,
+    clause: Option<usize>,
+    /// Base of the variables of `clause`.
+    base: usize,
+    /// Whether the call exited since it was last called or retried.
+    exited: bool,
+    /// Whether a clause, fact row or foreign answer ever resolved the call.
+    resolved: bool,
 }
 
-impl<'a, F: FnMut(&[Data])> Runtime<'a, F> {
-    pub fn run(world: &'a World, goals: &'a [Data], resolved_fn: F) {
+/// Goal data of exit markers, told apart by address; their base is the index of the frame.
+static EXIT: Data = Data::Symbol(Atom::NIL);
+
+#[derive(Clone, Copy)]
+enum Port {
+    Call,
+    Exit,
+    Redo,
+    Fail,
+}
+
+/// A call of a foreign predicate waiting for its answers.
+struct Call {
+    /// Goal node of the call.
+    node: usize,
+    future: ForeignFuture,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Builtin {
+    UnifyWithOccursCheck,
+    Par,
+}
+
+/// A goal with clauses or fact rows left to try on backtracking.
+struct Choicepoint<'a> {
+    /// Goal node of the call, which the arena keeps as long as the choicepoint.
+    node: usize,
+    arena_len: usize,
+    occurs_check: OccursCheck,
+    rule_indices: Candidates<'a>,
+    rows: Rows<'a>,
+    /// Whether this choicepoint had more than one alternative, and so appears in `path`.
+    branching: bool,
+    path_len: usize,
+    ordinal: u32,
+    /// Kept apart as only `par` goals, foreign calls and traced runs need it, so that the
+    /// choicepoints of plain clauses stay small.
+    extra: Option<Box<Extra<'a>>>,
+}
+
+/// Alternatives of a choicepoint other than clauses and fact rows, and its trace state.
+#[derive(Default)]
+struct Extra<'a> {
+    join: Option<Join<'a>>,
+    /// Answer rows of a foreign predicate left, last one first.
+    foreign: Vec<Vec<Atom>>,
+    /// Number of frames when the choicepoint was made, its own last if `traced`.
+    frames_len: usize,
+    traced: bool,
+    trace_parent: Option<usize>,
+}
+
+impl<'a> Choicepoint<'a> {
+    fn is_exhausted(&self) -> bool {
+        self.rule_indices.is_empty()
+            && self.rows.is_empty()
+            && self.extra.as_ref().is_none_or(|extra| {
+                extra.join.as_ref().is_none_or(|join| join.next.is_none())
+                    && extra.foreign.is_empty()
+            })
+    }
+}
+
+enum Alternative<'a> {
+    Rule(usize),
+    /// A fact row, whether it unified with the goal.
+    Row(bool),
+    Join(Vec<Delta<'a>>),
+    Foreign(Vec<Atom>),
+}
+
+/// Answers of the first conjunct of a join found at a time.
+const JOIN_BATCH: usize = 64;
+
+/// Most answers kept of each other conjunct of a join; a `par` goal with a conjunct that has
+/// more is resolved as a plain conjunction.
+const JOIN_MAX_ANSWERS: usize = 1024;
+
+/// Most inferences spent finding all answers of a conjunct other than the first of a join,
+/// beyond which it is resolved as a plain conjunction too.
+const JOIN_MAX_INFERENCES: u64 = 100_000;
+
+/// Cross product of the answers of independent conjuncts, in the order nested loops over
+/// them would take, which is the order of a sequential search. The first conjunct is the
+/// outer loop, so its answers are found a batch at a time as they are needed; those of the
+/// others are all found up front.
+struct Join<'a> {
+    /// Runtime finding the answers of the first conjunct, `None` once it has no more.
+    first: Option<Box<Runtime<'a>>>,
+    /// Answers of each conjunct, only the current batch for the first one.
+    answers: Vec<Vec<Delta<'a>>>,
+    /// Index of the next answer of each conjunct, `None` once all combinations were taken.
+    next: Option<Vec<usize>>,
+}
+
+impl<'a> Join<'a> {
+    fn new(first: Option<Box<Runtime<'a>>>, answers: Vec<Vec<Delta<'a>>>) -> Self {
+        let next = answers
+            .iter()
+            .all(|a| !a.is_empty())
+            .then(|| vec![0; answers.len()]);
+        Join {
+            first,
+            answers,
+            next,
+        }
+    }
+
+    /// Whether more than one combination may be left.
+    fn branching(&self) -> bool {
+        self.first.is_some() || self.answers.iter().map(|a| a.len()).product::<usize>() > 1
+    }
+
+    /// Takes the next combination, which must be left. The inferences of the first conjunct
+    /// count towards `inferences`, under `limits`.
+    fn next(
+        &mut self,
+        world: &'a World,
+        limits: &Limits,
+        inferences: &mut u64,
+    ) -> Result<Vec<Delta<'a>>, Error> {
+        let next = self.next.as_mut().unwrap();
+        let combination = next
+            .iter()
+            .zip(&self.answers)
+            .map(|(&i, answers)| answers[i].clone())
+            .collect();
+        for k in (0..next.len()).rev() {
+            next[k] += 1;
+            if next[k] < self.answers[k].len() {
+                return Ok(combination);
+            }
+            next[k] = 0;
+        }
+        // The batch of the first conjunct is used up.
+        self.answers[0].clear();
+        if let Some(first) = &mut self.first {
+            let used = first.inferences;
+            first.limits.inferences = limits
+                .inferences
+                .map(|max| used + max.saturating_sub(*inferences));
+            let (answers, exhausted) = first.take_answers(world, JOIN_BATCH)?;
+            *inferences += first.inferences - used;
+            self.answers[0] = answers;
+            if exhausted {
+                self.first = None;
+            }
+        }
+        if self.answers[0].is_empty() {
+            self.next = None;
+        }
+        Ok(combination)
+    }
+}
+
+impl<'a> Runtime<'a> {
+    pub fn run<F: FnMut(&[Data])>(
+        world: &'a World,
+        goals: &'a [Data],
+        resolved_fn: F,
+    ) -> Result<(), Error> {
+        Self::run_with(world, goals, &[], &[], &Limits::default(), resolved_fn)
+    }
+
+    /// Runs `goals` with each query variable of `params` bound to its value up front.
+    /// `keys` holds the predicate of each goal if known in advance, or is empty.
+    pub(crate) fn run_with<F: FnMut(&[Data])>(
+        world: &'a World,
+        goals: &'a [Data],
+        keys: &[Option<Key>],
+        params: &'a [(Param, Data)],
+        limits: &Limits,
+        mut resolved_fn: F,
+    ) -> Result<(), Error> {
+        if !matches!(world.strategy, SearchStrategy::DepthFirst) {
+            return strategy::run(world, goals, params, limits, resolved_fn);
+        }
+        if let Some(program) = world.program(limits) {
+            return program.run(goals, params, world.occurs_check, resolved_fn);
+        }
+        let mut rt = Self::with_params(goals, keys, params, limits);
+        while let Some(answer) = rt.next(world)? {
+            resolved_fn(&answer);
+        }
+        Ok(())
+    }
+
+    /// Starts resolving `goals` depth-first, as `run_with` does.
+    pub(crate) fn with_params(
+        goals: &'a [Data],
+        keys: &[Option<Key>],
+        params: &'a [(Param, Data)],
+        limits: &Limits,
+    ) -> Self {
+        let mut rt = Self::new(goals.iter().rev(), goals);
+        rt.set_limits(limits.clone());
+        // Goals are pushed last one first, so the first goal's node is the last.
+        rt.keys = keys.iter().rev().copied().collect();
+        for (param, value) in params {
+            let base = rt.bindings.alloc(value.max_var());
+            if !rt
+                .bindings
+                .unify_variable(param.0, Instance::new(value, base))
+            {
+                // Nothing is left to search.
+                rt.started = true;
+                rt.goals = None;
+                break;
+            }
+        }
+        rt
+    }
+
+    /// Starts resolving `goals` depth-first without borrowing them: they are built in the
+    /// bindings, which the runtime owns.
+    pub(crate) fn owning(goals: &[Data]) -> Self {
         let mut bindings = Bindings::new();
         let var_num = goals.iter().map(|d| d.max_var()).max().unwrap_or(0);
         bindings.push(var_num);
-        let goals: Vec<_> = goals.iter().rev().map(|d| bindings.instance(d)).collect();
+        let goals: Vec<_> = goals.iter().map(|d| bindings.build(d, 0)).collect();
+        let initial_goals = goals.iter().rev().copied().collect();
+        let mut rt = Self::with_bindings(bindings, initial_goals, goals);
+        rt.query_vars = var_num;
+        rt
+    }
+
+    /// Starts resolving `goals`; each answer is `template` as bound by a solution.
+    pub(crate) fn new(template: impl Iterator<Item = &'a Data>, goals: &'a [Data]) -> Self {
+        let template: Vec<_> = template.collect();
+        let mut bindings = Bindings::new();
+        let var_num = template
+            .iter()
+            .copied()
+            .chain(goals)
+            .map(|d| d.max_var())
+            .max()
+            .unwrap_or(0);
+        bindings.push(var_num);
+        let initial_goals = template.iter().map(|d| bindings.instance(d)).collect();
+        let goals = goals.iter().map(|d| bindings.instance(d)).collect();
+        let mut rt = Self::with_bindings(bindings, initial_goals, goals);
+        rt.query_vars = var_num;
+        rt
+    }
 
+    fn with_bindings(
+        bindings: Bindings<'a>,
+        initial_goals: Vec<Instance<'a>>,
+        goals: Vec<Instance<'a>>,
+    ) -> Self {
         let mut rt = Self {
-            initial_goals: goals.clone(),
-            resolved_fn,
-            goals,
+            goals: None,
+            arena: vec![],
+            keys: vec![],
+            compacted_len: 0,
+            live_bindings: 0,
+            shared_len: 0,
+            initial_goals,
+            started: false,
             bindings,
-            steps: vec![],
-            cut: world.symbol_pool.get(std::rc::Rc::new("cut".to_owned())),
+            choicepoints: vec![],
+            path: None,
+            donor: None,
+            split: None,
+            tasks: &[],
+            rng: None,
+            limits: Limits::default(),
+            deadline: None,
+            inferences: 0,
+            answers: 0,
+            held: false,
+            fuel: None,
+            yielded: false,
+            waker: None,
+            call: None,
+            tracer: None,
+            record_proofs: false,
+            frontier: None,
+            profiler: None,
+            frames: vec![],
+            trace_parent: None,
+            query_vars: 0,
         };
-        rt.next_step(world);
-        rt.process(world);
+        rt.goals = rt.push_goals(None, goals.into_iter().rev());
+        rt
     }
 
-    fn next_step(&mut self, world: &'a World) {
-        while self.goals.last().and_then(|g| g.data().as_symbol()) == Some(&self.cut) {
-            self.goals.pop();
+    /// Starts resolving `goal` on a snapshot of another runtime's bindings.
+    fn conjunct(mut bindings: Bindings<'a>, goal: Instance<'a>, limits: Limits) -> Self {
+        bindings.mark();
+        let shared_len = bindings.size();
+        let mut rt = Self::with_bindings(bindings, vec![], vec![goal]);
+        rt.shared_len = shared_len;
+        rt.set_limits(limits);
+        rt
+    }
 
-            self.stop_backtrack();
+    /// Reports the ports of predicate calls to `tracer` from now on.
+    pub fn set_tracer(&mut self, tracer: impl Tracer + 'a) {
+        self.tracer = Some(Box::new(tracer));
+    }
+
+    /// Records how each answer is proved from now on, see `proof`.
+    pub fn record_proofs(&mut self) {
+        self.record_proofs = true;
+    }
+
+    /// Records the goals that fail without being resolved from now on, see `why_not`.
+    pub fn record_failures(&mut self) {
+        self.frontier.get_or_insert_with(Frontier::default);
+    }
+
+    /// The deepest goals that no clause, fact row or foreign answer resolved, each with the
+    /// clause heads and fact rows that came closest, once `next` found no more answers.
+    /// Empty unless failures were recorded since the search started.
+    pub fn why_not(&self, world: &World) -> Vec<Failure> {
+        self.frontier
+            .as_ref()
+            .map_or(vec![], |frontier| frontier.failures(world))
+    }
+
+    /// Profiles the predicates and clauses called from now on, see `profile`.
+    pub fn record_profile(&mut self) {
+        self.profiler.get_or_insert_with(Profiler::new);
+    }
+
+    /// Calls, ports, unifications and time of each predicate and clause so far. Empty unless
+    /// a profile was recorded since the search started.
+    pub fn profile(&self) -> Profile {
+        self.profiler
+            .as_ref()
+            .map_or(Profile::default(), |profiler| profiler.profile())
+    }
+
+    /// Whether calls are followed in frames, for a tracer, proofs, failures or a profile.
+    fn tracing(&self) -> bool {
+        self.tracer.is_some()
+            || self.record_proofs
+            || self.frontier.is_some()
+            || self.profiler.is_some()
+    }
+
+    /// Proofs of the query's goals for the answer `next` just returned, one for each
+    /// predicate call among them. Empty unless proofs were recorded since the search started.
+    pub fn proof(&self, world: &'a World) -> Vec<Proof> {
+        // Frames still open are the calls of the answer, each after the one it was made from.
+        let mut open: Vec<Proof> = vec![];
+        let mut roots = vec![];
+        let close = |open: &mut Vec<Proof>, roots: &mut Vec<Proof>| {
+            let proof = open.pop().unwrap();
+            match open.last_mut() {
+                Some(parent) => parent.children.push(proof),
+                None => roots.push(proof),
+            }
+        };
+        for frame in &self.frames {
+            while open.len() > frame.depth {
+                close(&mut open, &mut roots);
+            }
+            let substitution = match frame.clause {
+                Some(i) => proof::clause_variables(&world.rules[i])
+                    .into_iter()
+                    .enumerate()
+                    .filter_map(|(n, var)| {
+                        let var = Instance::new(var?, frame.base);
+                        Some((n, self.bindings.data_indexed(var)))
+                    })
+                    .collect(),
+                None => vec![],
+            };
+            let goal = self.bindings.data_indexed(frame.goal);
+            open.push(Proof::new(goal, frame.clause, substitution));
+        }
+        while !open.is_empty() {
+            close(&mut open, &mut roots);
         }
+        roots
+    }
 
-        if let Some(goal) = self.goals.pop() {
-            self.steps.push(Step {
-                rule_indices: world.rule_map.get(goal.data()).iter(),
-                goal_index: self.goals.len(),
-                goal,
-            });
-        } else {
-            // All goals are resolved
-            let datas: Vec<_> = self
-                .initial_goals
+    fn set_limits(&mut self, limits: Limits) {
+        self.deadline = limits.deadline();
+        self.limits = limits;
+    }
+
+    /// Finds up to `max` more answers, each as the bindings made on top of the initial ones,
+    /// and whether there are no more.
+    fn take_answers(
+        &mut self,
+        world: &'a World,
+        max: usize,
+    ) -> Result<(Vec<Delta<'a>>, bool), Error> {
+        let mut answers = vec![];
+        while answers.len() < max {
+            if self.next(world)?.is_none() {
+                return Ok((answers, true));
+            }
+            answers.push(self.bindings.delta());
+        }
+        Ok((answers, false))
+    }
+
+    /// Resumes a task donated by another runtime.
+    pub(crate) fn from_task(
+        task: &'a Task,
+        donor: &'a dyn Donor,
+        record_path: bool,
+        limits: &Limits,
+    ) -> Self {
+        let mut rt = Self::new(task.template.iter(), &task.goals);
+        rt.set_limits(limits.clone());
+        rt.path = record_path.then(|| task.path.clone());
+        rt.donor = Some(donor);
+        rt.split = task.split.clone();
+        rt
+    }
+
+    /// Finds the next answer, or `None` once the search space is exhausted.
+    pub fn next(&mut self, world: &'a World) -> Result<Option<Vec<Data>>, Error> {
+        // The caller's time between answers is not profiled.
+        if let Some(profiler) = &mut self.profiler {
+            profiler.unpause();
+        }
+        let answer = self.search(world);
+        if let Some(profiler) = &mut self.profiler {
+            profiler.pause();
+        }
+        answer
+    }
+
+    fn search(&mut self, world: &'a World) -> Result<Option<Vec<Data>>, Error> {
+        if self.held {
+            return Err(Error::LimitExceeded(Limit::Answers));
+        }
+        let mut resume = std::mem::replace(&mut self.started, true)
+            && !std::mem::replace(&mut self.yielded, false);
+        loop {
+            if (!resume || self.backtrack(world)?) && self.solve(world)? {
+                break;
+            }
+            if self.yielded {
+                return Ok(None);
+            }
+            let Some((task, tasks)) = self.tasks.split_first() else {
+                return Ok(None);
+            };
+            self.load(task, tasks);
+            resume = false;
+        }
+        if self.limits.answers.is_some_and(|max| self.answers >= max) {
+            self.held = true;
+            return Err(Error::LimitExceeded(Limit::Answers));
+        }
+        self.answers += 1;
+        Ok(Some(
+            self.initial_goals
                 .iter()
                 .map(|&i| self.bindings.data(i))
-                .collect();
-            (self.resolved_fn)(&datas);
-            self.bindings.pop();
+                .collect(),
+        ))
+    }
+
+    /// Replaces the search with `task`, keeping the limits and what was used of them.
+    fn load(&mut self, task: &'a Task, tasks: &'a [Task]) {
+        let mut rt = Self::new(task.template.iter(), &task.goals);
+        rt.started = true;
+        rt.split = task.split.clone();
+        rt.tasks = tasks;
+        rt.rng = self.rng.take();
+        rt.limits = std::mem::take(&mut self.limits);
+        rt.deadline = self.deadline;
+        rt.inferences = self.inferences;
+        rt.answers = self.answers;
+        rt.tracer = self.tracer.take();
+        rt.record_proofs = self.record_proofs;
+        rt.frontier = self.frontier.take();
+        rt.profiler = self.profiler.take();
+        *self = rt;
+    }
+
+    /// Continues the search saved in `snapshot`.
+    pub(crate) fn from_snapshot(snapshot: &'a Snapshot, limits: &Limits) -> Self {
+        let mut rt = Self::new(std::iter::empty(), &[]);
+        rt.started = true;
+        rt.tasks = snapshot.tasks();
+        rt.set_limits(limits.clone());
+        rt
+    }
+
+    /// Saves what is left of the search: the goals not yet resolved, if it stopped midway,
+    /// or the answer held back by the answers limit, and the alternatives of each choicepoint, in the order they would be searched.
+    /// Taken between answers or once `next` failed with `LimitExceeded`, `Cancelled` or
+    /// `TimedOut`; cyclic terms cannot be saved.
+    pub fn snapshot(&self) -> Result<Snapshot, Error> {
+        let mut tasks = vec![];
+        if !self.started || self.held || self.goals.is_some() || self.call.is_some() {
+            let call = self.call.as_ref().map(|call| self.arena[call.node].goal);
+            tasks.push(self.save(None, call, self.goals, self.split.clone())?);
+        }
+        for (k, cp) in self.choicepoints.iter().enumerate().rev() {
+            let mark = self.bindings.marks() - (self.choicepoints.len() - k);
+            let split = Split {
+                ordinal: cp.ordinal,
+                rules: cp.rule_indices.clone().collect(),
+                rows: cp.rows.remaining(),
+            };
+            let node = &self.arena[cp.node];
+            tasks.push(self.save(Some(mark), Some(node.goal), node.next, Some(split))?);
         }
+        tasks.extend_from_slice(self.tasks);
+        Ok(Snapshot::new(tasks))
     }
 
-    fn process(&mut self, world: &'a World) {
-        while let Some(step) = self.steps.last_mut() {
-            let Some(&rule_index) = step.rule_indices.next() else {
-                self.goals.truncate(step.goal_index);
-                self.goals.push(step.goal);
-                self.steps.pop();
-                self.bindings.pop();
+    /// Polls for the next answer, yielding after `fuel` inferences or while a foreign
+    /// predicate is pending.
+    pub(crate) fn poll_next(
+        &mut self,
+        world: &'a World,
+        cx: &mut Context,
+        fuel: u64,
+    ) -> Poll<Result<Option<Vec<Data>>, Error>> {
+        self.fuel = Some(fuel);
+        self.waker = Some(cx.waker().clone());
+        let result = self.next(world);
+        self.fuel = None;
+        self.waker = None;
+        match result {
+            Ok(None) if self.yielded => {
+                // A pending call wakes the task itself once it can progress.
+                if self.call.is_none() {
+                    cx.waker().wake_by_ref();
+                }
+                Poll::Pending
+            }
+            result => Poll::Ready(result),
+        }
+    }
+
+    /// Turns the search into an async stream of answers, see `Solutions`.
+    pub fn into_stream(self, world: &'a World, yield_every: u64) -> Solutions<'a> {
+        Solutions::new(world, self, yield_every)
+    }
+
+    /// Ordinals of the alternatives leading to the current answer, if recorded.
+    /// Comparing paths orders answers as a sequential search would find them.
+    pub(crate) fn path(&self) -> Option<&[u32]> {
+        self.path.as_deref()
+    }
+
+    /// Resolves goals until all are resolved (`true`) or no alternative is left (`false`).
+    fn solve(&mut self, world: &'a World) -> Result<bool, Error> {
+        loop {
+            if let Some(mut call) = self.call.take() {
+                let rows = match &self.waker {
+                    None => block_on(&mut call.future),
+                    Some(waker) => match call.future.as_mut().poll(&mut Context::from_waker(waker))
+                    {
+                        Poll::Ready(rows) => rows,
+                        Poll::Pending => {
+                            self.call = Some(call);
+                            self.yielded = true;
+                            return Ok(false);
+                        }
+                    },
+                };
+                if !self.foreign(world, call.node, rows)? && !self.backtrack(world)? {
+                    return Ok(false);
+                }
                 continue;
+            }
+            if self.choicepoints.is_empty() {
+                self.collect_garbage();
+            }
+            let Some(node) = self.goals else {
+                break;
             };
+            if std::ptr::eq(self.arena[node].goal.data(), &EXIT) {
+                self.goals = self.arena[node].next;
+                self.trace_exit(self.arena[node].goal.base());
+                continue;
+            }
+            if self.fuel == Some(0) {
+                self.yielded = true;
+                return Ok(false);
+            }
+            self.check_limits(node)?;
+            let goal = self.arena[node].goal;
+            self.goals = self.arena[node].next;
 
-            let rule = &world.rules[rule_index];
-            self.bindings.push(rule.var_num);
-            let head = self.bindings.instance(&rule.head);
-            if !self.bindings.unify(step.goal, head) {
+            if goal.data().as_symbol() == Some(Atom::CUT) {
+                self.stop_backtrack();
+                continue;
+            }
+
+            let key = match self.keys.get(node) {
+                Some(&Some(key)) => Some(key),
+                _ => Key::of_instance(&self.bindings, goal),
+            };
+            if let Some(builtin) = builtin(key) {
+                if !self.call_builtin(world, builtin, node)? && !self.backtrack(world)? {
+                    return Ok(false);
+                }
+                continue;
+            }
+
+            if self.tracing() {
+                self.trace_call(goal);
+            }
+
+            if let Some(f) = world.foreign_for(key) {
+                let args = foreign_args(&self.bindings, goal);
+                self.call = Some(Call {
+                    node,
+                    future: f(args),
+                });
+                continue;
+            }
+
+            let (rule_indices, rows, ordinal, branching) = match self.split.take() {
+                Some(split) => {
+                    let mut rows = Rows::empty();
+                    if !split.rows.is_empty() {
+                        rows = world.facts.get(&self.bindings, goal, key);
+                        rows.set_remaining(&split.rows);
+                    }
+                    (Candidates::from_vec(split.rules), rows, split.ordinal, true)
+                }
+                None => {
+                    let mut rule_indices =
+                        world.rule_map.get(&world.rules, &self.bindings, goal, key);
+                    let mut rows = world.facts.get(&self.bindings, goal, key);
+                    if let ClauseOrder::Random { seed } = world.clause_order {
+                        let rng = self.rng.get_or_insert_with(|| Rng::new(seed));
+                        rule_indices.shuffle(rng);
+                        rows.shuffle(rng);
+                    }
+                    // A call with one alternative at most needs no choicepoint.
+                    if rule_indices.len() + rows.len() <= 1 && !self.tracing() {
+                        let occurs_check = world.occurs_check_for(key);
+                        let rest = self.goals.take();
+                        let resolved = match (rule_indices.next(), rows.next_row()) {
+                            (Some(i), _) => self.enter(world, i, goal, rest, occurs_check, None)?,
+                            (None, Some(row)) => {
+                                let unified =
+                                    rows.unify(&world.facts, &mut self.bindings, goal, row);
+                                if unified {
+                                    self.goals = rest;
+                                }
+                                unified
+                            }
+                            (None, None) => false,
+                        };
+                        if !resolved && !self.backtrack(world)? {
+                            return Ok(false);
+                        }
+                        continue;
+                    }
+                    let branching = rule_indices.len() + rows.len() > 1;
+                    (rule_indices, rows, 0, branching)
+                }
+            };
+            self.bindings.mark();
+            self.goals = None;
+            let cp = Choicepoint {
+                node,
+                arena_len: self.arena.len(),
+                occurs_check: world.occurs_check_for(key),
+                branching,
+                rule_indices,
+                rows,
+                path_len: self.path.as_ref().map_or(0, |p| p.len()),
+                ordinal,
+                extra: self.tracing().then(|| Box::new(self.extra(true))),
+            };
+            self.choicepoints.push(cp);
+            if let Some(donor) = self.donor.filter(|donor| donor.wants_work()) {
+                self.donate(donor);
+            }
+            if !self.backtrack(world)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Resumes the newest choicepoint with its next alternative, dropping exhausted ones.
+    /// A choicepoint is dropped as soon as its last alternative is taken, so deterministic
+    /// calls leave neither a choicepoint nor a mark behind.
+    fn backtrack(&mut self, world: &'a World) -> Result<bool, Error> {
+        while let Some(cp) = self.choicepoints.last_mut() {
+            self.bindings.undo();
+            self.arena.truncate(cp.arena_len);
+            if self.tracer.is_some()
+                || self.record_proofs
+                || self.frontier.is_some()
+                || self.profiler.is_some()
+            {
+                // An untraced choicepoint, of a `par` goal, resumes the call it was made in.
+                let extra = cp.extra.as_ref().unwrap();
+                let frames_len = extra.frames_len;
+                let frame = match extra.traced {
+                    true => Some(frames_len - 1),
+                    false => extra.trace_parent,
+                };
+                self.trace_parent = extra.trace_parent;
+                self.trace_fail(frames_len);
+                if let Some(f) = frame {
+                    self.trace_redo(f);
+                }
+                if let Some(profiler) = &mut self.profiler {
+                    profiler.resume(frame);
+                }
+            }
+            let cp = self.choicepoints.last_mut().unwrap();
+            let alternative = if let Some(i) = cp.rule_indices.next() {
+                Alternative::Rule(i)
+            } else if let Some(row) = cp.rows.next_row() {
+                let goal = self.arena[cp.node].goal;
+                Alternative::Row(cp.rows.unify(&world.facts, &mut self.bindings, goal, row))
+            } else if let Some(extra) = &mut cp.extra {
+                if let Some(join) = extra.join.as_mut().filter(|join| join.next.is_some()) {
+                    Alternative::Join(join.next(world, &self.limits, &mut self.inferences)?)
+                } else if let Some(row) = extra.foreign.pop() {
+                    Alternative::Foreign(row)
+                } else {
+                    let frames_len = extra.traced.then_some(extra.frames_len);
+                    self.drop_choicepoint();
+                    self.bindings.pop();
+                    if let Some(frames_len) = frames_len {
+                        self.trace_fail(frames_len - 1);
+                    }
+                    continue;
+                }
+            } else {
+                self.drop_choicepoint();
                 self.bindings.pop();
                 continue;
+            };
+            if let (Some(path), true) = (&mut self.path, cp.branching) {
+                path.truncate(cp.path_len);
+                path.push(cp.ordinal);
+            }
+            cp.ordinal += 1;
+            let frame = cp
+                .extra
+                .as_ref()
+                .filter(|extra| extra.traced)
+                .map(|extra| extra.frames_len - 1);
+            let (goal, rest) = (self.arena[cp.node].goal, self.arena[cp.node].next);
+            let occurs_check = cp.occurs_check;
+            if cp.is_exhausted() {
+                self.drop_choicepoint();
+                self.bindings.discard();
+            }
+
+            if let (Some(profiler), Some(f)) = (&mut self.profiler, frame) {
+                let clause = match alternative {
+                    Alternative::Rule(i) => Some(i),
+                    _ => None,
+                };
+                profiler.unify(f, clause);
+            }
+            match alternative {
+                Alternative::Rule(rule_index) => {
+                    if self.enter(world, rule_index, goal, rest, occurs_check, frame)? {
+                        return Ok(true);
+                    }
+                }
+                Alternative::Row(unified) => {
+                    if unified {
+                        self.goals = rest;
+                        if let Some(f) = frame {
+                            self.trace_enter(f, None, 0, true);
+                        }
+                        return Ok(true);
+                    }
+                }
+                Alternative::Join(combination) => {
+                    for delta in &combination {
+                        self.bindings.apply(delta);
+                    }
+                    self.goals = rest;
+                    return Ok(true);
+                }
+                Alternative::Foreign(row) => {
+                    if unify_row(&mut self.bindings, goal, &row) {
+                        self.goals = rest;
+                        if let Some(f) = frame {
+                            self.trace_enter(f, None, 0, true);
+                        }
+                        return Ok(true);
+                    }
+                }
+            }
+        }
+        self.trace_fail(0);
+        Ok(false)
+    }
+
+    /// Drops the newest choicepoint where it lies, as popping would first move it out.
+    fn drop_choicepoint(&mut self) {
+        self.choicepoints.truncate(self.choicepoints.len() - 1);
+    }
+
+    /// Unifies `goal` with the head of clause `rule_index` and, if they unify, makes the
+    /// clause body the goals before `rest`. `frame` is the traced call of `goal`, if any.
+    fn enter(
+        &mut self,
+        world: &'a World,
+        rule_index: usize,
+        goal: Instance<'a>,
+        rest: Option<usize>,
+        occurs_check: OccursCheck,
+        frame: Option<usize>,
+    ) -> Result<bool, Error> {
+        let rule = &world.rules[rule_index];
+        let base = self.bindings.alloc(rule.var_num);
+        let head = Instance::new(&rule.head, base);
+        match self.bindings.unify_with(goal, head, occurs_check) {
+            Ok(true) => {
+                let exit = rule.body.is_empty();
+                let rest = match frame {
+                    Some(f) if !exit => {
+                        let marker = Instance::new(&EXIT, f);
+                        self.push_goals(rest, std::iter::once(marker))
+                    }
+                    _ => rest,
+                };
+                let body = rule.body.iter().map(|d| Instance::new(d, base));
+                self.goals = self.push_goals(rest, body);
+                if let Some(f) = frame {
+                    self.trace_enter(f, Some(rule_index), base, exit);
+                }
+                Ok(true)
+            }
+            Ok(false) => Ok(false),
+            Err(data) => Err(Error::OccursCheck(data)),
+        }
+    }
+
+    /// Trace state of a new choicepoint, its call's frame the newest if `traced`.
+    fn extra(&self, traced: bool) -> Extra<'a> {
+        Extra {
+            frames_len: self.frames.len(),
+            traced,
+            trace_parent: self.trace_parent,
+            ..Default::default()
+        }
+    }
+
+    /// Reports the call of `goal`, opening a frame for it.
+    fn trace_call(&mut self, goal: Instance<'a>) {
+        let parent = self.trace_parent;
+        self.frames.push(Frame {
+            goal,
+            called: self.traced_data(goal),
+            parent,
+            depth: parent.map_or(0, |p| self.frames[p].depth + 1),
+            clause: None,
+            base: 0,
+            exited: false,
+            resolved: false,
+        });
+        self.port(Port::Call, self.frames.len() - 1);
+    }
+
+    /// Records that frame `f` went on through `clause`, its variables at `base`, exiting at
+    /// once if `exit`.
+    fn trace_enter(&mut self, f: usize, clause: Option<usize>, base: usize, exit: bool) {
+        self.frames[f].clause = clause;
+        self.frames[f].base = base;
+        self.frames[f].resolved = true;
+        if let Some(profiler) = &mut self.profiler {
+            profiler.enter(f, clause);
+        }
+        self.trace_parent = Some(f);
+        if exit {
+            self.trace_exit(f);
+        }
+    }
+
+    fn trace_exit(&mut self, f: usize) {
+        self.frames[f].exited = true;
+        self.trace_parent = self.frames[f].parent;
+        self.port(Port::Exit, f);
+    }
+
+    /// Reports backtracking into frame `f`: a retry of it and of each of its ancestors that
+    /// exited, outermost first.
+    fn trace_redo(&mut self, f: usize) {
+        let mut exited = vec![];
+        let mut frame = Some(f);
+        while let Some(i) = frame {
+            if self.frames[i].exited {
+                exited.push(i);
+            }
+            frame = self.frames[i].parent;
+        }
+        for i in exited.into_iter().rev() {
+            self.frames[i].exited = false;
+            self.port(Port::Redo, i);
+        }
+    }
+
+    /// Reports the frames from `len` on as failed, newest first, and closes them. Frames
+    /// that exited are retried first.
+    fn trace_fail(&mut self, len: usize) {
+        while self.frames.len() > len {
+            self.trace_redo(self.frames.len() - 1);
+            self.port(Port::Fail, self.frames.len() - 1);
+            let frame = self.frames.pop().unwrap();
+            if let (Some(frontier), false) = (&mut self.frontier, frame.resolved) {
+                frontier.record(&frame.called, frame.depth);
+            }
+        }
+    }
+
+    fn port(&mut self, port: Port, f: usize) {
+        if let Some(profiler) = &mut self.profiler {
+            match port {
+                Port::Call => profiler.call(&self.frames[f].called, self.frames[f].parent),
+                Port::Exit => profiler.exit(f),
+                Port::Redo => profiler.redo(f),
+                Port::Fail => profiler.fail(f),
+            }
+        }
+        if self.tracer.is_none() {
+            return;
+        }
+        let frame = &self.frames[f];
+        let (depth, clause) = (frame.depth, frame.clause);
+        let exited;
+        let goal = match port {
+            Port::Exit => {
+                exited = self.traced_data(frame.goal);
+                &exited
+            }
+            _ => &frame.called,
+        };
+        let tracer = self.tracer.as_mut().unwrap();
+        match port {
+            Port::Call => tracer.call(goal, depth, clause),
+            Port::Exit => tracer.exit(goal, depth, clause),
+            Port::Redo => tracer.redo(goal, depth, clause),
+            Port::Fail => tracer.fail(goal, depth, clause),
+        }
+    }
+
+    /// `goal` as reported to a tracer: unbound variables are numbered by their index in the
+    /// bindings, or by that of the first query variable bound to them.
+    fn traced_data(&self, goal: Instance<'a>) -> Data {
+        let mut data = self.bindings.data_indexed(goal);
+        let mut aliases = HashMap::new();
+        for var in 0..self.query_vars {
+            match self.bindings.unbound_index(var) {
+                Some(idx) if idx != var => aliases.entry(idx).or_insert(var),
+                _ => continue,
+            };
+        }
+        if !aliases.is_empty() {
+            data.rename_vars(&aliases);
+        }
+        data
+    }
+
+    fn check_limits(&mut self, node: usize) -> Result<(), Error> {
+        self.inferences += 1;
+        if let Some(fuel) = &mut self.fuel {
+            *fuel -= 1;
+        }
+        self.limits.interrupt(self.inferences, self.deadline)?;
+        let exceeded = if self
+            .limits
+            .inferences
+            .is_some_and(|max| self.inferences > max)
+        {
+            Limit::Inferences
+        } else if self
+            .limits
+            .depth
+            .is_some_and(|max| self.arena[node].len > max)
+        {
+            Limit::Depth
+        } else if self
+            .limits
+            .bindings
+            .is_some_and(|max| self.bindings.size() > max)
+        {
+            Limit::Bindings
+        } else {
+            return Ok(());
+        };
+        Err(Error::LimitExceeded(exceeded))
+    }
+
+    /// Hands the clause alternatives left at the oldest choicepoint to `donor`, as they lead
+    /// to the largest part of the search space. Its fact rows are kept here.
+    fn donate(&mut self, donor: &dyn Donor) {
+        let Some(k) = self
+            .choicepoints
+            .iter()
+            .position(|cp| !cp.rule_indices.is_empty())
+        else {
+            return;
+        };
+        let cp = &self.choicepoints[k];
+        // The goals are copied as bound when the choicepoint was made, i.e. at its mark.
+        // Cyclic terms cannot be copied, so those are searched here.
+        let mark = self.bindings.marks() - (self.choicepoints.len() - k);
+        let node = &self.arena[cp.node];
+        let Ok(mut task) = self.save(Some(mark), Some(node.goal), node.next, None) else {
+            return;
+        };
+        let cp = &mut self.choicepoints[k];
+        let ordinal = cp.ordinal;
+        let rules: Vec<_> = std::mem::take(&mut cp.rule_indices).collect();
+        cp.ordinal += rules.len() as u32;
+        task.path = self
+            .path
+            .as_ref()
+            .map_or(vec![], |p| p[..cp.path_len].to_vec());
+        task.split = Some(Split {
+            ordinal,
+            rules,
+            rows: vec![],
+        });
+        donor.donate(task);
+    }
+
+    /// Copies the answer template and `first` followed by the goals from `rest`, as bound
+    /// at `mark` or now, into a task.
+    fn save(
+        &self,
+        mark: Option<usize>,
+        first: Option<Instance<'a>>,
+        rest: Option<usize>,
+        split: Option<Split>,
+    ) -> Result<Task, Error> {
+        let mut instances = self.initial_goals.clone();
+        instances.extend(first);
+        let mut node = rest;
+        while let Some(i) = node {
+            let goal = self.arena[i].goal;
+            if !std::ptr::eq(goal.data(), &EXIT) {
+                instances.push(goal);
+            }
+            node = self.arena[i].next;
+        }
+        let Some(mut template) = self.bindings.copy(mark, &instances) else {
+            let cyclic = instances
+                .iter()
+                .find(|&&i| self.bindings.copy(mark, &[i]).is_none())
+                .unwrap();
+            return Err(Error::CyclicTerm(self.bindings.data(*cyclic)));
+        };
+        let goals = template.split_off(self.initial_goals.len());
+        Ok(Task {
+            template,
+            goals,
+            path: vec![],
+            split,
+        })
+    }
+
+    /// Prepends goals given last first.
+    fn push_goals(
+        &mut self,
+        mut goals: Option<usize>,
+        rev_goals: impl Iterator<Item = Instance<'a>>,
+    ) -> Option<usize> {
+        for goal in rev_goals {
+            let len = goals.map_or(0, |i| self.arena[i].len) + 1;
+            self.arena.push(GoalNode {
+                goal,
+                next: goals,
+                len,
+            });
+            goals = Some(self.arena.len() - 1);
+        }
+        goals
+    }
+
+    /// Reclaims resolved goals and unreachable bindings once nothing can backtrack into them.
+    fn collect_garbage(&mut self) {
+        // Exit markers keep frame indices as bases, which compacting would renumber.
+        if self.tracing() {
+            return;
+        }
+        let compact_bindings = self.shared_len == 0
+            && self.bindings.size() >= COMPACT_MIN_BINDINGS.max(2 * self.live_bindings);
+        if compact_bindings || self.arena.len() >= COMPACT_MIN_GOALS.max(2 * self.compacted_len) {
+            self.compact();
+        }
+        if compact_bindings {
+            let mut roots: Vec<_> = self
+                .initial_goals
+                .iter_mut()
+                .chain(self.arena.iter_mut().map(|node| &mut node.goal))
+                .collect();
+            self.bindings.compact(&mut roots);
+            self.live_bindings = self.bindings.size();
+        }
+    }
+
+    /// Rebuilds the arena from the goals still to be resolved, dropping resolved ones.
+    fn compact(&mut self) {
+        let mut goals = Vec::new();
+        let mut node = self.goals;
+        while let Some(i) = node {
+            goals.push(self.arena[i].goal);
+            node = self.arena[i].next;
+        }
+        self.arena.clear();
+        self.keys.clear();
+        self.goals = self.push_goals(None, goals.into_iter().rev());
+        self.compacted_len = self.arena.len();
+    }
+
+    fn call_builtin(
+        &mut self,
+        world: &'a World,
+        builtin: Builtin,
+        node: usize,
+    ) -> Result<bool, Error> {
+        let goal = self.bindings.resolve(self.arena[node].goal);
+        let Data::Term(v) = goal.data() else {
+            return Ok(false);
+        };
+        let args: Vec<_> = v[1..]
+            .iter()
+            .map(|d| Instance::new(d, goal.base()))
+            .collect();
+        match builtin {
+            Builtin::UnifyWithOccursCheck => {
+                Ok(self.bindings.unify_with_occurs_check(args[0], args[1]))
             }
+            Builtin::Par => self.par(world, node, args),
+        }
+    }
 
-            self.goals.truncate(step.goal_index);
-            self.goals
-                .extend(rule.body.iter().map(|d| self.bindings.instance(d)));
-            self.next_step(world);
+    /// Resolves `(par g1 g2 ...)`. Conjuncts that share no unbound variables cannot affect
+    /// each other, so their answers are found concurrently and then joined; otherwise, or if
+    /// a conjunct after the first has too many answers to keep, they are resolved as a plain
+    /// conjunction.
+    fn par(
+        &mut self,
+        world: &'a World,
+        node: usize,
+        conjuncts: Vec<Instance<'a>>,
+    ) -> Result<bool, Error> {
+        // Traced runs resolve the conjuncts here, where their calls can be followed.
+        if self.tracing() || !self.independent(world, &conjuncts) {
+            self.goals = self.push_goals(self.goals, conjuncts.into_iter().rev());
+            return Ok(true);
         }
+        let Some(mut join) = self.solve_conjuncts(world, &conjuncts)? else {
+            self.goals = self.push_goals(self.goals, conjuncts.into_iter().rev());
+            return Ok(true);
+        };
+        // Resuming a split-off `par` goal skips the combinations already taken.
+        let ordinal = self.split.take().map_or(0, |split| split.ordinal);
+        for _ in 0..ordinal {
+            if join.next.is_none() {
+                break;
+            }
+            join.next(world, &self.limits, &mut self.inferences)?;
+        }
+        if join.next.is_none() {
+            return Ok(false);
+        }
+        self.bindings.mark();
+        self.goals = None;
+        self.choicepoints.push(Choicepoint {
+            node,
+            arena_len: self.arena.len(),
+            occurs_check: world.occurs_check,
+            rule_indices: Candidates::default(),
+            rows: Rows::empty(),
+            branching: join.branching(),
+            path_len: self.path.as_ref().map_or(0, |p| p.len()),
+            ordinal,
+            extra: Some(Box::new(Extra {
+                join: Some(join),
+                ..self.extra(false)
+            })),
+        });
+        // Taking a combination always succeeds.
+        self.backtrack(world)
+    }
+
+    /// Makes a choicepoint over the answer rows of the foreign predicate called at `node`.
+    fn foreign(
+        &mut self,
+        world: &'a World,
+        node: usize,
+        mut rows: Vec<Vec<Atom>>,
+    ) -> Result<bool, Error> {
+        // Resuming a split-off call skips the rows already taken.
+        let ordinal = self.split.take().map_or(0, |split| split.ordinal);
+        rows.drain(..rows.len().min(ordinal as usize));
+        if rows.is_empty() {
+            return Ok(false);
+        }
+        rows.reverse();
+        self.bindings.mark();
+        self.goals = None;
+        self.choicepoints.push(Choicepoint {
+            node,
+            arena_len: self.arena.len(),
+            occurs_check: world.occurs_check,
+            rule_indices: Candidates::default(),
+            rows: Rows::empty(),
+            branching: rows.len() > 1,
+            path_len: self.path.as_ref().map_or(0, |p| p.len()),
+            ordinal,
+            extra: Some(Box::new(Extra {
+                foreign: rows,
+                ..self.extra(self.tracing())
+            })),
+        });
+        self.backtrack(world)
+    }
+
+    /// Whether `conjuncts` share no unbound variables. Cut prunes across conjuncts, so any
+    /// use of it makes them dependent.
+    fn independent(&self, world: &World, conjuncts: &[Instance<'a>]) -> bool {
+        let is_cut = |&c| self.bindings.resolve(c).data().as_symbol() == Some(Atom::CUT);
+        if world.uses_cut || conjuncts.iter().any(is_cut) {
+            return false;
+        }
+        let mut seen = HashSet::new();
+        conjuncts.iter().all(|&c| {
+            let mut vars = HashSet::new();
+            self.bindings.unbound_vars(c, &mut vars);
+            let disjoint = vars.is_disjoint(&seen);
+            seen.extend(vars);
+            disjoint
+        })
+    }
+
+    /// Joins the answers of each conjunct, found on spare threads of `world` while there are
+    /// any: the first batch of the first conjunct and all answers of the others. `None` if
+    /// one of the others has more than `JOIN_MAX_ANSWERS`, or does not find them all within
+    /// `JOIN_MAX_INFERENCES`. Their inferences count towards the query's.
+    fn solve_conjuncts(
+        &mut self,
+        world: &'a World,
+        conjuncts: &[Instance<'a>],
+    ) -> Result<Option<Join<'a>>, Error> {
+        let bindings = &self.bindings;
+        // Each conjunct may use what is left of the query's limits on its own.
+        let limits = Limits {
+            inferences: self
+                .limits
+                .inferences
+                .map(|max| max.saturating_sub(self.inferences)),
+            answers: None,
+            timeout: self
+                .deadline
+                .map(|d| d.saturating_duration_since(Instant::now())),
+            ..self.limits.clone()
+        };
+        let conjunct = |goal, limits| Runtime::conjunct(bindings.snapshot(), goal, limits);
+        let capped = limits.inferences.is_none_or(|n| n > JOIN_MAX_INFERENCES);
+        let solve = |goal| {
+            let limits = Limits {
+                inferences: Some(
+                    limits
+                        .inferences
+                        .map_or(JOIN_MAX_INFERENCES, |n| n.min(JOIN_MAX_INFERENCES)),
+                ),
+                ..limits.clone()
+            };
+            let mut rt = conjunct(goal, limits);
+            match rt.take_answers(world, JOIN_MAX_ANSWERS) {
+                Ok((answers, exhausted)) => Ok((exhausted.then_some(answers), rt.inferences)),
+                Err(Error::LimitExceeded(Limit::Inferences)) if capped => Ok((None, rt.inferences)),
+                Err(e) => Err(e),
+            }
+        };
+        let spare = &world.spare_threads;
+        let (first, others) = thread::scope(|s| {
+            let handles: Vec<_> = conjuncts[1..]
+                .iter()
+                .map(|&goal| {
+                    let acquired = spare
+                        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1));
+                    acquired.is_ok().then(|| {
+                        s.spawn(move || {
+                            let answers = solve(goal);
+                            spare.fetch_add(1, Ordering::SeqCst);
+                            answers
+                        })
+                    })
+                })
+                .collect();
+            let mut first = Box::new(conjunct(conjuncts[0], limits.clone()));
+            let first = first.take_answers(world, JOIN_BATCH).map(|a| (first, a));
+            let others: Vec<Result<_, Error>> = handles
+                .into_iter()
+                .zip(&conjuncts[1..])
+                .map(|(handle, &goal)| match handle {
+                    Some(handle) => handle.join().unwrap(),
+                    None => solve(goal),
+                })
+                .collect();
+            (first, others)
+        });
+        let (first, (first_answers, exhausted)) = first?;
+        self.inferences += first.inferences;
+        let mut answers = vec![first_answers];
+        let mut joinable = true;
+        for other in others {
+            let (other_answers, inferences) = other?;
+            self.inferences += inferences;
+            match other_answers {
+                Some(a) => answers.push(a),
+                None => joinable = false,
+            }
+        }
+        if self
+            .limits
+            .inferences
+            .is_some_and(|max| self.inferences > max)
+        {
+            return Err(Error::LimitExceeded(Limit::Inferences));
+        }
+        Ok(joinable.then(|| Join::new((!exhausted).then_some(first), answers)))
     }
 
     fn stop_backtrack(&mut self) {
-        self.steps.clear();
+        self.choicepoints.clear();
+        self.tasks = &[];
+        self.bindings.commit();
+    }
+}
+
+/// Arguments of `goal` as bound so far, for calling a foreign predicate.
+pub(crate) fn foreign_args<'a>(bindings: &Bindings<'a>, goal: Instance<'a>) -> Vec<Data> {
+    let goal = bindings.resolve(goal);
+    let Data::Term(v) = goal.data() else {
+        return vec![];
+    };
+    v[1..]
+        .iter()
+        .map(|d| bindings.data(Instance::new(d, goal.base())))
+        .collect()
+}
+
+/// Unifies the arguments of `goal` with an answer row of a foreign predicate.
+pub(crate) fn unify_row<'a>(bindings: &mut Bindings<'a>, goal: Instance<'a>, row: &[Atom]) -> bool {
+    let goal = bindings.resolve(goal);
+    let Data::Term(v) = goal.data() else {
+        return false;
+    };
+    v.len() == row.len() + 1
+        && v[1..].iter().zip(row).all(|(arg, atom)| {
+            bindings.unify(
+                Instance::new(arg, goal.base()),
+                Instance::new(atom.data(), 0),
+            )
+        })
+}
+
+/// The builtin called by a goal of predicate `key`.
+pub(crate) fn builtin(key: Option<Key>) -> Option<Builtin> {
+    match key? {
+        Key::Term(Atom::UNIFY_WITH_OCCURS_CHECK, 3) => Some(Builtin::UnifyWithOccursCheck),
+        Key::Term(Atom::PAR, n) if n > 1 => Some(Builtin::Par),
+        _ => None,
     }
 }
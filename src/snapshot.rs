@@ -0,0 +1,254 @@
+use crate::{
+    atom::Atom,
+    data::Data,
+    limits::Limits,
+    parallel::{Split, Task},
+    rule_map::Key,
+    runtime::Runtime,
+    user_data::UserData,
+    world::{VariableScope, World},
+};
+
+/// Identifies the byte format, followed by its version.
+const MAGIC: &[u8] = b"prlg-snapshot";
+const VERSION: u8 = 2;
+
+/// A depth-first search saved by `Runtime::snapshot`, to be resumed later, possibly in
+/// another process, against the same `World`. Symbols are saved by name; clauses by their
+/// index in `World::rules`.
+#[derive(Clone)]
+pub struct Snapshot {
+    /// Searched first to last, each with the answer template of the query.
+    tasks: Vec<Task>,
+}
+
+/// Bytes that are not a snapshot, or a snapshot of a search over other rules.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidSnapshot;
+
+impl std::fmt::Display for InvalidSnapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid snapshot")
+    }
+}
+
+impl std::error::Error for InvalidSnapshot {}
+
+impl Snapshot {
+    pub(crate) fn new(tasks: Vec<Task>) -> Self {
+        Snapshot { tasks }
+    }
+
+    /// A search of `data_slice` that has not started yet.
+    pub fn query(data_slice: &[UserData]) -> Self {
+        let goals = VariableScope::new().new_data_vec(data_slice);
+        Snapshot::new(vec![Task {
+            template: goals.iter().rev().cloned().collect(),
+            goals,
+            path: vec![],
+            split: None,
+        }])
+    }
+
+    pub(crate) fn tasks(&self) -> &[Task] {
+        &self.tasks
+    }
+
+    /// Whether nothing is left to search.
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    /// Continues the search against `world`, which must have the rules and facts it was
+    /// saved with. Answers have the same form as those of `World::run`.
+    pub fn resume<'a>(
+        &'a self,
+        world: &'a World,
+        limits: &Limits,
+    ) -> Result<Runtime<'a>, InvalidSnapshot> {
+        for task in &self.tasks {
+            let Some(split) = &task.split else {
+                continue;
+            };
+            if split.rules.iter().any(|&i| i >= world.rules.len()) {
+                return Err(InvalidSnapshot);
+            }
+            if split.rows.is_empty() {
+                continue;
+            }
+            // Rows are of the table of the task's first goal.
+            let table = match task.goals.first().and_then(Key::of) {
+                Some(Key::Term(name, len)) => world.facts.table(name, len - 1),
+                _ => None,
+            };
+            match table {
+                Some(table) if split.rows.iter().all(|&row| (row as usize) < table.len()) => {}
+                _ => return Err(InvalidSnapshot),
+            }
+        }
+        Ok(Runtime::from_snapshot(self, limits))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        write_len(&mut bytes, self.tasks.len());
+        for task in &self.tasks {
+            write_data(&mut bytes, &task.template);
+            write_data(&mut bytes, &task.goals);
+            match &task.split {
+                None => bytes.push(0),
+                Some(split) => {
+                    bytes.push(1);
+                    bytes.extend(split.ordinal.to_le_bytes());
+                    write_len(&mut bytes, split.rules.len());
+                    for &rule in &split.rules {
+                        write_len(&mut bytes, rule);
+                    }
+                    write_len(&mut bytes, split.rows.len());
+                    for &row in &split.rows {
+                        write_len(&mut bytes, row as usize);
+                    }
+                }
+            }
+        }
+        bytes
+    }
+
+    /// Reads a snapshot saved by `to_bytes`. Symbols new to the process stay interned for
+    /// good; bytes adding more than `atom::MAX_UNTRUSTED_ATOMS` of them are invalid.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, InvalidSnapshot> {
+        let mut reader = Reader(bytes);
+        if reader.take(MAGIC.len())? != MAGIC || reader.take(1)? != [VERSION] {
+            return Err(InvalidSnapshot);
+        }
+        let mut tasks = vec![];
+        for _ in 0..reader.count()? {
+            let template = reader.data()?;
+            let goals = reader.data()?;
+            let split = match reader.take(1)? {
+                [0] => None,
+                [1] => Some(Split {
+                    ordinal: u32::from_le_bytes(reader.take(4)?.try_into().unwrap()),
+                    rules: (0..reader.count()?)
+                        .map(|_| reader.len())
+                        .collect::<Result<_, _>>()?,
+                    rows: (0..reader.count()?)
+                        .map(|_| u32::try_from(reader.len()?).map_err(|_| InvalidSnapshot))
+                        .collect::<Result<_, _>>()?,
+                }),
+                _ => return Err(InvalidSnapshot),
+            };
+            // Saved variables are numbered from 0, so there are fewer than there are bytes.
+            if template
+                .iter()
+                .chain(&goals)
+                .any(|d| d.max_var() > bytes.len())
+            {
+                return Err(InvalidSnapshot);
+            }
+            tasks.push(Task {
+                template,
+                goals,
+                path: vec![],
+                split,
+            });
+        }
+        match reader.0.is_empty() {
+            true => Ok(Snapshot::new(tasks)),
+            false => Err(InvalidSnapshot),
+        }
+    }
+}
+
+fn write_len(bytes: &mut Vec<u8>, len: usize) {
+    bytes.extend((len as u64).to_le_bytes());
+}
+
+/// Writes a list of data, each term as its tag and length followed by its elements.
+fn write_data(bytes: &mut Vec<u8>, data: &[Data]) {
+    write_len(bytes, data.len());
+    let mut stack: Vec<_> = data.iter().rev().collect();
+    while let Some(data) = stack.pop() {
+        match data {
+            Data::Variable(n) => {
+                bytes.push(0);
+                write_len(bytes, *n);
+            }
+            Data::Symbol(s) => {
+                let name = s.name();
+                bytes.push(1);
+                write_len(bytes, name.len());
+                bytes.extend(name.as_bytes());
+            }
+            Data::Term(v) => {
+                bytes.push(2);
+                write_len(bytes, v.len());
+                stack.extend(v.iter().rev());
+            }
+        }
+    }
+}
+
+struct Reader<'b>(&'b [u8]);
+
+impl<'b> Reader<'b> {
+    fn take(&mut self, n: usize) -> Result<&'b [u8], InvalidSnapshot> {
+        if self.0.len() < n {
+            return Err(InvalidSnapshot);
+        }
+        let (taken, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn len(&mut self) -> Result<usize, InvalidSnapshot> {
+        let len = u64::from_le_bytes(self.take(8)?.try_into().unwrap());
+        usize::try_from(len).map_err(|_| InvalidSnapshot)
+    }
+
+    /// Reads the length of a list, each element of which takes at least one more byte.
+    fn count(&mut self) -> Result<usize, InvalidSnapshot> {
+        let count = self.len()?;
+        match count <= self.0.len() {
+            true => Ok(count),
+            false => Err(InvalidSnapshot),
+        }
+    }
+
+    fn data(&mut self) -> Result<Vec<Data>, InvalidSnapshot> {
+        enum Task {
+            Read,
+            Build(usize),
+        }
+
+        let len = self.count()?;
+        let mut tasks: Vec<_> = (0..len).map(|_| Task::Read).collect();
+        let mut done: Vec<Data> = Vec::new();
+        while let Some(task) = tasks.pop() {
+            match task {
+                Task::Read => match self.take(1)? {
+                    [0] => done.push(Data::Variable(self.len()?)),
+                    [1] => {
+                        let len = self.len()?;
+                        let name = std::str::from_utf8(self.take(len)?);
+                        let name = name.map_err(|_| InvalidSnapshot)?;
+                        let atom = Atom::untrusted(name).ok_or(InvalidSnapshot)?;
+                        done.push(Data::Symbol(atom));
+                    }
+                    [2] => {
+                        let len = self.count()?;
+                        tasks.push(Task::Build(len));
+                        tasks.extend((0..len).map(|_| Task::Read));
+                    }
+                    _ => return Err(InvalidSnapshot),
+                },
+                Task::Build(len) => {
+                    let v = done.split_off(done.len() - len);
+                    done.push(Data::Term(v.into()));
+                }
+            }
+        }
+        Ok(done)
+    }
+}
@@ -0,0 +1,383 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::Arc,
+    time::Instant,
+};
+
+use crate::{
+    atom::Atom,
+    bindings::{Bindings, Instance},
+    data::Data,
+    foreign::block_on,
+    limits::{Limit, Limits},
+    prepared_query::Param,
+    rng::Rng,
+    rule_map::Key,
+    runtime::{self, Builtin, Error},
+    world::{ClauseOrder, World},
+};
+
+/// Cost of a state of the search, given its goals left to resolve, first goal first.
+pub type Cost = Arc<dyn Fn(&[Data]) -> u64 + Send + Sync>;
+
+/// Order in which `World::run` explores alternatives.
+/// Strategies other than depth-first keep many resolvents at once, copying each out of the
+/// bindings. They have no choicepoints for cut to prune, so reaching a cut is an
+/// `Error::Unsupported`; cyclic terms, which cannot be copied, are an `Error::CyclicTerm`.
+#[derive(Clone, Default)]
+pub enum SearchStrategy {
+    /// Backtracking over the newest choicepoint first, as Prolog does.
+    #[default]
+    DepthFirst,
+    /// All resolvents of one derivation length before any longer one, so no answer is
+    /// hidden behind an infinite branch.
+    BreadthFirst,
+    /// Depth-first down to `start` resolution steps, then `start + step` and so on, until
+    /// a search is not cut off. Each answer is reported once, at the first depth finding it.
+    IterativeDeepening { start: usize, step: usize },
+    /// The cheapest resolvent first; ties go to the earliest found.
+    BestFirst(Cost),
+    /// Each branch in turn searches depth-first for `steps` resolution steps. Alternatives
+    /// met on the way become branches of their own, queued behind the others, so answers
+    /// of all clauses interleave as in miniKanren instead of later clauses starving.
+    Interleaving { steps: usize },
+}
+
+impl SearchStrategy {
+    pub fn best_first(cost: impl Fn(&[Data]) -> u64 + Send + Sync + 'static) -> Self {
+        SearchStrategy::BestFirst(Arc::new(cost))
+    }
+}
+
+/// A resolvent with the answer template as bound so far, its variables numbered from 0.
+#[derive(Clone)]
+pub(crate) struct State {
+    pub(crate) template: Vec<Data>,
+    pub(crate) goals: Vec<Data>,
+    var_num: usize,
+    depth: usize,
+}
+
+impl State {
+    /// The resolvent of a query that has not started.
+    pub(crate) fn query(goals: &[Data]) -> Result<Self, Error> {
+        let mut bindings = Bindings::new();
+        bindings.push(goals.iter().map(|d| d.max_var()).max().unwrap_or(0));
+        let template: Vec<_> = goals.iter().rev().map(|d| Instance::new(d, 0)).collect();
+        let goals = goals.iter().map(|d| Instance::new(d, 0));
+        resolvent(&bindings, &template, goals, 0)
+    }
+}
+
+/// How `expand` derived a resolvent from its parent.
+#[derive(Clone, Copy)]
+pub(crate) enum Choice {
+    /// Resolving the first goal with the clause at this index in `World::rules`.
+    Rule(usize),
+    /// With this row of the goal's fact table.
+    Row(usize),
+    /// With an answer of a foreign predicate.
+    Foreign,
+    /// By a builtin, or by cut.
+    Builtin,
+}
+
+/// What every strategy needs while searching.
+struct Search<'w, 'l, F> {
+    world: &'w World,
+    rng: Option<Rng>,
+    limits: &'l Limits,
+    deadline: Option<Instant>,
+    inferences: u64,
+    answers: usize,
+    resolved_fn: F,
+}
+
+impl<F: FnMut(&[Data])> Search<'_, '_, F> {
+    /// Counts resolving the first goal of `state` against the limits, then does it.
+    fn expand(&mut self, state: &State, mut push: impl FnMut(State)) -> Result<(), Error> {
+        if state.goals[0].as_symbol() == Some(Atom::CUT) {
+            return Err(Error::Unsupported("cut"));
+        }
+        self.inferences += 1;
+        let limits = self.limits;
+        limits.interrupt(self.inferences, self.deadline)?;
+        let exceeded = if limits.inferences.is_some_and(|max| self.inferences > max) {
+            Some(Limit::Inferences)
+        } else if limits.depth.is_some_and(|max| state.goals.len() > max) {
+            Some(Limit::Depth)
+        } else {
+            limits
+                .bindings
+                .is_some_and(|max| state.var_num > max)
+                .then_some(Limit::Bindings)
+        };
+        if let Some(limit) = exceeded {
+            return Err(Error::LimitExceeded(limit));
+        }
+        expand(self.world, state, &mut self.rng, |_, s| push(s))
+    }
+
+    /// Reports an answer, unless as many as the limit allows were reported already.
+    fn answer(&mut self, state: &State) -> Result<(), Error> {
+        if self.limits.answers.is_some_and(|max| self.answers >= max) {
+            return Err(Error::LimitExceeded(Limit::Answers));
+        }
+        (self.resolved_fn)(&state.template);
+        self.answers += 1;
+        Ok(())
+    }
+}
+
+pub(crate) fn run<F: FnMut(&[Data])>(
+    world: &World,
+    goals: &[Data],
+    params: &[(Param, Data)],
+    limits: &Limits,
+    resolved_fn: F,
+) -> Result<(), Error> {
+    let mut bindings = Bindings::new();
+    bindings.push(goals.iter().map(|d| d.max_var()).max().unwrap_or(0));
+    for (param, value) in params {
+        let base = bindings.alloc(value.max_var());
+        if !bindings.unify_variable(param.0, Instance::new(value, base)) {
+            return Ok(());
+        }
+    }
+    let template: Vec<_> = goals.iter().rev().map(|d| Instance::new(d, 0)).collect();
+    let goals = goals.iter().map(|d| Instance::new(d, 0));
+    let initial = resolvent(&bindings, &template, goals, 0)?;
+    let mut search = Search {
+        world,
+        rng: match world.clause_order {
+            ClauseOrder::Source => None,
+            ClauseOrder::Random { seed } => Some(Rng::new(seed)),
+        },
+        limits,
+        deadline: limits.deadline(),
+        inferences: 0,
+        answers: 0,
+        resolved_fn,
+    };
+
+    match &world.strategy {
+        SearchStrategy::DepthFirst => {
+            depth_limited(&mut search, &initial, usize::MAX, 0)?;
+        }
+        SearchStrategy::BreadthFirst => {
+            let mut queue = VecDeque::from([initial]);
+            while let Some(state) = queue.pop_front() {
+                if state.goals.is_empty() {
+                    search.answer(&state)?;
+                    continue;
+                }
+                search.expand(&state, |s| queue.push_back(s))?;
+            }
+        }
+        SearchStrategy::IterativeDeepening { start, step } => {
+            let (mut limit, mut min_depth) = (*start, 0);
+            while depth_limited(&mut search, &initial, limit, min_depth)? {
+                min_depth = limit + 1;
+                limit += step.max(&1);
+            }
+        }
+        SearchStrategy::BestFirst(cost) => {
+            let mut queue = BTreeMap::new();
+            let mut seq = 0u64;
+            queue.insert((cost(&initial.goals), seq), initial);
+            while let Some((_, state)) = queue.pop_first() {
+                if state.goals.is_empty() {
+                    search.answer(&state)?;
+                    continue;
+                }
+                search.expand(&state, |s| {
+                    seq += 1;
+                    queue.insert((cost(&s.goals), seq), s);
+                })?;
+            }
+        }
+        SearchStrategy::Interleaving { steps } => {
+            let mut queue = VecDeque::from([initial]);
+            'branches: while let Some(mut state) = queue.pop_front() {
+                for _ in 0..(*steps).max(1) {
+                    if state.goals.is_empty() {
+                        search.answer(&state)?;
+                        continue 'branches;
+                    }
+                    let mut resolvents = vec![];
+                    search.expand(&state, |s| resolvents.push(s))?;
+                    let mut resolvents = resolvents.into_iter();
+                    let Some(first) = resolvents.next() else {
+                        continue 'branches;
+                    };
+                    queue.extend(resolvents);
+                    state = first;
+                }
+                queue.push_back(state);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Searches depth-first down to `limit` resolution steps, reporting answers found at
+/// `min_depth` steps or more. Returns whether a resolvent was cut off at the limit.
+fn depth_limited<F: FnMut(&[Data])>(
+    search: &mut Search<F>,
+    initial: &State,
+    limit: usize,
+    min_depth: usize,
+) -> Result<bool, Error> {
+    let mut cut_off = false;
+    let mut stack = vec![initial.clone()];
+    while let Some(state) = stack.pop() {
+        if state.goals.is_empty() {
+            if state.depth >= min_depth {
+                search.answer(&state)?;
+            }
+            continue;
+        }
+        if state.depth >= limit {
+            cut_off = true;
+            continue;
+        }
+        let len = stack.len();
+        search.expand(&state, |s| stack.push(s))?;
+        stack[len..].reverse();
+    }
+    Ok(cut_off)
+}
+
+/// Resolves the first goal of `state` in every way it can be, in the order depth-first
+/// search would try them, passing on each resolvent with how it was derived.
+pub(crate) fn expand(
+    world: &World,
+    state: &State,
+    rng: &mut Option<Rng>,
+    mut push: impl FnMut(Choice, State),
+) -> Result<(), Error> {
+    let mut bindings = Bindings::new();
+    bindings.push(state.var_num);
+    let template: Vec<_> = state.template.iter().map(|d| Instance::new(d, 0)).collect();
+    let (goal, rest) = state.goals.split_first().unwrap();
+    let goal = Instance::new(goal, 0);
+    let rest = || rest.iter().map(|d| Instance::new(d, 0));
+    let depth = state.depth + 1;
+
+    if goal.data().as_symbol() == Some(Atom::CUT) {
+        push(
+            Choice::Builtin,
+            resolvent(&bindings, &template, rest(), depth)?,
+        );
+        return Ok(());
+    }
+    let key = Key::of_instance(&bindings, goal);
+    if let Some(builtin) = runtime::builtin(key) {
+        let goal = bindings.resolve(goal);
+        let Data::Term(v) = goal.data() else {
+            unreachable!()
+        };
+        let args: Vec<_> = v[1..]
+            .iter()
+            .map(|d| Instance::new(d, goal.base()))
+            .collect();
+        match builtin {
+            Builtin::UnifyWithOccursCheck => {
+                if bindings.unify_with_occurs_check(args[0], args[1]) {
+                    push(
+                        Choice::Builtin,
+                        resolvent(&bindings, &template, rest(), depth)?,
+                    );
+                }
+            }
+            Builtin::Par => {
+                let goals = args.into_iter().chain(rest());
+                push(
+                    Choice::Builtin,
+                    resolvent(&bindings, &template, goals, depth)?,
+                );
+            }
+        }
+        return Ok(());
+    }
+
+    if let Some(f) = world.foreign_for(key) {
+        for row in block_on(f(runtime::foreign_args(&bindings, goal))) {
+            bindings.mark();
+            if runtime::unify_row(&mut bindings, goal, &row) {
+                push(
+                    Choice::Foreign,
+                    resolvent(&bindings, &template, rest(), depth)?,
+                );
+            }
+            bindings.pop();
+        }
+        return Ok(());
+    }
+
+    let occurs_check = world.occurs_check_for(key);
+    let mut rule_indices = world.rule_map.get(&world.rules, &bindings, goal, key);
+    if let Some(rng) = rng {
+        rule_indices.shuffle(rng);
+    }
+    for rule_index in rule_indices {
+        let rule = &world.rules[rule_index];
+        bindings.mark();
+        let base = bindings.alloc(rule.var_num);
+        let head = Instance::new(&rule.head, base);
+        if bindings
+            .unify_with(goal, head, occurs_check)
+            .map_err(Error::OccursCheck)?
+        {
+            // Bodies are stored last goal first.
+            let body = rule.body.iter().rev().map(|d| Instance::new(d, base));
+            let resolvent = resolvent(&bindings, &template, body.chain(rest()), depth)?;
+            push(Choice::Rule(rule_index), resolvent);
+        }
+        bindings.pop();
+    }
+    let mut rows = world.facts.get(&bindings, goal, key);
+    if let Some(rng) = rng {
+        rows.shuffle(rng);
+    }
+    while let Some(row) = rows.next_row() {
+        bindings.mark();
+        if rows.unify(&world.facts, &mut bindings, goal, row) {
+            push(
+                Choice::Row(row),
+                resolvent(&bindings, &template, rest(), depth)?,
+            );
+        }
+        bindings.pop();
+    }
+    Ok(())
+}
+
+fn resolvent<'a>(
+    bindings: &Bindings<'a>,
+    template: &[Instance<'a>],
+    goals: impl Iterator<Item = Instance<'a>>,
+    depth: usize,
+) -> Result<State, Error> {
+    let mut instances = template.to_vec();
+    instances.extend(goals);
+    let Some(mut copies) = bindings.copy(None, &instances) else {
+        let cyclic = instances
+            .iter()
+            .find(|&&i| bindings.copy(None, &[i]).is_none())
+            .unwrap();
+        return Err(Error::CyclicTerm(bindings.data(*cyclic)));
+    };
+    let goals = copies.split_off(template.len());
+    Ok(State {
+        var_num: copies
+            .iter()
+            .chain(&goals)
+            .map(|d| d.max_var())
+            .max()
+            .unwrap_or(0),
+        template: copies,
+        goals,
+        depth,
+    })
+}
@@ -0,0 +1,72 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{
+    data::Data,
+    runtime::{Error, Runtime},
+    snapshot::Snapshot,
+    world::World,
+};
+
+/// Answers of a depth-first search as an async stream, for running queries on an executor
+/// without blocking it. Polling yields back to the executor every `yield_every` inferences
+/// and while a foreign predicate is pending. The stream ends after the last answer or the
+/// first error.
+pub struct Solutions<'a> {
+    world: &'a World,
+    runtime: Runtime<'a>,
+    yield_every: u64,
+    done: bool,
+}
+
+impl<'a> Solutions<'a> {
+    pub(crate) fn new(world: &'a World, runtime: Runtime<'a>, yield_every: u64) -> Self {
+        Solutions {
+            world,
+            runtime,
+            yield_every: yield_every.max(1),
+            done: false,
+        }
+    }
+
+    /// Polls for the next answer, with the signature of `futures::Stream::poll_next`.
+    pub fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Vec<Data>, Error>>> {
+        let this = self.get_mut();
+        if this.done {
+            return Poll::Ready(None);
+        }
+        let answer = match this.runtime.poll_next(this.world, cx, this.yield_every) {
+            Poll::Ready(answer) => answer,
+            Poll::Pending => return Poll::Pending,
+        };
+        this.done = !matches!(answer, Ok(Some(_)));
+        Poll::Ready(answer.transpose())
+    }
+
+    /// The next answer, or `None` once the stream has ended.
+    pub fn next_answer(&mut self) -> Next<'_, 'a> {
+        Next(self)
+    }
+
+    /// Saves what is left of the search as of the last poll, see `Runtime::snapshot`.
+    pub fn snapshot(&self) -> Result<Snapshot, Error> {
+        self.runtime.snapshot()
+    }
+}
+
+/// Future returned by `Solutions::next_answer`.
+pub struct Next<'s, 'a>(&'s mut Solutions<'a>);
+
+impl Future for Next<'_, '_> {
+    type Output = Option<Result<Vec<Data>, Error>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut *self.0).poll_next(cx)
+    }
+}
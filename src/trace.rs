@@ -0,0 +1,121 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::Write,
+};
+
+use crate::{atom::Atom, data::Data, user_data::UserData, world::VariableScope};
+
+/// Receives the Byrd box ports of the predicate calls of a depth-first run. `depth` counts
+/// the calls a goal is nested in. `clause` is the index in `World::rules` of the clause the
+/// goal last succeeded through, `None` before that and for fact rows and foreign answers.
+/// Unbound variables are numbered by their index in the bindings, the query's first; one
+/// that a query variable is bound to is numbered as that query variable. After an exit,
+/// backtracking into the call retries it and each of its ancestors that exited, outermost
+/// first, before it fails or exits again. Tracers are `Send + Sync` so that a traced query
+/// may move between threads.
+pub trait Tracer: Send + Sync {
+    /// The goal is called.
+    fn call(&mut self, _goal: &Data, _depth: usize, _clause: Option<usize>) {}
+    /// The goal succeeded.
+    fn exit(&mut self, _goal: &Data, _depth: usize, _clause: Option<usize>) {}
+    /// Backtracking returned to the goal for another answer.
+    fn redo(&mut self, _goal: &Data, _depth: usize, _clause: Option<usize>) {}
+    /// The goal has no more answers.
+    fn fail(&mut self, _goal: &Data, _depth: usize, _clause: Option<usize>) {}
+}
+
+impl<T: Tracer + ?Sized> Tracer for &mut T {
+    fn call(&mut self, goal: &Data, depth: usize, clause: Option<usize>) {
+        (**self).call(goal, depth, clause)
+    }
+
+    fn exit(&mut self, goal: &Data, depth: usize, clause: Option<usize>) {
+        (**self).exit(goal, depth, clause)
+    }
+
+    fn redo(&mut self, goal: &Data, depth: usize, clause: Option<usize>) {
+        (**self).redo(goal, depth, clause)
+    }
+
+    fn fail(&mut self, goal: &Data, depth: usize, clause: Option<usize>) {
+        (**self).fail(goal, depth, clause)
+    }
+}
+
+/// Writes each port on a line indented by depth, naming the query's variables as written
+/// and others `_G<n>`. With spy points set, only ports of spied predicates are written.
+pub struct PrettyTracer<W> {
+    out: W,
+    names: HashMap<usize, String>,
+    spies: HashSet<(Atom, usize)>,
+}
+
+impl<W: Write> PrettyTracer<W> {
+    /// Traces `query`, whose variable names it shows.
+    pub fn new(query: &[UserData], out: W) -> Self {
+        let mut scope = VariableScope::new();
+        scope.new_data_vec(query);
+        let names = scope
+            .into_variables()
+            .filter_map(|(name, data)| match data {
+                Data::Variable(n) => Some((n, name)),
+                _ => None,
+            })
+            .collect();
+        PrettyTracer {
+            out,
+            names,
+            spies: HashSet::new(),
+        }
+    }
+
+    /// Adds a spy point on the predicate `name`/`arity`.
+    pub fn spy(&mut self, name: &str, arity: usize) {
+        self.spies.insert((Atom::new(name), arity));
+    }
+
+    fn write(&mut self, port: &str, goal: &Data, depth: usize, clause: Option<usize>) {
+        if !self.spies.is_empty() && !predicate(goal).is_some_and(|p| self.spies.contains(&p)) {
+            return;
+        }
+        let names = &self.names;
+        let goal = goal.display_with(|f, n| match names.get(&n) {
+            Some(name) => write!(f, "{}", name),
+            None => write!(f, "_G{}", n),
+        });
+        let clause = clause.map_or(String::new(), |i| format!("  [clause {}]", i));
+        let indent = depth * 2;
+        let _ = writeln!(
+            self.out,
+            "{:indent$}{}: ({}) {}{}",
+            "", port, depth, goal, clause
+        );
+    }
+}
+
+impl<W: Write + Send + Sync> Tracer for PrettyTracer<W> {
+    fn call(&mut self, goal: &Data, depth: usize, clause: Option<usize>) {
+        self.write("Call", goal, depth, clause);
+    }
+
+    fn exit(&mut self, goal: &Data, depth: usize, clause: Option<usize>) {
+        self.write("Exit", goal, depth, clause);
+    }
+
+    fn redo(&mut self, goal: &Data, depth: usize, clause: Option<usize>) {
+        self.write("Redo", goal, depth, clause);
+    }
+
+    fn fail(&mut self, goal: &Data, depth: usize, clause: Option<usize>) {
+        self.write("Fail", goal, depth, clause);
+    }
+}
+
+/// Name and arity of the predicate `goal` calls.
+pub(crate) fn predicate(goal: &Data) -> Option<(Atom, usize)> {
+    match goal {
+        Data::Symbol(s) => Some((*s, 0)),
+        Data::Term(v) => Some((v.first()?.as_symbol()?, v.len() - 1)),
+        Data::Variable(_) => None,
+    }
+}
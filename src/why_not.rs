@@ -0,0 +1,182 @@
+use std::{collections::HashSet, fmt};
+
+use crate::{
+    bindings::{Bindings, Instance},
+    data::Data,
+    rule_map::Key,
+    world::World,
+};
+
+/// Distinct goals kept at the deepest frontier depth.
+const MAX_GOALS: usize = 64;
+
+/// Clause heads and fact rows kept for each frontier goal.
+const MAX_CLOSEST: usize = 3;
+
+/// Clause heads, and then fact rows, compared with each frontier goal at most.
+const MAX_COMPARED: usize = 1000;
+
+/// A goal on the frontier of a failed search: no clause or fact row of its predicate
+/// resolved it, and no goal that failed that way was nested deeper. Recorded by
+/// `Runtime::record_failures`.
+#[derive(Debug)]
+pub struct Failure {
+    /// The goal as called. Unbound variables are numbered by their index in the bindings,
+    /// as for a `Tracer`.
+    pub goal: Data,
+    /// Number of calls the goal was nested in.
+    pub depth: usize,
+    /// Clause heads and fact rows of the goal's predicate that came closest to matching it,
+    /// closest first.
+    pub closest: Vec<Mismatch>,
+}
+
+/// A clause head or fact row that does not unify with a goal.
+#[derive(Debug)]
+pub struct Mismatch {
+    /// Index in `World::rules` of the clause, `None` for a fact row.
+    pub rule: Option<usize>,
+    pub head: Data,
+    /// Position, from 1, of the first argument that did not unify once those before it did.
+    pub argument: usize,
+}
+
+/// Writes the goal, then each of the closest heads on its own indented line.
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} at depth {}", self.goal, self.depth)?;
+        if self.closest.is_empty() {
+            return writeln!(f, "  no clauses or facts");
+        }
+        for m in &self.closest {
+            write!(f, "  argument {} of {}", m.argument, m.head)?;
+            match m.rule {
+                Some(i) => writeln!(f, "  [clause {}]", i)?,
+                None => writeln!(f, "  [fact]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The deepest goals seen failing without being resolved.
+#[derive(Default)]
+pub(crate) struct Frontier {
+    depth: usize,
+    goals: Vec<Data>,
+    seen: HashSet<String>,
+}
+
+impl Frontier {
+    pub(crate) fn record(&mut self, goal: &Data, depth: usize) {
+        if depth < self.depth || (depth == self.depth && self.goals.len() == MAX_GOALS) {
+            return;
+        }
+        if depth > self.depth || self.goals.is_empty() {
+            self.depth = depth;
+            self.goals.clear();
+            self.seen.clear();
+        }
+        if self.seen.insert(goal.to_string()) {
+            self.goals.push(goal.clone());
+        }
+    }
+
+    pub(crate) fn failures(&self, world: &World) -> Vec<Failure> {
+        self.goals
+            .iter()
+            .map(|goal| Failure {
+                goal: goal.clone(),
+                depth: self.depth,
+                closest: closest(world, goal),
+            })
+            .collect()
+    }
+}
+
+/// A clause head or fact row of the goal's predicate.
+enum Head<'w> {
+    /// Index and head arguments of a clause.
+    Rule(usize, &'w [Data]),
+    Row(usize),
+}
+
+/// Unifies the arguments of `goal` with those of the clause heads and fact rows of its
+/// predicate in turn, keeping the heads that got furthest. The indexes pick the heads to
+/// compare first: those that match its bound arguments.
+fn closest(world: &World, goal: &Data) -> Vec<Mismatch> {
+    let Data::Term(args) = goal else {
+        return vec![];
+    };
+    let Some(functor) = args.first().and_then(Data::as_symbol) else {
+        return vec![];
+    };
+    let key = Key::term(functor, args.len());
+
+    let mut bindings = Bindings::new();
+    bindings.alloc(goal.max_var() + 1);
+    let indexed = world
+        .rule_map
+        .get(&world.rules, &bindings, Instance::new(goal, 0), Some(key));
+    let mut seen = HashSet::new();
+    let rules: Vec<_> = indexed
+        .chain(world.rule_map.clauses(key).iter().copied())
+        .filter(|&i| seen.insert(i))
+        .filter_map(|i| match &world.rules[i].head {
+            Data::Term(head)
+                if head.len() == args.len() && head[0].as_symbol() == Some(functor) =>
+            {
+                Some(Head::Rule(i, &head[1..]))
+            }
+            _ => None,
+        })
+        .take(MAX_COMPARED)
+        .collect();
+    let table = world.facts.table(functor, args.len() - 1);
+    let rows = table.map_or(vec![], |t| {
+        world.facts.nearest_rows(t, &args[1..], MAX_COMPARED)
+    });
+    let cells = |row| (0..args.len() - 1).map(move |c| world.facts.cell(table.unwrap(), row, c));
+
+    let mut mismatches: Vec<_> = rules
+        .into_iter()
+        .chain(rows.into_iter().map(Head::Row))
+        .filter_map(|head| {
+            bindings.mark();
+            let (head_args, base): (Vec<_>, _) = match head {
+                Head::Rule(i, head_args) => (
+                    head_args.iter().collect(),
+                    bindings.alloc(world.rules[i].var_num),
+                ),
+                Head::Row(row) => (cells(row).collect(), 0),
+            };
+            let argument = args[1..]
+                .iter()
+                .zip(head_args)
+                .position(|(g, h)| !bindings.unify(Instance::new(g, 0), Instance::new(h, base)));
+            bindings.pop();
+            Some((argument? + 1, head))
+        })
+        .collect();
+    mismatches.sort_by_key(|&(argument, _)| std::cmp::Reverse(argument));
+    mismatches.truncate(MAX_CLOSEST);
+    mismatches
+        .into_iter()
+        .map(|(argument, head)| match head {
+            Head::Rule(i, _) => Mismatch {
+                rule: Some(i),
+                head: world.rules[i].head.clone(),
+                argument,
+            },
+            Head::Row(row) => Mismatch {
+                rule: None,
+                head: Data::Term(
+                    std::iter::once(args[0].clone())
+                        .chain(cells(row).cloned())
+                        .collect(),
+                ),
+                argument,
+            },
+        })
+        .collect()
+}
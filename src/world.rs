@@ -1,6 +1,34 @@
-use std::{collections::HashMap, rc::Rc};
+use std::{
+    collections::HashMap,
+    fs::File,
+    future::Future,
+    io,
+    io::BufReader,
+    path::Path,
+    sync::{atomic::AtomicUsize, Arc},
+    thread,
+};
 
-use crate::{data::Data, rule_map::RuleMap, runtime::Runtime, user_data::UserData};
+use crate::{
+    atom::Atom,
+    bindings::OccursCheck,
+    data::Data,
+    fact_table::{self, FactBase},
+    foreign::Foreign,
+    limits::Limits,
+    machine::Program,
+    parallel::{self, Answers, Parallel},
+    prepared_query::PreparedQuery,
+    profile::Profile,
+    proof::Proof,
+    rule_map::{Key, KeyMap, RuleMap},
+    runtime::{Error, Runtime},
+    strategy::SearchStrategy,
+    stream::Solutions,
+    trace::Tracer,
+    user_data::UserData,
+    why_not::Failure,
+};
 
 pub struct Rule {
     pub head: Data,
@@ -9,12 +37,9 @@ pub struct Rule {
 }
 
 impl Rule {
-    pub fn from_user_data(
-        v: &[UserData],
-        intern: &mut impl FnMut(Rc<String>) -> Rc<String>,
-    ) -> Self {
+    pub fn from_user_data(v: &[UserData]) -> Self {
         let mut scope = VariableScope::new();
-        let mut it = v.iter().map(|x| scope.new_data(x, intern));
+        let mut it = v.iter().map(|x| scope.new_data(x));
         let head = it.next().unwrap();
         let body = it.rev().collect();
         Rule {
@@ -25,31 +50,7 @@ impl Rule {
     }
 }
 
-pub struct SymbolPool(HashMap<Rc<String>, Rc<String>>);
-
-impl SymbolPool {
-    pub fn new() -> Self {
-        SymbolPool(Default::default())
-    }
-
-    pub fn get_and_insert(&mut self, string: Rc<String>) -> Rc<String> {
-        if let Some(r) = self.0.get(&string) {
-            r.clone()
-        } else {
-            self.0.insert(string.clone(), string.clone());
-            string
-        }
-    }
-
-    pub fn get(&self, string: Rc<String>) -> Rc<String> {
-        if let Some(r) = self.0.get(&string) {
-            r.clone()
-        } else {
-            string
-        }
-    }
-}
-
+#[derive(Default)]
 pub struct VariableScope(HashMap<String, Data>);
 
 impl VariableScope {
@@ -61,11 +62,7 @@ impl VariableScope {
         self.0.len()
     }
 
-    pub fn new_data(
-        &mut self,
-        data: &UserData,
-        intern: &mut impl FnMut(Rc<String>) -> Rc<String>,
-    ) -> Data {
+    pub fn new_data(&mut self, data: &UserData) -> Data {
         let n = self.size();
         match data {
             UserData::Variable(v) => self
@@ -78,42 +75,273 @@ impl VariableScope {
                 self.0.insert(format!("unnamed:{}", n), data.clone());
                 data
             }
-            UserData::Term(v) => Data::Term(v.iter().map(|x| self.new_data(x, intern)).collect()),
-            UserData::Symbol(s) => Data::Symbol(intern(Rc::new(s.clone()))),
+            UserData::Term(v) => Data::Term(v.iter().map(|x| self.new_data(x)).collect()),
+            UserData::Symbol(s) => Data::Symbol(Atom::new(s)),
         }
     }
 
-    pub fn new_data_vec(
-        &mut self,
-        slice: &[UserData],
-        intern: &mut impl FnMut(Rc<String>) -> Rc<String>,
-    ) -> Vec<Data> {
-        slice.iter().map(|x| self.new_data(x, intern)).collect()
+    /// Named variables and the data they were numbered as; wildcards are left out.
+    pub fn into_variables(self) -> impl Iterator<Item = (String, Data)> {
+        self.0
+            .into_iter()
+            .filter(|(name, _)| !name.starts_with("unnamed:"))
     }
+
+    pub fn new_data_vec(&mut self, slice: &[UserData]) -> Vec<Data> {
+        slice.iter().map(|x| self.new_data(x)).collect()
+    }
+}
+
+/// How a `World` resolves goals against its rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mode {
+    /// Unify goals with the clause heads directly.
+    #[default]
+    Interpreted,
+    /// Compile the rules up front to WAM-style instructions (get/put/unify, try/retry/trust,
+    /// call/execute) and run depth-first queries on the `machine` module's VM, with the
+    /// same answers as interpreted mode. Queries the VM cannot run are interpreted: those
+    /// with limits, a clause order, foreign predicates, fact tables or per-predicate occurs
+    /// checks, and streams, snapshots, traces, proofs and profiles; so are all queries if a
+    /// clause head has no symbol functor. The VM frees memory on backtracking only.
+    Compiled,
+}
+
+/// Order in which the clauses and fact rows matching a goal are tried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ClauseOrder {
+    /// The order of `rules`, then of the fact table.
+    #[default]
+    Source,
+    /// A random order of the clauses, then of the fact rows, at every call; the same for
+    /// every run from the same seed (on one thread).
+    Random { seed: u64 },
 }
 
 pub struct World {
     pub rules: Vec<Rule>,
-    pub symbol_pool: SymbolPool,
+    pub facts: FactBase,
+    pub occurs_check: OccursCheck,
+    pub strategy: SearchStrategy,
+    pub clause_order: ClauseOrder,
     pub(crate) rule_map: RuleMap,
+    pub(crate) program: Option<Program>,
+    /// Whether some rule body calls cut, which prunes across threads' work.
+    pub(crate) uses_cut: bool,
+    /// Threads `par` goals may still start besides the ones running queries.
+    pub(crate) spare_threads: AtomicUsize,
+    predicate_occurs_check: KeyMap<OccursCheck>,
+    foreign: KeyMap<Foreign>,
 }
 
 impl World {
     pub fn new(rules: Vec<Vec<UserData>>) -> Self {
-        let mut symbol_pool = SymbolPool::new();
-        let rules = rules
+        Self::with_mode(rules, Mode::default())
+    }
+
+    pub fn with_mode(rules: Vec<Vec<UserData>>, mode: Mode) -> Self {
+        let rules: Vec<_> = rules
             .into_iter()
-            .map(|rule| Rule::from_user_data(&rule, &mut |s| symbol_pool.get_and_insert(s)))
+            .map(|rule| Rule::from_user_data(&rule))
             .collect();
+        let rule_map = RuleMap::from_rules(&rules);
+        let program = match mode {
+            Mode::Interpreted => None,
+            Mode::Compiled => Program::compile(&rules, &rule_map),
+        };
+        let uses_cut = rules.iter().any(|rule| {
+            rule.body
+                .iter()
+                .any(|goal| goal.as_symbol() == Some(Atom::CUT))
+        });
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
         Self {
-            rule_map: RuleMap::from_rules(&rules),
+            rule_map,
+            program,
+            uses_cut,
+            spare_threads: AtomicUsize::new(threads - 1),
             rules,
-            symbol_pool,
+            facts: FactBase::default(),
+            occurs_check: OccursCheck::default(),
+            strategy: SearchStrategy::default(),
+            clause_order: ClauseOrder::default(),
+            predicate_occurs_check: KeyMap::default(),
+            foreign: KeyMap::default(),
         }
     }
 
-    pub fn run<F: FnMut(&[Data])>(&self, data_slice: &[UserData], resolved_fn: F) {
-        let goals = VariableScope::new().new_data_vec(data_slice, &mut |s| self.symbol_pool.get(s));
-        Runtime::run(&self, &goals, resolved_fn);
+    /// Overrides `occurs_check` for head unification of the predicate `name`/`arity`.
+    pub fn set_occurs_check(&mut self, name: &str, arity: usize, occurs_check: OccursCheck) {
+        self.predicate_occurs_check
+            .insert(Key::term(Atom::new(name), arity + 1), occurs_check);
+    }
+
+    /// The compiled program, if a depth-first query under `limits` can run on it.
+    pub(crate) fn program(&self, limits: &Limits) -> Option<&Program> {
+        let runs = limits.is_unlimited()
+            && self.clause_order == ClauseOrder::Source
+            && self.foreign.is_empty()
+            && self.facts.keys().next().is_none()
+            && self.predicate_occurs_check.is_empty();
+        self.program.as_ref().filter(|_| runs)
+    }
+
+    pub(crate) fn occurs_check_for(&self, key: Option<Key>) -> OccursCheck {
+        if self.predicate_occurs_check.is_empty() {
+            return self.occurs_check;
+        }
+        key.and_then(|key| self.predicate_occurs_check.get(&key))
+            .map_or(self.occurs_check, |&o| o)
+    }
+
+    /// Defines the predicate `name`/`arity` by `f`, which may await external work. Goals
+    /// calling it take the rows of its answers as alternatives; clauses and facts of the
+    /// same predicate are not tried.
+    pub fn register_foreign<F, Fut>(&mut self, name: &str, arity: usize, f: F)
+    where
+        F: Fn(Vec<Data>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Vec<Vec<Atom>>> + Send + 'static,
+    {
+        let f: Foreign = Box::new(move |args| Box::pin(f(args)));
+        self.foreign
+            .insert(Key::term(Atom::new(name), arity + 1), f);
+    }
+
+    pub(crate) fn foreign_keys(&self) -> impl Iterator<Item = Key> + '_ {
+        self.foreign.keys().copied()
+    }
+
+    pub(crate) fn foreign_for(&self, key: Option<Key>) -> Option<&Foreign> {
+        if self.foreign.is_empty() {
+            return None;
+        }
+        key.and_then(|key| self.foreign.get(&key))
+    }
+
+    /// Appends ground facts `(name ...)` given as rows of symbols, returning the number of rows.
+    pub fn load_facts<I, R, S>(&mut self, name: &str, rows: I) -> io::Result<usize>
+    where
+        I: IntoIterator<Item = R>,
+        R: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let rows = rows.into_iter().map(Ok);
+        self.facts.load(name, rows, |cell| Some(Atom::new(cell)))
+    }
+
+    /// Appends the rows of a tab-separated file as facts `(name ...)`. Symbols new to the
+    /// process stay interned after the `World` is dropped; loading fails with
+    /// `InvalidData` once `atom::MAX_UNTRUSTED_ATOMS` such symbols came from files and snapshots.
+    pub fn load_tsv(&mut self, name: &str, path: impl AsRef<Path>) -> io::Result<usize> {
+        let reader = BufReader::new(File::open(path)?);
+        let rows = fact_table::read_rows(reader, fact_table::split_tsv);
+        self.facts.load(name, rows, Atom::untrusted)
+    }
+
+    /// Appends the rows of a comma-separated file as facts `(name ...)`, interning symbols
+    /// as `load_tsv` does.
+    pub fn load_csv(&mut self, name: &str, path: impl AsRef<Path>) -> io::Result<usize> {
+        let reader = BufReader::new(File::open(path)?);
+        let rows = fact_table::read_rows(reader, fact_table::split_csv);
+        self.facts.load(name, rows, Atom::untrusted)
+    }
+
+    /// Converts `goals` once for repeated runs; their named variables become parameters.
+    pub fn prepare(&self, goals: &[UserData]) -> PreparedQuery<'_> {
+        PreparedQuery::new(self, goals)
+    }
+
+    pub fn run<F: FnMut(&[Data])>(
+        &self,
+        data_slice: &[UserData],
+        resolved_fn: F,
+    ) -> Result<(), Error> {
+        let goals = VariableScope::new().new_data_vec(data_slice);
+        Runtime::run(self, &goals, resolved_fn)
+    }
+
+    /// Like `run`, aborting with `Error::LimitExceeded` once the query uses up one of `limits`.
+    pub fn run_limited<F: FnMut(&[Data])>(
+        &self,
+        data_slice: &[UserData],
+        limits: &Limits,
+        resolved_fn: F,
+    ) -> Result<(), Error> {
+        let goals = VariableScope::new().new_data_vec(data_slice);
+        Runtime::run_with(self, &goals, &[], &[], limits, resolved_fn)
+    }
+
+    /// Answers of `data_slice` as an async stream, yielding every `yield_every` inferences,
+    /// see `Solutions`. Always searches depth-first.
+    pub fn stream(&self, data_slice: &[UserData], yield_every: u64) -> Solutions<'_> {
+        let goals = VariableScope::new().new_data_vec(data_slice);
+        Solutions::new(self, Runtime::owning(&goals), yield_every)
+    }
+
+    /// Like `run`, reporting the ports of predicate calls to `tracer`. Always searches
+    /// depth-first.
+    pub fn run_traced<F: FnMut(&[Data])>(
+        &self,
+        data_slice: &[UserData],
+        tracer: &mut dyn Tracer,
+        mut resolved_fn: F,
+    ) -> Result<(), Error> {
+        let goals = VariableScope::new().new_data_vec(data_slice);
+        let mut rt = Runtime::new(goals.iter().rev(), &goals);
+        rt.set_tracer(tracer);
+        while let Some(answer) = rt.next(self)? {
+            resolved_fn(&answer);
+        }
+        Ok(())
+    }
+
+    /// Like `run`, also passing `resolved_fn` how each answer was proved, one proof for
+    /// each predicate call among the goals.
+    pub fn run_with_proofs<F: FnMut(&[Data], &[Proof])>(
+        &self,
+        data_slice: &[UserData],
+        mut resolved_fn: F,
+    ) -> Result<(), Error> {
+        let goals = VariableScope::new().new_data_vec(data_slice);
+        let mut rt = Runtime::new(goals.iter().rev(), &goals);
+        rt.record_proofs();
+        while let Some(answer) = rt.next(self)? {
+            resolved_fn(&answer, &rt.proof(self));
+        }
+        Ok(())
+    }
+
+    /// Like `run`, also profiling the search, see `Runtime::profile`.
+    pub fn run_profiled<F: FnMut(&[Data])>(
+        &self,
+        data_slice: &[UserData],
+        mut resolved_fn: F,
+    ) -> Result<Profile, Error> {
+        let goals = VariableScope::new().new_data_vec(data_slice);
+        let mut rt = Runtime::new(goals.iter().rev(), &goals);
+        rt.record_profile();
+        while let Some(answer) = rt.next(self)? {
+            resolved_fn(&answer);
+        }
+        Ok(rt.profile())
+    }
+
+    /// Explains why `data_slice` has no answers, see `Runtime::why_not`. Empty if it has one.
+    pub fn why_not(&self, data_slice: &[UserData]) -> Result<Vec<Failure>, Error> {
+        let goals = VariableScope::new().new_data_vec(data_slice);
+        let mut rt = Runtime::new(goals.iter().rev(), &goals);
+        rt.record_failures();
+        Ok(match rt.next(self)? {
+            Some(_) => vec![],
+            None => rt.why_not(self),
+        })
+    }
+
+    /// Searches `data_slice` depth-first on several threads, see `Parallel`. Answers
+    /// arrive as they are found; dropping them stops the search. Unbound variables in
+    /// answers may be numbered differently than in a sequential run.
+    pub fn run_parallel(self: &Arc<Self>, data_slice: &[UserData], options: Parallel) -> Answers {
+        let goals = VariableScope::new().new_data_vec(data_slice);
+        parallel::spawn(self.clone(), goals, options)
     }
 }
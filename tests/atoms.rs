@@ -0,0 +1,22 @@
+use std::thread;
+
+use prlg::Atom;
+
+/// Atoms interned concurrently get one id per name, and read back their names.
+#[test]
+fn concurrent_interning() {
+    let names: Vec<_> = (0..5000).map(|i| format!("atom_test_{i}")).collect();
+    let ids: Vec<Vec<u32>> = thread::scope(|s| {
+        let workers: Vec<_> = (0..4)
+            .map(|_| s.spawn(|| names.iter().map(|n| Atom::new(n).id()).collect()))
+            .collect();
+        workers.into_iter().map(|w| w.join().unwrap()).collect()
+    });
+    assert!(ids.iter().all(|v| *v == ids[0]));
+    for (name, &id) in names.iter().zip(&ids[0]) {
+        let atom = Atom::new(name);
+        assert_eq!(atom.id(), id);
+        assert_eq!(&*atom.name(), name);
+    }
+    assert_eq!(&*Atom::CONS.name(), "cons");
+}
@@ -0,0 +1,77 @@
+#[macro_use]
+extern crate prlg;
+
+use prlg::{
+    limits::Limits, snapshot::Snapshot, strategy::SearchStrategy, world::ClauseOrder, World,
+};
+
+fn world() -> World {
+    let mut world = World::new(rules![
+        (c c0) (c c1) (c c2) (c c3) (c c4) (c c5) (c c6) (c c7)
+        (both {x} {y}) {
+            (c {x})
+            (p {y})
+        }
+    ]);
+    let rows: Vec<_> = (0..16).map(|i| [format!("r{i}")]).collect();
+    world.load_facts("p", rows).unwrap();
+    world
+}
+
+fn answers(world: &World, goal: prlg::user_data::UserData) -> Vec<String> {
+    let mut out = vec![];
+    world.run(&[goal], |c| out.push(c[0].to_string())).unwrap();
+    out
+}
+
+fn sorted(mut answers: Vec<String>) -> Vec<String> {
+    answers.sort();
+    answers
+}
+
+#[test]
+fn seed_decides_the_order() {
+    let mut world = world();
+    for strategy in [SearchStrategy::DepthFirst, SearchStrategy::BreadthFirst] {
+        world.strategy = strategy;
+        for goal in [data! {(c {x})}, data! {(p {x})}, data! {(both {x} {y})}] {
+            world.clause_order = ClauseOrder::Source;
+            let source = answers(&world, goal.clone());
+            world.clause_order = ClauseOrder::Random { seed: 1 };
+            let first = answers(&world, goal.clone());
+            assert_eq!(answers(&world, goal.clone()), first);
+            world.clause_order = ClauseOrder::Random { seed: 2 };
+            let second = answers(&world, goal.clone());
+            assert_ne!(first, second);
+            assert_ne!(first, source);
+            assert_eq!(sorted(first), sorted(source.clone()));
+            assert_eq!(sorted(second), sorted(source));
+        }
+    }
+}
+
+#[test]
+fn resumed_snapshot_keeps_the_shuffled_rows() {
+    let mut world = world();
+    world.clause_order = ClauseOrder::Random { seed: 3 };
+    let whole = answers(&world, data! {(p {x})});
+
+    let limits = Limits {
+        inferences: Some(5),
+        ..Default::default()
+    };
+    let mut resumed = vec![];
+    let mut bytes = Snapshot::query(&[data! {(p {x})}]).to_bytes();
+    loop {
+        let snapshot = Snapshot::from_bytes(&bytes).unwrap();
+        if snapshot.is_empty() {
+            break;
+        }
+        let mut rt = snapshot.resume(&world, &limits).unwrap();
+        while let Ok(Some(answer)) = rt.next(&world) {
+            resumed.push(answer[0].to_string());
+        }
+        bytes = rt.snapshot().unwrap().to_bytes();
+    }
+    assert_eq!(resumed, whole);
+}
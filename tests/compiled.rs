@@ -0,0 +1,304 @@
+#[macro_use]
+extern crate prlg;
+
+use prlg::{bindings::OccursCheck, user_data::UserData, world::Mode, World};
+
+/// Answers of `query`, or its error, as text.
+fn answers(world: &World, query: &[UserData]) -> Result<Vec<String>, String> {
+    let mut answers = vec![];
+    world
+        .run(query, |c| answers.push(format!("{:?}", c)))
+        .map_err(|e| format!("{:?}", e))?;
+    Ok(answers)
+}
+
+/// Runs each query interpreted and compiled under every occurs check, expecting the same
+/// answers in the same order. Queries with cyclic answers are left out: their variables
+/// are named after the size of the interpreter's store.
+fn assert_same(rules: Vec<Vec<UserData>>, queries: &[Vec<UserData>]) {
+    for occurs_check in [OccursCheck::False, OccursCheck::True, OccursCheck::Error] {
+        let mut interpreted = World::with_mode(rules.clone(), Mode::Interpreted);
+        let mut compiled = World::with_mode(rules.clone(), Mode::Compiled);
+        interpreted.occurs_check = occurs_check;
+        compiled.occurs_check = occurs_check;
+        for query in queries {
+            eprintln!("Q {:?} {:?}", occurs_check, query);
+            let expected = answers(&interpreted, query);
+            assert!(expected.as_ref().map_or(true, |a| !a.is_empty()), "{:?}", query);
+            assert_eq!(
+                answers(&compiled, query),
+                expected,
+                "{:?} {:?}",
+                occurs_check,
+                query
+            );
+        }
+    }
+}
+
+#[test]
+fn main() {
+    let rules = rules![
+        (yo {x})
+        (hoge fuga)
+        (my_list [a b c])
+        (member {x} [{x} . {}])
+        (member {x} [{} . {xs}]) {
+            (member {x} {xs})
+        }
+        (append nil {xs} {xs})
+        (append [{x} . {xs}] {ys} [{x} . {zs}]) {
+            (append {xs} {ys} {zs})
+        }
+        (delete [] {} [])
+        (delete [{x} . {xs}] {x} {ys}) {
+            (delete {xs} {x} {ys})
+            cut
+        }
+        (delete [{x} . {xs}] {y} [{x} . {ys}]) {
+            (delete {xs} {y} {ys})
+        }
+        (isa ピカチュウ pokemon)
+        (isa カイリュー pokemon)
+        (isa ヤドラン pokemon)
+        (all_pokemon {p}) {
+            (isa {p} pokemon)
+        }
+        (hogehoge ({fuga} {fuga} {piyp})) {
+            {piyo}
+        }
+        (eq {x} {x})
+        (perm {xs}) {
+            (eq {xs} [{} {} {}])
+            (member a {xs})
+            (member b {xs})
+            (member c {xs})
+        }
+    ];
+    let queries = [
+        vec![data! {(all_pokemon {nyan})}],
+        vec![data! {(append (cons a nil) (cons b nil) {nyan})}],
+        vec![data! {(append {x} {y} [a b c])}],
+        vec![data! {(delete [a b c d] c {})}],
+        vec![data! {(delete [a b a] a {x})}],
+        vec![data! {(my_list {nyan})}],
+        vec![data! {(perm {nyan})}],
+        vec![data! {(member {x} [a {y} b])}, data! {(eq {x} {y})}],
+        vec![data! {(eq {x} (f {y}))}, data! {(eq {y} (g {x}))}, data! {(isa {p} {x})}],
+        vec![data! {(eq (f {x} {y}) (f {y} {x}))}, data! {(yo {x})}],
+    ];
+    assert_same(rules, &queries);
+}
+
+#[test]
+fn nat() {
+    let rules = rules![
+        (add zero {y} {y})
+        (add (s {x}) {y} (s {z})) {
+            (add {x} {y} {z})
+        }
+        (mul zero {} zero)
+        (mul (s {x}) {y} {z}) {
+            (mul {x} {y} {z_})
+            (add {y} {z_} {z})
+        }
+        (le {x} {y}) {
+            (add {x} {} {y})
+            cut
+        }
+        (lt {x} (s {y})) {
+            (add {x} {} {y})
+            cut
+        }
+        (div {x} {y} zero {x}) {
+            (lt {x} {y})
+        }
+        (div {x} {y} (s {q}) {r}) {
+            (add {y} {x_} {x})
+            (div {x_} {y} {q} {r})
+        }
+    ];
+    let queries = [
+        vec![data! {(add (s zero) (s (s zero)) {})}],
+        vec![data! {(add {x} {y} (s (s (s zero))))}],
+        vec![data! {(mul (s (s (s zero))) (s (s (s (s zero)))) {})}],
+        vec![
+            data! {(mul (s (s (s zero))) (s (s (s (s zero)))) {a})},
+            data! {(div (s {a}) (s (s (s zero))) {b} {c})},
+        ],
+        vec![data! {(le {x} (s (s zero)))}],
+        vec![data! {(lt (s zero) {x})}, data! {(add {x} {x} {y})}],
+    ];
+    assert_same(rules, &queries);
+}
+
+#[test]
+fn search() {
+    let rules = rules![
+        (color red)
+        (color green)
+        (color blue)
+        (nat z)
+        (nat (s {x})) {
+            (nat {x})
+        }
+        (even z)
+        (even (s (s {x}))) {
+            (even {x})
+        }
+        (sel {x} (cons {x} {t}) {t})
+        (sel {x} (cons {h} {t}) (cons {h} {r})) {
+            (sel {x} {t} {r})
+        }
+        (perm nil nil)
+        (perm {l} (cons {x} {p})) {
+            (sel {x} {l} {r})
+            (perm {r} {p})
+        }
+        (two_perms {a} {b} {p} {q}) {
+            (par (perm {a} {p}) (perm {b} {q}))
+        }
+        (parent alice bob)
+        (parent bob carol)
+        (parent bob dave)
+        (grandparent {x} {z}) {
+            (parent {x} {y})
+            (parent {y} {z})
+        }
+        (ancestor {x} {y}) {
+            (parent {x} {y})
+        }
+        (ancestor {x} {z}) {
+            (parent {x} {y})
+            (ancestor {y} {z})
+        }
+    ];
+    let queries = [
+        vec![data! {(color {c})}],
+        vec![data! {(nat (s (s z)))}, data! {(even (s (s (s (s z)))))}],
+        vec![data! {(perm [a b c] {p})}],
+        vec![data! {(two_perms [a b c] [d e] {p} {q})}],
+        vec![data! {(par (ancestor alice {z}) (ancestor bob {z}))}],
+        vec![data! {(grandparent {Who} dave)}],
+        vec![data! {(grandparent alice {Grandchild})}],
+        vec![data! {(ancestor alice {Who})}],
+        vec![data! {(ancestor alice carol)}],
+    ];
+    assert_same(rules, &queries);
+}
+
+#[test]
+fn calls_through_variables() {
+    let rules = rules![
+        (color red)
+        (color green)
+        (pair {x} {y})
+        (call {g}) {
+            {g}
+        }
+        (apply {f} {x}) {
+            ({f} {x})
+        }
+        (eq {x} {x})
+    ];
+    let queries = [
+        vec![data! {{g}}],
+        vec![data! {({f} {x})}],
+        vec![data! {({f} {x} {y})}, data! {(eq {f} pair)}],
+        vec![data! {(call (color {c}))}],
+        vec![data! {(call (par (color {x}) (color {y}) (pair {x} {y})))}],
+        vec![data! {(apply color {c})}],
+        vec![data! {(call (unify_with_occurs_check {x} (f {y})))}],
+        vec![data! {(call cut)}],
+        vec![data! {(call ())}],
+        vec![data! {(unify_with_occurs_check {x} (f {x}))}],
+    ];
+    for query in &queries[..7] {
+        assert_same(rules.clone(), std::slice::from_ref(query));
+    }
+    // These have no answers.
+    for query in &queries[7..] {
+        let world = World::with_mode(rules.clone(), Mode::Compiled);
+        assert_eq!(answers(&world, query), Ok(vec![]));
+    }
+}
+
+#[test]
+fn zebra() {
+    let rules = rules![
+        (eq {x} {x})
+        (member {x} (cons {x} {}))
+        (member {x} (cons {} {xs})) {
+            (member {x} {xs})
+        }
+        (nextto {x} {y} {list}) {
+            (iright {x} {y} {list})
+        }
+        (nextto {x} {y} {list}) {
+            (iright {y} {x} {list})
+        }
+        (iright {left} {right} (cons {left} (cons {right} {})))
+        (iright {left} {right} (cons {} {rest})) {
+            (iright {left} {right} {rest})
+        }
+        (zebra {h} {w} {z}) {
+            (eq {h} [
+                (house norwegian {} {} {} {})
+                {}
+                (house {} {} {} milk {})
+                {}
+                {}
+            ])
+            (member (house englishman {} {} {} red) {h})
+            (member (house spaniard dog {} {} {}) {h})
+            (member (house {} {} {} coffee green) {h})
+            (member (house ukrainian {} {} tea {}) {h})
+            (iright (house {} {} {} {} ivory)
+                    (house {} {} {} {} green) {h})
+            (member (house {} snails winston {} {}) {h})
+            (member (house {} {} kools {} yellow) {h})
+            (nextto (house {} {} chesterfield {} {})
+                    (house {} fox {} {} {}) {h})
+            (nextto (house {} {} kools {} {})
+                    (house {} horse {} {} {}) {h})
+            (member (house {} {} luckystrike oj {}) {h})
+            (member (house japanese {} parliaments {} {}) {h})
+            (nextto (house norwegian {} {} {} {})
+                    (house {} {} {} {} blue) {h})
+            (member (house {w} {} {} water {}) {h})
+            (member (house {z} zebra {} {} {}) {h})
+        }
+    ];
+    assert_same(rules, &[vec![data! {(zebra {h} {w} {z})}]]);
+}
+
+#[test]
+fn prepared_queries() {
+    let rules = rules![
+        (add zero {y} {y})
+        (add (s {x}) {y} (s {z})) {
+            (add {x} {y} {z})
+        }
+    ];
+    let interpreted = World::with_mode(rules.clone(), Mode::Interpreted);
+    let compiled = World::with_mode(rules, Mode::Compiled);
+    let params = [
+        vec![("x", data! {(s zero)}), ("y", data! {(s {n})})],
+        vec![("z", data! {(s (s zero))})],
+        vec![("x", data! {{a}}), ("z", data! {(s {b})})],
+    ];
+    for params in &params {
+        let mut expected = vec![];
+        let query = interpreted.prepare(&[data! {(add {x} {y} {z})}]);
+        query
+            .run(params, |c| expected.push(format!("{:?}", c)))
+            .unwrap();
+        let mut answers = vec![];
+        let query = compiled.prepare(&[data! {(add {x} {y} {z})}]);
+        query
+            .run(params, |c| answers.push(format!("{:?}", c)))
+            .unwrap();
+        assert!(!expected.is_empty());
+        assert_eq!(answers, expected, "{:?}", params);
+    }
+}
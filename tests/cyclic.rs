@@ -0,0 +1,96 @@
+#[macro_use]
+extern crate prlg;
+
+use std::cmp::Ordering;
+
+use prlg::{
+    bindings::{Bindings, Instance},
+    data::Data,
+    world::Mode,
+    World,
+};
+
+fn answers(world: &World, query: &[prlg::user_data::UserData]) -> Vec<String> {
+    let mut answers = vec![];
+    world
+        .run(query, |c| {
+            answers.push(
+                c.iter()
+                    .map(|d| d.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            )
+        })
+        .unwrap();
+    answers
+}
+
+#[test]
+fn unifies_two_cyclic_terms() {
+    for mode in [Mode::Interpreted, Mode::Compiled] {
+        let world = World::with_mode(rules![(eq {x} {x})], mode);
+        let query = [
+            data! {(eq {x} (f {x}))},
+            data! {(eq {y} (f (f {y})))},
+            data! {(eq {x} {y})},
+        ];
+        assert_eq!(answers(&world, &query).len(), 1);
+        // Unfoldings that differ somewhere do not unify.
+        let query = [
+            data! {(eq {x} (f a {x}))},
+            data! {(eq {y} (f a (f b {y})))},
+            data! {(eq {x} {y})},
+        ];
+        assert!(answers(&world, &query).is_empty());
+    }
+}
+
+#[test]
+fn prints_cyclic_answers() {
+    let world = World::new(rules![(eq {x} {x})]);
+    assert_eq!(
+        answers(&world, &[data! {(eq {x} (f {x}))}]),
+        ["(@ (eq {2} (f {2})) [(= {2} (f {2}))])"]
+    );
+}
+
+/// Two variables of a new store, bound to the last two of `terms`.
+fn cyclic_pair<'a>(
+    bindings: &mut Bindings<'a>,
+    terms: &'a [Data; 4],
+) -> (Instance<'a>, Instance<'a>) {
+    bindings.push(2);
+    let x = bindings.instance(&terms[0]);
+    let y = bindings.instance(&terms[1]);
+    assert!(bindings.unify(x, bindings.instance(&terms[2])));
+    assert!(bindings.unify(y, bindings.instance(&terms[3])));
+    (x, y)
+}
+
+#[test]
+fn compares_cyclic_terms() {
+    let f = |arg: Data| Data::Term(vec![Data::Symbol(prlg::Atom::new("f")), arg].into());
+    let g = |arg: Data| Data::Term(vec![Data::Symbol(prlg::Atom::new("g")), arg].into());
+
+    let terms = [
+        Data::Variable(0),
+        Data::Variable(1),
+        f(Data::Variable(0)),
+        f(f(Data::Variable(1))),
+    ];
+    let mut bindings = Bindings::new();
+    let (x, y) = cyclic_pair(&mut bindings, &terms);
+    assert_eq!(bindings.compare(x, y), Ordering::Equal);
+    assert_eq!(bindings.compare(y, x), Ordering::Equal);
+
+    let terms = [
+        Data::Variable(0),
+        Data::Variable(1),
+        f(Data::Variable(0)),
+        f(g(Data::Variable(1))),
+    ];
+    let mut bindings = Bindings::new();
+    let (x, y) = cyclic_pair(&mut bindings, &terms);
+    assert_eq!(bindings.compare(x, y), Ordering::Less);
+    assert_eq!(bindings.compare(y, x), Ordering::Greater);
+}
@@ -0,0 +1,91 @@
+#[macro_use]
+extern crate prlg;
+
+use prlg::{dot, World};
+
+fn world() -> World {
+    let mut world = World::new(rules![
+        (grandparent {x} {z}) {
+            (parent {x} {y})
+            (parent {y} {z})
+        }
+        (related {x} {y}) {
+            (par (grandparent {x} {z}) (grandparent {y} {z}))
+            (sibling {x} {y})
+        }
+        (older {x} {y}) {
+            (age {x} {a})
+            cut
+        }
+    ]);
+    world
+        .load_facts(
+            "parent",
+            [["alice", "bob"], ["bob", "carol"], ["erin", "frank"]],
+        )
+        .unwrap();
+    world.register_foreign("age", 2, |_| async { vec![] });
+    world
+}
+
+const FULL: &str = r#"digraph search_tree {
+    node [shape=box];
+    n0 [label="(grandparent {0} {1})"];
+    n0 -> n1 [label="clause 0"];
+    n1 [label="(parent {0} {2}), (parent {2} {1})"];
+    n1 -> n2 [label="fact row 0"];
+    n1 -> n3 [label="fact row 1"];
+    n1 -> n4 [label="fact row 2"];
+    n2 [label="(parent bob {0})"];
+    n2 -> n5 [label="fact row 1"];
+    n5 [label="yes: (grandparent alice carol)", style=filled, fillcolor=palegreen];
+    n3 [label="(parent carol {0})", style=filled, fillcolor=lightpink];
+    n4 [label="(parent frank {0})", style=filled, fillcolor=lightpink];
+}
+"#;
+
+const CUT_OFF: &str = r#"digraph search_tree {
+    node [shape=box];
+    n0 [label="(grandparent {0} {1})"];
+    n0 -> n1 [label="clause 0"];
+    n1 [label="(parent {0} {2}), (parent {2} {1})"];
+    n1 -> n2 [label="fact row 0"];
+    n1 -> n3 [label="fact row 1"];
+    n1 -> n4 [label="fact row 2"];
+    n2 [label="(parent bob {0})", style=dashed];
+    n3 [label="(parent carol {0})", style=dashed];
+    n4 [label="(parent frank {0})", style=dashed];
+}
+"#;
+
+const GRAPH: &str = r#"digraph call_graph {
+    p0 [label="grandparent/2"];
+    p1 [label="related/2"];
+    p2 [label="older/2"];
+    p3 [label="parent/2", shape=cylinder];
+    p4 [label="age/2", shape=component];
+    p5 [label="sibling/2", style=dashed, color=red];
+    p0 -> p3;
+    p1 -> p0;
+    p1 -> p5;
+    p2 -> p4;
+}
+"#;
+
+#[test]
+fn search_tree() {
+    let tree = dot::search_tree(&world(), &[data! {(grandparent {x} {who})}], 100).unwrap();
+    assert_eq!(tree, FULL);
+}
+
+#[test]
+fn search_tree_stops_after_max_steps() {
+    let tree = dot::search_tree(&world(), &[data! {(grandparent {x} {who})}], 2).unwrap();
+    assert_eq!(tree, CUT_OFF);
+}
+
+#[test]
+fn call_graph() {
+    let graph = dot::call_graph(&world());
+    assert_eq!(graph, GRAPH);
+}
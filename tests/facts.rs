@@ -0,0 +1,52 @@
+#[macro_use]
+extern crate prlg;
+
+use prlg::World;
+
+fn answers(world: &World, goal: prlg::user_data::UserData) -> Vec<String> {
+    let mut out = vec![];
+    world
+        .run(&[goal], |c| {
+            out.push(
+                c.iter()
+                    .map(|d| d.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            )
+        })
+        .unwrap();
+    out
+}
+
+#[test]
+fn zero_arity_facts_match() {
+    let mut world = World::new(rules![]);
+    let rows: [[&str; 0]; 1] = [[]];
+    assert_eq!(world.load_facts("p", rows).unwrap(), 1);
+    assert_eq!(answers(&world, data! {(p)}).len(), 1);
+}
+
+#[test]
+fn arity_mismatch_loads_nothing() {
+    let mut world = World::new(rules![]);
+    world.load_facts("q", [vec!["a", "b"]]).unwrap();
+    let rows = [vec!["c", "d"], vec!["e"], vec!["f", "g"]];
+    assert!(world.load_facts("q", rows).is_err());
+    assert_eq!(answers(&world, data! {(q {x} {y})}), ["(q a b)"]);
+
+    let rows = [vec!["h"], vec!["i", "j"]];
+    assert!(world.load_facts("r", rows).is_err());
+    assert!(answers(&world, data! {(r {x})}).is_empty());
+}
+
+#[test]
+fn csv_quoting() {
+    let path = std::env::temp_dir().join(format!("prlg-csv-{}.csv", std::process::id()));
+    std::fs::write(&path, "a,\"b,c\",\"say \"\"hi\"\"\"\r\n\nd,,\"\"\n").unwrap();
+    let mut world = World::new(rules![]);
+    let loaded = world.load_csv("t", &path);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.unwrap(), 2);
+    let rows = answers(&world, data! {(t {x} {y} {z})});
+    assert_eq!(rows, ["(t a b,c say \"hi\")", "(t d  )"]);
+}
@@ -0,0 +1,156 @@
+#[macro_use]
+extern crate prlg;
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll, Wake, Waker},
+    thread,
+    time::Duration,
+};
+
+use prlg::{data::Data, foreign::block_on, user_data::UserData, Atom, World};
+
+/// Ready once a thread started on the first poll has slept for a moment and woken it.
+struct Delay(Option<Arc<AtomicBool>>);
+
+impl Future for Delay {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        match &self.0 {
+            Some(done) if done.load(Ordering::SeqCst) => Poll::Ready(()),
+            Some(_) => Poll::Pending,
+            None => {
+                let done = Arc::new(AtomicBool::new(false));
+                let (flag, waker) = (done.clone(), cx.waker().clone());
+                thread::spawn(move || {
+                    thread::sleep(Duration::from_millis(10));
+                    flag.store(true, Ordering::SeqCst);
+                    waker.wake();
+                });
+                self.0 = Some(done);
+                Poll::Pending
+            }
+        }
+    }
+}
+
+fn rows(rows: &[[&str; 2]]) -> Vec<Vec<Atom>> {
+    let row = |row: &[&str; 2]| row.iter().map(|s| Atom::new(s)).collect();
+    rows.iter().map(row).collect()
+}
+
+fn world() -> World {
+    let mut world = World::new(rules![
+        (kind ignored clause)
+        (vowel {x}) {
+            (kind {x} vowel)
+        }
+    ]);
+    world.register_foreign("kind", 2, |args: Vec<Data>| async move {
+        let all = [["a", "vowel"], ["b", "consonant"], ["e", "vowel"]];
+        let first = args[0].as_symbol().map(|s| s.name());
+        let matching: Vec<_> = all
+            .into_iter()
+            .filter(|row| first.as_deref().is_none_or(|f| f == row[0]))
+            .collect();
+        rows(&matching)
+    });
+    world.register_foreign("slow", 1, |_| async {
+        Delay(None).await;
+        vec![vec![Atom::new("done")]]
+    });
+    world
+}
+
+fn answers(world: &World, query: &[UserData]) -> Vec<String> {
+    let mut answers = vec![];
+    world
+        .run(query, |c| answers.push(c[0].to_string()))
+        .unwrap();
+    answers
+}
+
+#[test]
+fn foreign_rows_are_alternatives() {
+    let world = world();
+    // Clauses of a foreign predicate are not tried.
+    assert_eq!(
+        answers(&world, &[data! {(kind {x} {k})}]),
+        ["(kind a vowel)", "(kind b consonant)", "(kind e vowel)"]
+    );
+    // The foreign function sees arguments as bound so far.
+    assert_eq!(
+        answers(&world, &[data! {(kind b {k})}]),
+        ["(kind b consonant)"]
+    );
+    assert_eq!(
+        answers(&world, &[data! {(vowel {x})}]),
+        ["(vowel a)", "(vowel e)"]
+    );
+    assert!(answers(&world, &[data! {(kind c {k})}]).is_empty());
+}
+
+#[test]
+fn blocking_runs_await_foreign_futures() {
+    let world = world();
+    assert_eq!(answers(&world, &[data! {(slow {x})}]), ["(slow done)"]);
+}
+
+#[derive(Default)]
+struct CountWakes(AtomicUsize);
+
+impl Wake for CountWakes {
+    fn wake(self: Arc<Self>) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn streams_yield_while_a_foreign_call_is_pending() {
+    let world = world();
+    let mut stream = world.stream(&[data! {(slow {x})}], 1000);
+    let wakes = Arc::new(CountWakes::default());
+    let waker = Waker::from(wakes.clone());
+    let mut cx = Context::from_waker(&waker);
+    assert!(Pin::new(&mut stream).poll_next(&mut cx).is_pending());
+    // Only the foreign future wakes the task, once it is ready.
+    while wakes.0.load(Ordering::SeqCst) == 0 {
+        thread::sleep(Duration::from_millis(1));
+    }
+    match Pin::new(&mut stream).poll_next(&mut cx) {
+        Poll::Ready(Some(answer)) => assert_eq!(answer.unwrap()[0].to_string(), "(slow done)"),
+        _ => panic!("no answer after the wake"),
+    }
+    assert!(matches!(
+        Pin::new(&mut stream).poll_next(&mut cx),
+        Poll::Ready(None)
+    ));
+}
+
+#[test]
+fn streams_move_to_other_threads() {
+    let world = world();
+    let mut stream = world.stream(&[data! {(vowel {x})}, data! {(slow {y})}], 1);
+    let answers = thread::scope(|s| {
+        s.spawn(move || {
+            let mut answers = vec![];
+            block_on(async {
+                while let Some(answer) = stream.next_answer().await {
+                    let answer: Vec<_> = answer.unwrap().iter().map(|d| d.to_string()).collect();
+                    answers.push(answer.join(" "));
+                }
+            });
+            answers
+        })
+        .join()
+        .unwrap()
+    });
+    // Answers list the goals last first.
+    assert_eq!(answers, ["(slow done) (vowel a)", "(slow done) (vowel e)"]);
+}
@@ -0,0 +1,191 @@
+#[macro_use]
+extern crate prlg;
+
+use std::{thread, time::Duration};
+
+use prlg::{
+    limits::{CancelToken, Limit, Limits},
+    runtime::Error,
+    strategy::SearchStrategy,
+    user_data::UserData,
+    world::Mode,
+    World,
+};
+
+fn world(mode: Mode) -> World {
+    World::with_mode(
+        rules![
+            (color red)
+            (color green)
+            (color blue)
+            (loop) {
+                (loop)
+            }
+            (wide) {
+                (wide)
+                (wide)
+            }
+            (count {n}) {
+                (count (s {n}))
+            }
+            (chain {l}) {
+                (chain (c {x} {l}))
+            }
+        ],
+        mode,
+    )
+}
+
+/// Each world to run in: both modes depth-first, and breadth-first.
+fn worlds() -> Vec<World> {
+    let mut breadth_first = world(Mode::Interpreted);
+    breadth_first.strategy = SearchStrategy::BreadthFirst;
+    vec![
+        world(Mode::Interpreted),
+        world(Mode::Compiled),
+        breadth_first,
+    ]
+}
+
+fn run(world: &World, query: &[UserData], limits: Limits) -> (usize, Result<(), Error>) {
+    let mut answers = 0;
+    let result = world.run_limited(query, &limits, |_| answers += 1);
+    (answers, result)
+}
+
+#[test]
+fn inferences() {
+    let limits = Limits {
+        inferences: Some(100),
+        ..Default::default()
+    };
+    for world in worlds() {
+        let (_, result) = run(&world, &[data! {(count z)}], limits.clone());
+        assert!(matches!(
+            result,
+            Err(Error::LimitExceeded(Limit::Inferences))
+        ));
+        let (answers, result) = run(&world, &[data! {(color {c})}], limits.clone());
+        assert_eq!((answers, result.is_ok()), (3, true));
+    }
+}
+
+#[test]
+fn depth() {
+    let limits = Limits {
+        depth: Some(50),
+        ..Default::default()
+    };
+    for world in worlds() {
+        let (_, result) = run(&world, &[data! {(wide)}], limits.clone());
+        assert!(matches!(result, Err(Error::LimitExceeded(Limit::Depth))));
+        // The goal list of `count` does not grow.
+        let limits = Limits {
+            inferences: Some(1000),
+            ..limits.clone()
+        };
+        let (_, result) = run(&world, &[data! {(count z)}], limits);
+        assert!(matches!(
+            result,
+            Err(Error::LimitExceeded(Limit::Inferences))
+        ));
+    }
+}
+
+#[test]
+fn bindings() {
+    let limits = Limits {
+        bindings: Some(500),
+        ..Default::default()
+    };
+    for world in worlds() {
+        let (_, result) = run(&world, &[data! {(chain nil)}], limits.clone());
+        assert!(matches!(result, Err(Error::LimitExceeded(Limit::Bindings))));
+        let (answers, result) = run(&world, &[data! {(color {c})}], limits.clone());
+        assert_eq!((answers, result.is_ok()), (3, true));
+    }
+}
+
+#[test]
+fn answers() {
+    let limits = |answers| Limits {
+        answers: Some(answers),
+        ..Default::default()
+    };
+    for world in worlds() {
+        let query = [data! {(color {c})}];
+        let (answers, result) = run(&world, &query, limits(2));
+        assert!(matches!(result, Err(Error::LimitExceeded(Limit::Answers))));
+        assert_eq!(answers, 2);
+        // The limit is only reported if there is another answer.
+        let (answers, result) = run(&world, &query, limits(3));
+        assert_eq!((answers, result.is_ok()), (3, true));
+        let (answers, result) = run(&world, &[data! {(color black)}], limits(0));
+        assert_eq!((answers, result.is_ok()), (0, true));
+        let (answers, result) = run(&world, &query, limits(0));
+        assert!(matches!(result, Err(Error::LimitExceeded(Limit::Answers))));
+        assert_eq!(answers, 0);
+    }
+}
+
+/// An answer held back by the limit is kept in a snapshot taken after it.
+#[test]
+fn snapshot_after_the_answers_limit() {
+    let world = world(Mode::Interpreted);
+    let limits = Limits {
+        answers: Some(1),
+        ..Default::default()
+    };
+    let query = prlg::snapshot::Snapshot::query(&[data! {(color {c})}]);
+    let mut rt = query.resume(&world, &limits).unwrap();
+    assert_eq!(
+        rt.next(&world).unwrap().unwrap()[0].to_string(),
+        "(color red)"
+    );
+    assert!(matches!(
+        rt.next(&world),
+        Err(Error::LimitExceeded(Limit::Answers))
+    ));
+    let snapshot = rt.snapshot().unwrap();
+    let mut rt = snapshot.resume(&world, &Limits::default()).unwrap();
+    let mut rest = vec![];
+    while let Some(answer) = rt.next(&world).unwrap() {
+        rest.push(answer[0].to_string());
+    }
+    assert_eq!(rest, ["(color green)", "(color blue)"]);
+}
+
+#[test]
+fn cancel_from_another_thread() {
+    for world in worlds() {
+        let token = CancelToken::new();
+        let limits = Limits {
+            cancel: Some(token.clone()),
+            ..Default::default()
+        };
+        let canceller = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            token.cancel();
+        });
+        let (_, result) = run(&world, &[data! {(loop)}], limits);
+        assert!(matches!(result, Err(Error::Cancelled)));
+        canceller.join().unwrap();
+        // The world still runs queries.
+        let (answers, result) = run(&world, &[data! {(color {c})}], Limits::default());
+        assert_eq!((answers, result.is_ok()), (3, true));
+    }
+}
+
+#[test]
+fn timeout() {
+    let limits = Limits {
+        timeout: Some(Duration::from_millis(20)),
+        ..Default::default()
+    };
+    for world in worlds() {
+        let (_, result) = run(&world, &[data! {(loop)}], limits.clone());
+        assert!(matches!(result, Err(Error::TimedOut)));
+        let (answers, result) = run(&world, &[data! {(color {c})}], limits.clone());
+        assert_eq!((answers, result.is_ok()), (3, true));
+    }
+}
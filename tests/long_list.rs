@@ -0,0 +1,90 @@
+#[macro_use]
+extern crate prlg;
+
+use prlg::{
+    bindings::{Bindings, Instance},
+    data::Data,
+    Atom, World,
+};
+
+const LEN: usize = 1 << 20;
+
+/// Runs `f` on a thread with a stack far too small to recurse over a long list.
+fn small_stack(f: impl FnOnce() + Send + 'static) {
+    std::thread::Builder::new()
+        .stack_size(256 * 1024)
+        .spawn(f)
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+/// `[a a ... a | tail]` with `len` elements.
+fn list(len: usize, tail: Data) -> Data {
+    (0..len).fold(tail, |tail, _| {
+        Data::Term([Data::Symbol(Atom::CONS), Data::Symbol(Atom::new("a")), tail].into())
+    })
+}
+
+#[test]
+fn bindings_long_list() {
+    small_stack(|| {
+        let open = list(LEN, Data::Variable(0));
+        let closed = list(LEN + 1, Data::Symbol(Atom::NIL));
+        assert_eq!(open.max_var(), 1);
+        assert_eq!(closed.max_var(), 0);
+
+        let mut bindings = Bindings::new();
+        bindings.alloc(open.max_var());
+        assert!(bindings.unify(Instance::new(&open, 0), Instance::new(&closed, 0)));
+        let copy = bindings.data(Instance::new(&open, 0));
+        assert_eq!(copy.max_var(), 0);
+        assert_eq!(copy.to_string(), closed.to_string());
+    });
+}
+
+#[test]
+fn append_long_list() {
+    small_stack(|| {
+        let world = World::new(rules![
+            (append nil {xs} {xs})
+            (append [{x} . {xs}] {ys} [{x} . {zs}]) {
+                (append {xs} {ys} {zs})
+            }
+
+            (double {xs} {ys}) {
+                (append {xs} {xs} {ys})
+            }
+
+            (eq {x} {x})
+
+            (long_list {l}) {
+                (double [a] {l1}) (double {l1} {l2}) (double {l2} {l3}) (double {l3} {l4})
+                (double {l4} {l5}) (double {l5} {l6}) (double {l6} {l7}) (double {l7} {l8})
+                (double {l8} {l9}) (double {l9} {l10}) (double {l10} {l11}) (double {l11} {l12})
+                (double {l12} {l13}) (double {l13} {l14}) (double {l14} {l15}) (double {l15} {l16})
+                (double {l16} {l17}) (double {l17} {l18}) (double {l18} {l19}) (double {l19} {l20})
+                (eq {l20} {l})
+            }
+        ]);
+        let goals = [
+            data! {(long_list {l})},
+            data! {(long_list {m})},
+            data! {(eq {l} {m})},
+            data! {(append {l} [{t}] {r})},
+        ];
+        let mut count = 0;
+        world
+            .run(&goals, |c| {
+                let l = list(LEN, Data::Symbol(Atom::NIL));
+                assert_eq!(c[3].to_string(), format!("(long_list {})", l));
+                assert_eq!(c[3].max_var(), 0);
+                // `(append L [T] R)`, with `T`, the third query variable, unbound in both lists.
+                assert_eq!(c[0].max_var(), 3);
+                assert_eq!(c[0].to_string().matches('a').count(), 2 * LEN + 1);
+                count += 1;
+            })
+            .unwrap();
+        assert_eq!(count, 1);
+    });
+}
@@ -0,0 +1,34 @@
+#[macro_use]
+extern crate prlg;
+
+use prlg::{
+    limits::{Limit, Limits},
+    runtime::Error,
+    world::Mode,
+    World,
+};
+
+/// A deterministic loop passing an unbound argument on runs in bounded bindings.
+#[test]
+fn tail_recursion_with_unbound_argument() {
+    for mode in [Mode::Interpreted, Mode::Compiled] {
+        let world = World::with_mode(
+            rules![
+                (loop {x}) {
+                    (loop {x})
+                }
+            ],
+            mode,
+        );
+        let limits = Limits {
+            inferences: Some(1_000_000),
+            bindings: Some(1 << 18),
+            ..Default::default()
+        };
+        let result = world.run_limited(&[data! {(loop {x})}], &limits, |_| {});
+        assert!(matches!(
+            result,
+            Err(Error::LimitExceeded(Limit::Inferences))
+        ));
+    }
+}
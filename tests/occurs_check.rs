@@ -0,0 +1,74 @@
+#[macro_use]
+extern crate prlg;
+
+use prlg::{bindings::OccursCheck, runtime::Error, user_data::UserData, world::Mode, World};
+
+fn new_world(mode: Mode, occurs_check: OccursCheck) -> World {
+    let mut world = World::with_mode(
+        rules![
+            (eq {x} {x})
+            (same {x} {x})
+        ],
+        mode,
+    );
+    world.occurs_check = occurs_check;
+    world
+}
+
+fn count(world: &World, query: &[UserData]) -> Result<usize, Error> {
+    let mut answers = 0;
+    world.run(query, |_| answers += 1)?;
+    Ok(answers)
+}
+
+#[test]
+fn modes() {
+    for mode in [Mode::Interpreted, Mode::Compiled] {
+        let cyclic = [data! {(eq {y} (f {y}))}];
+        let world = new_world(mode, OccursCheck::False);
+        assert_eq!(count(&world, &cyclic).unwrap(), 1);
+        let world = new_world(mode, OccursCheck::True);
+        assert_eq!(count(&world, &cyclic).unwrap(), 0);
+        assert_eq!(count(&world, &[data! {(eq {y} (f {z}))}]).unwrap(), 1);
+        let world = new_world(mode, OccursCheck::Error);
+        assert!(matches!(count(&world, &cyclic), Err(Error::OccursCheck(_))));
+    }
+}
+
+#[test]
+fn per_predicate() {
+    for mode in [Mode::Interpreted, Mode::Compiled] {
+        let mut world = new_world(mode, OccursCheck::True);
+        world.set_occurs_check("same", 2, OccursCheck::False);
+        assert_eq!(count(&world, &[data! {(eq {y} (f {y}))}]).unwrap(), 0);
+        assert_eq!(count(&world, &[data! {(same {y} (f {y}))}]).unwrap(), 1);
+
+        let mut world = new_world(mode, OccursCheck::False);
+        world.set_occurs_check("same", 2, OccursCheck::Error);
+        assert_eq!(count(&world, &[data! {(eq {y} (f {y}))}]).unwrap(), 1);
+        assert!(matches!(
+            count(&world, &[data! {(same {y} (f {y}))}]),
+            Err(Error::OccursCheck(_))
+        ));
+    }
+}
+
+#[test]
+fn unify_with_occurs_check() {
+    for mode in [Mode::Interpreted, Mode::Compiled] {
+        for occurs_check in [OccursCheck::False, OccursCheck::True, OccursCheck::Error] {
+            let world = new_world(mode, occurs_check);
+            let query = [data! {(unify_with_occurs_check {y} (f {y}))}];
+            assert_eq!(count(&world, &query).unwrap(), 0);
+            let query = [data! {(unify_with_occurs_check (g {y} b) (g (f {z}) {z}))}];
+            let mut answers = vec![];
+            world
+                .run(&query, |c| answers.push(c[0].to_string()))
+                .unwrap();
+            assert_eq!(
+                answers,
+                ["(unify_with_occurs_check (g (f b) b) (g (f b) b))"]
+            );
+        }
+    }
+}
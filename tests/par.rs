@@ -0,0 +1,114 @@
+#[macro_use]
+extern crate prlg;
+
+use prlg::{
+    limits::{Limit, Limits},
+    runtime::Error,
+    user_data::UserData,
+    World,
+};
+
+fn world() -> World {
+    World::new(rules![
+        (nat z)
+        (nat (s {x})) {
+            (nat {x})
+        }
+        (color red)
+        (color green)
+        (color blue)
+        (d n0) (d n1) (d n2) (d n3) (d n4) (d n5) (d n6) (d n7) (d n8) (d n9)
+        (pair {x} {y}) {
+            (d {x})
+            (d {y})
+        }
+    ])
+}
+
+fn run(world: &World, goals: &[UserData], limits: &Limits) -> (Vec<String>, Result<(), Error>) {
+    let mut answers = vec![];
+    let result = world.run_limited(goals, limits, |c| {
+        answers.push(
+            c.iter()
+                .rev()
+                .map(|d| d.to_string())
+                .collect::<Vec<_>>()
+                .join(" "),
+        )
+    });
+    (answers, result)
+}
+
+fn first_two() -> Limits {
+    Limits {
+        answers: Some(2),
+        inferences: Some(100_000),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn first_conjunct_with_infinitely_many_answers() {
+    let (answers, result) = run(
+        &world(),
+        &[data! {(par (nat {x}) (color {y}))}],
+        &first_two(),
+    );
+    assert!(matches!(result, Err(Error::LimitExceeded(Limit::Answers))));
+    assert_eq!(
+        answers,
+        ["(par (nat z) (color red))", "(par (nat z) (color green))"]
+    );
+}
+
+#[test]
+fn later_conjunct_with_infinitely_many_answers() {
+    let (answers, result) = run(
+        &world(),
+        &[data! {(par (color {y}) (nat {x}))}],
+        &first_two(),
+    );
+    assert!(matches!(result, Err(Error::LimitExceeded(Limit::Answers))));
+    assert_eq!(
+        answers,
+        ["(par (color red) (nat z))", "(par (color red) (nat (s z)))"]
+    );
+}
+
+#[test]
+fn joins_in_sequential_order() {
+    let world = world();
+    let limits = Limits::default();
+    let (joined, result) = run(&world, &[data! {(par (pair {a} {b}) (color {c}))}], &limits);
+    result.unwrap();
+    let (sequential, result) = run(
+        &world,
+        &[data! {(pair {a} {b})}, data! {(color {c})}],
+        &limits,
+    );
+    result.unwrap();
+    // More answers than a batch of the first conjunct.
+    assert_eq!(joined.len(), 300);
+    let sequential: Vec<_> = sequential.iter().map(|a| format!("(par {a})")).collect();
+    assert_eq!(joined, sequential);
+}
+
+#[test]
+fn conjunct_inferences_count_towards_the_query() {
+    let mut n = data! {z};
+    for _ in 0..60 {
+        n = term![sym!(s), n];
+    }
+    let goal = term![sym!(par), term![sym!(nat), n.clone()], term![sym!(nat), n]];
+    let limits = |inferences| Limits {
+        inferences: Some(inferences),
+        ..Default::default()
+    };
+    let (_, result) = run(&world(), std::slice::from_ref(&goal), &limits(150));
+    result.unwrap();
+    let (_, result) = run(&world(), &[goal], &limits(100));
+    assert!(matches!(
+        result,
+        Err(Error::LimitExceeded(Limit::Inferences))
+    ));
+}
@@ -0,0 +1,137 @@
+#[macro_use]
+extern crate prlg;
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use prlg::{bindings::OccursCheck, parallel::Parallel, runtime::Error, user_data::UserData, World};
+
+/// The world, and how many times `tick` was called.
+fn new_world() -> (Arc<World>, Arc<AtomicUsize>) {
+    let mut world = World::new(rules![
+        (nat z)
+        (nat (s {x})) {
+            (nat {x})
+        }
+        (color red)
+        (color green)
+        (color blue)
+        (d n0) (d n1) (d n2) (d n3) (d n4) (d n5) (d n6) (d n7) (d n8) (d n9)
+        (pair {x} {y}) {
+            (d {x})
+            (d {y})
+        }
+        (eq {x} {x})
+    ]);
+    world.occurs_check = OccursCheck::Error;
+    let ticks = Arc::new(AtomicUsize::new(0));
+    let counter = ticks.clone();
+    world.register_foreign("tick", 0, move |_| {
+        counter.fetch_add(1, Ordering::SeqCst);
+        async { vec![vec![]] }
+    });
+    (Arc::new(world), ticks)
+}
+
+fn options(ordered: bool) -> Parallel {
+    Parallel {
+        threads: 4,
+        ordered,
+    }
+}
+
+fn sequential(world: &World, query: &[UserData]) -> Vec<String> {
+    let mut answers = vec![];
+    world
+        .run(query, |c| answers.push(format!("{:?}", c)))
+        .unwrap();
+    answers
+}
+
+fn parallel(world: &Arc<World>, query: &[UserData], ordered: bool) -> Vec<String> {
+    world
+        .run_parallel(query, options(ordered))
+        .map(|c| format!("{:?}", c.unwrap()))
+        .collect()
+}
+
+/// 30,000 answers.
+fn query() -> Vec<UserData> {
+    vec![
+        data! {(pair {a} {b})},
+        data! {(color {c})},
+        data! {(pair {e} {f})},
+    ]
+}
+
+#[test]
+fn ordered_answers_are_sequential() {
+    let (world, _) = new_world();
+    let expected = sequential(&world, &query());
+    assert_eq!(expected.len(), 30_000);
+    assert_eq!(parallel(&world, &query(), true), expected);
+}
+
+#[test]
+fn unordered_answers_match_as_a_set() {
+    let (world, _) = new_world();
+    let mut expected = sequential(&world, &query());
+    let mut answers = parallel(&world, &query(), false);
+    expected.sort();
+    answers.sort();
+    assert_eq!(answers, expected);
+}
+
+/// Waits for `ticks` to settle, failing if it does not.
+fn assert_stops(ticks: &AtomicUsize) {
+    thread::sleep(Duration::from_millis(50));
+    let stopped = ticks.load(Ordering::SeqCst);
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(ticks.load(Ordering::SeqCst), stopped);
+}
+
+#[test]
+fn dropping_the_answers_stops_the_search() {
+    for ordered in [false, true] {
+        let (world, ticks) = new_world();
+        // Infinitely many answers.
+        let mut answers =
+            world.run_parallel(&[data! {(nat {x})}, data! {(tick)}], options(ordered));
+        if !ordered {
+            assert!(answers.next().unwrap().is_ok());
+        }
+        thread::sleep(Duration::from_millis(20));
+        drop(answers);
+        assert_stops(&ticks);
+
+        // A search that never finds an answer.
+        let (world, ticks) = new_world();
+        let query = [data! {(nat {x})}, data! {(tick)}, data! {(color black)}];
+        let answers = world.run_parallel(&query, options(ordered));
+        while ticks.load(Ordering::SeqCst) == 0 {
+            thread::yield_now();
+        }
+        drop(answers);
+        assert_stops(&ticks);
+    }
+}
+
+#[test]
+fn errors_end_the_answers() {
+    for ordered in [false, true] {
+        let (world, _) = new_world();
+        let query = [data! {(color {c})}, data! {(eq {y} (f {y}))}];
+        let answers: Vec<_> = world.run_parallel(&query, options(ordered)).collect();
+        // Workers that failed at the same time may each report it.
+        assert!(!answers.is_empty());
+        assert!(answers
+            .iter()
+            .all(|a| matches!(a, Err(Error::OccursCheck(_)))));
+    }
+}
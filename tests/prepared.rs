@@ -0,0 +1,98 @@
+#[macro_use]
+extern crate prlg;
+
+use prlg::{
+    data::Data, limits::Limits, prepared_query::PreparedQuery, runtime::Error,
+    strategy::SearchStrategy, user_data::UserData, world::VariableScope, Atom, World,
+};
+
+fn world() -> World {
+    World::new(rules![
+        (add zero {y} {y})
+        (add (s {x}) {y} (s {z})) {
+            (add {x} {y} {z})
+        }
+    ])
+}
+
+fn answers(query: &PreparedQuery, params: &[(&str, UserData)]) -> Vec<String> {
+    let mut answers = vec![];
+    query
+        .run(params, |c| answers.push(c[0].to_string()))
+        .unwrap();
+    answers
+}
+
+#[test]
+fn converted_values_give_the_same_answers() {
+    let world = world();
+    let query = world.prepare(&[data! {(add {x} {y} {z})}]);
+    let by_name = answers(&query, &[("x", data! {(s zero)}), ("y", data! {zero})]);
+    assert_eq!(by_name, ["(add (s zero) zero (s zero))"]);
+
+    let (x, y) = (query.param("x").unwrap(), query.param("y").unwrap());
+    let s = Data::Symbol(Atom::new("s"));
+    let zero = Data::Symbol(Atom::new("zero"));
+    let one = Data::Term(vec![s, zero.clone()].into());
+    let mut by_slot = vec![];
+    query
+        .run_with(&[(x, one), (y, zero)], &Limits::default(), |c| {
+            by_slot.push(c[0].to_string())
+        })
+        .unwrap();
+    assert_eq!(by_slot, by_name);
+}
+
+#[test]
+fn values_with_variables() {
+    let world = world();
+    let query = world.prepare(&[data! {(add {x} {y} {z})}]);
+    let mut scope = VariableScope::new();
+    let params = [
+        (query.param("x").unwrap(), scope.new_data(&data! {(s zero)})),
+        (query.param("z").unwrap(), scope.new_data(&data! {(s {n})})),
+    ];
+    let mut answers = vec![];
+    query
+        .run_with(&params, &Limits::default(), |c| {
+            answers.push(c[0].to_string())
+        })
+        .unwrap();
+    assert_eq!(answers.len(), 1);
+    // `y` and `n` end up the same variable.
+    let words: Vec<_> = answers[0]
+        .split([' ', '(', ')'])
+        .filter(|w| !w.is_empty())
+        .collect();
+    assert_eq!(words[..4], ["add", "s", "zero", words[3]]);
+    assert_eq!(words[5], words[3]);
+}
+
+#[test]
+fn variable_goal_bound_by_a_parameter() {
+    let mut world = world();
+    let query_answers = |world: &World| {
+        let query = world.prepare(&[data! {{g}}]);
+        answers(&query, &[("g", data! {(add (s zero) (s zero) {z})})])
+    };
+    assert_eq!(
+        query_answers(&world),
+        ["(add (s zero) (s zero) (s (s zero)))"]
+    );
+    world.strategy = SearchStrategy::BreadthFirst;
+    assert_eq!(
+        query_answers(&world),
+        ["(add (s zero) (s zero) (s (s zero)))"]
+    );
+}
+
+#[test]
+fn unknown_parameter() {
+    let world = world();
+    let query = world.prepare(&[data! {(add {x} {y} {z})}]);
+    assert!(query.param("w").is_none());
+    assert!(matches!(
+        query.run(&[("w", data! {zero})], |_| {}),
+        Err(Error::UnknownParameter(name)) if name == "w"
+    ));
+}
@@ -0,0 +1,93 @@
+#[macro_use]
+extern crate prlg;
+
+use prlg::{
+    profile::{Counts, Profile},
+    user_data::UserData,
+    World,
+};
+
+fn profile(world: &World, query: &[UserData]) -> Profile {
+    world.run_profiled(query, |_| {}).unwrap()
+}
+
+fn counts<'p>(profile: &'p Profile, name: &str, arity: usize) -> &'p Counts {
+    let p = profile.predicates.iter();
+    let mut p = p.filter(|p| &*p.name.name() == name && p.arity == arity);
+    &p.next().unwrap().counts
+}
+
+/// Every exit and failure ends a call or a retry of one.
+fn assert_consistent(profile: &Profile) {
+    let counts = profile
+        .predicates
+        .iter()
+        .map(|p| (format!("{}/{}", p.name.name(), p.arity), &p.counts))
+        .chain(
+            profile
+                .clauses
+                .iter()
+                .map(|c| (format!("clause {}", c.rule), &c.counts)),
+        );
+    for (name, c) in counts {
+        assert!(c.exits <= c.calls + c.redos, "{name}: {c:?}");
+        assert!(c.fails <= c.calls + c.redos, "{name}: {c:?}");
+        assert!(c.exclusive <= c.inclusive, "{name}: {c:?}");
+    }
+}
+
+#[test]
+fn nested_call() {
+    let world = World::new(rules![
+        (q a)
+        (q b)
+        (p {y}) {
+            (q {y})
+        }
+    ]);
+    let profile = profile(&world, &[data! {(p {x})}]);
+    assert_consistent(&profile);
+    let p = counts(&profile, "p", 1);
+    assert_eq!((p.calls, p.exits, p.redos, p.fails), (1, 2, 2, 1));
+    let q = counts(&profile, "q", 1);
+    assert_eq!((q.calls, q.exits, q.redos, q.fails), (1, 2, 2, 1));
+}
+
+#[test]
+fn permutations() {
+    let world = World::new(rules![
+        (sel {x} (cons {x} {t}) {t})
+        (sel {x} (cons {h} {t}) (cons {h} {r})) {
+            (sel {x} {t} {r})
+        }
+        (perm nil nil)
+        (perm {l} (cons {x} {p})) {
+            (sel {x} {l} {r})
+            (perm {r} {p})
+        }
+    ]);
+    let profile = profile(&world, &[data! {(perm [a b c d e] {p})}]);
+    assert_consistent(&profile);
+    let perm = counts(&profile, "perm", 2);
+    // Each call on a list of n elements exits n! times, each of the six lengths 120 times
+    // in all.
+    assert_eq!(perm.calls, 1 + 5 + 5 * 4 + 5 * 4 * 3 + 5 * 4 * 3 * 2 + 120);
+    assert_eq!(perm.exits, 6 * 120);
+}
+
+#[test]
+fn par_goal_resumes_its_call() {
+    let world = World::new(rules![
+        (q a)
+        (q b)
+        (r c)
+        (r d)
+        (p {x} {y}) {
+            (par (q {x}) (r {y}))
+        }
+    ]);
+    let profile = profile(&world, &[data! {(p {x} {y})}]);
+    assert_consistent(&profile);
+    let p = counts(&profile, "p", 2);
+    assert_eq!((p.calls, p.exits, p.redos, p.fails), (1, 4, 4, 1));
+}
@@ -0,0 +1,99 @@
+#[macro_use]
+extern crate prlg;
+
+use prlg::{proof::Proof, World};
+
+fn world() -> World {
+    let mut world = World::new(rules![
+        (parent alice bob)
+        (ancestor {x} {y}) {
+            (parent {x} {y})
+        }
+        (ancestor {x} {z}) {
+            (parent {x} {y})
+            (ancestor {y} {z})
+        }
+    ]);
+    world
+        .load_facts("parent", [["bob", "carol"], ["bob", "dave"]])
+        .unwrap();
+    world
+}
+
+/// Each node's goal and clause, depth first.
+fn shape(proof: &Proof) -> Vec<(usize, String, Option<usize>)> {
+    let mut nodes = vec![];
+    let mut stack = vec![(proof, 0)];
+    while let Some((proof, depth)) = stack.pop() {
+        nodes.push((depth, proof.goal.to_string(), proof.rule));
+        stack.extend(proof.children.iter().rev().map(|c| (c, depth + 1)));
+    }
+    nodes
+}
+
+#[test]
+fn proof_tree() {
+    let world = world();
+    let mut proofs = vec![];
+    world
+        .run_with_proofs(&[data! {(ancestor alice {who})}], |answer, p| {
+            assert_eq!(p.len(), 1);
+            proofs.push((
+                answer[0].to_string(),
+                shape(&p[0]),
+                p[0].to_term().to_string(),
+            ))
+        })
+        .unwrap();
+    let answers: Vec<_> = proofs.iter().map(|(a, _, _)| a.as_str()).collect();
+    assert_eq!(
+        answers,
+        [
+            "(ancestor alice bob)",
+            "(ancestor alice carol)",
+            "(ancestor alice dave)"
+        ]
+    );
+    assert_eq!(
+        proofs[0].1,
+        [
+            (0, "(ancestor alice bob)".to_string(), Some(1)),
+            (1, "(parent alice bob)".to_string(), Some(0)),
+        ]
+    );
+    // Fact rows have no clause.
+    assert_eq!(
+        proofs[2].1,
+        [
+            (0, "(ancestor alice dave)".to_string(), Some(2)),
+            (1, "(parent alice bob)".to_string(), Some(0)),
+            (1, "(ancestor bob dave)".to_string(), Some(1)),
+            (2, "(parent bob dave)".to_string(), None),
+        ]
+    );
+    assert_eq!(
+        proofs[1].2,
+        "(proof (ancestor alice carol) 2 [(= _0 alice) (= _1 carol) (= _2 bob)] \
+         [(proof (parent alice bob) 0 nil nil) \
+         (proof (ancestor bob carol) 1 [(= _0 bob) (= _1 carol)] \
+         [(proof (parent bob carol) none nil nil)])])"
+    );
+}
+
+#[test]
+fn display() {
+    let world = world();
+    let mut text = String::new();
+    world
+        .run_with_proofs(&[data! {(ancestor alice carol)}], |_, proofs| {
+            text = proofs[0].to_string()
+        })
+        .unwrap();
+    assert_eq!(
+        text,
+        "(ancestor alice carol)  [clause 2: _0 = alice, _1 = carol, _2 = bob]\n  \
+         (parent alice bob)  [clause 0]\n  \
+         (ancestor bob carol)  [clause 1: _0 = bob, _1 = carol]\n    \
+         (parent bob carol)\n"
+    );
+}
@@ -0,0 +1,15 @@
+use prlg::World;
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn world_is_send_and_sync() {
+    assert_send_sync::<World>();
+}
+
+fn assert_send<T: Send>() {}
+
+#[test]
+fn streams_are_send() {
+    assert_send::<prlg::stream::Solutions<'static>>();
+}
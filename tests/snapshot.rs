@@ -0,0 +1,144 @@
+#[macro_use]
+extern crate prlg;
+
+use prlg::{
+    limits::Limits,
+    snapshot::{InvalidSnapshot, Snapshot},
+    user_data::UserData,
+    World,
+};
+
+fn world() -> World {
+    let mut world = World::new(rules![
+        (sel {x} (cons {x} {t}) {t})
+        (sel {x} (cons {h} {t}) (cons {h} {r})) {
+            (sel {x} {t} {r})
+        }
+        (perm nil nil)
+        (perm {l} (cons {x} {p})) {
+            (sel {x} {l} {r})
+            (perm {r} {p})
+        }
+    ]);
+    world.load_facts("q", [["n0"], ["n1"], ["n2"]]).unwrap();
+    world
+}
+
+fn query() -> Vec<UserData> {
+    vec![data! {(perm [a b c] {p})}, data! {(q {y})}]
+}
+
+fn show(answer: &[prlg::data::Data]) -> String {
+    let parts: Vec<_> = answer.iter().rev().map(|d| d.to_string()).collect();
+    parts.join(" ")
+}
+
+/// Answers of the query run in sessions of a few inferences, going through bytes between
+/// sessions, along with the bytes saved after each session.
+fn in_sessions(world: &World, inferences: u64) -> (Vec<String>, Vec<Vec<u8>>) {
+    let limits = Limits {
+        inferences: Some(inferences),
+        ..Default::default()
+    };
+    let (mut answers, mut saved) = (vec![], vec![]);
+    let mut bytes = Snapshot::query(&query()).to_bytes();
+    loop {
+        let snapshot = Snapshot::from_bytes(&bytes).unwrap();
+        assert_eq!(snapshot.to_bytes(), bytes);
+        if snapshot.is_empty() {
+            return (answers, saved);
+        }
+        let mut rt = snapshot.resume(world, &limits).unwrap();
+        while let Ok(Some(answer)) = rt.next(world) {
+            answers.push(show(&answer));
+        }
+        bytes = rt.snapshot().unwrap().to_bytes();
+        saved.push(bytes.clone());
+    }
+}
+
+#[test]
+fn resumes_to_the_same_answers() {
+    let world = world();
+    let mut whole = vec![];
+    world.run(&query(), |c| whole.push(show(c))).unwrap();
+    assert_eq!(whole.len(), 18);
+    for inferences in [1, 3, 7, 40] {
+        let (answers, saved) = in_sessions(&world, inferences);
+        assert_eq!(answers, whole);
+        assert!(saved.len() > 1);
+    }
+}
+
+#[test]
+fn rejects_invalid_bytes() {
+    let world = world();
+    let (_, saved) = in_sessions(&world, 7);
+    let bytes = &saved[saved.len() / 2];
+    for len in 0..bytes.len() {
+        assert_eq!(
+            Snapshot::from_bytes(&bytes[..len]).err(),
+            Some(InvalidSnapshot)
+        );
+    }
+    let mut longer = bytes.clone();
+    longer.push(0);
+    assert!(Snapshot::from_bytes(&longer).is_err());
+
+    let version = b"prlg-snapshot".len();
+    for other in [0, 1, 3, 255] {
+        let mut other_version = bytes.clone();
+        other_version[version] = other;
+        assert!(Snapshot::from_bytes(&other_version).is_err());
+    }
+
+    // Whatever a corrupted byte turns into, reading it does not panic.
+    for i in 0..bytes.len() {
+        for value in [0, 1, 2, 0x7f, 0xff] {
+            let mut corrupted = bytes.clone();
+            corrupted[i] = value;
+            if let Ok(snapshot) = Snapshot::from_bytes(&corrupted) {
+                snapshot.to_bytes();
+            }
+        }
+    }
+}
+
+#[test]
+fn rejects_other_rules() {
+    let world = world();
+    let (_, saved) = in_sessions(&world, 7);
+    let fewer = World::new(rules![(perm nil nil)]);
+    let snapshots: Vec<_> = saved
+        .iter()
+        .map(|b| Snapshot::from_bytes(b).unwrap())
+        .collect();
+    assert!(snapshots
+        .iter()
+        .any(|s| s.resume(&fewer, &Limits::default()).is_err()));
+}
+
+#[test]
+fn rejects_rows_past_the_table() {
+    let rows = |n: usize| (0..n).map(|i| [format!("n{}", i)]).collect::<Vec<_>>();
+    let mut world = World::new(rules![(p {x}) { (q {x}) }]);
+    world.load_facts("q", rows(20)).unwrap();
+    let query = Snapshot::query(&[data! {(p {x})}]);
+    let mut rt = query.resume(&world, &Limits::default()).unwrap();
+    rt.next(&world).unwrap().unwrap();
+    let bytes = rt.snapshot().unwrap().to_bytes();
+
+    let mut fewer = World::new(rules![(p {x}) { (q {x}) }]);
+    fewer.load_facts("q", rows(3)).unwrap();
+    let snapshot = Snapshot::from_bytes(&bytes).unwrap();
+    assert_eq!(
+        snapshot.resume(&fewer, &Limits::default()).err(),
+        Some(InvalidSnapshot)
+    );
+    let mut rt = snapshot.resume(&world, &Limits::default()).unwrap();
+    let mut count = 0;
+    while rt.next(&world).unwrap().is_some() {
+        count += 1;
+    }
+    assert_eq!(count, 19);
+}
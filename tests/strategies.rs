@@ -0,0 +1,181 @@
+#[macro_use]
+extern crate prlg;
+
+use prlg::{
+    limits::{Limit, Limits},
+    runtime::Error,
+    strategy::SearchStrategy,
+    user_data::UserData,
+    World,
+};
+
+fn strategies() -> Vec<SearchStrategy> {
+    vec![
+        SearchStrategy::BreadthFirst,
+        SearchStrategy::IterativeDeepening { start: 2, step: 2 },
+        SearchStrategy::best_first(|goals| goals.len() as u64),
+        SearchStrategy::Interleaving { steps: 4 },
+    ]
+}
+
+/// Only depth-first search has choicepoints for cut to prune.
+#[test]
+fn cut_is_unsupported() {
+    let mut world = World::new(rules![
+        (first {x}) {
+            (color {x})
+            cut
+        }
+        (color red)
+        (color green)
+    ]);
+    let mut answers = vec![];
+    world
+        .run(&[data! {(first {x})}], |c| answers.push(c[0].to_string()))
+        .unwrap();
+    assert_eq!(answers, ["(first red)"]);
+    for strategy in strategies() {
+        world.strategy = strategy;
+        assert!(matches!(
+            world.run(&[data! {(first {x})}], |_| {}),
+            Err(Error::Unsupported("cut"))
+        ));
+        // Rules with cut that are not reached do not matter.
+        let mut colors = 0;
+        world.run(&[data! {(color {x})}], |_| colors += 1).unwrap();
+        assert_eq!(colors, 2);
+    }
+}
+
+fn run(world: &World, query: &[UserData], limits: &Limits) -> (Vec<String>, Result<(), Error>) {
+    let mut answers = vec![];
+    let result = world.run_limited(query, limits, |c| answers.push(c[0].to_string()));
+    (answers, result)
+}
+
+#[test]
+fn answers_behind_an_infinite_first_clause() {
+    let mut world = World::new(rules![
+        (p {x}) {
+            (loop)
+        }
+        (p found)
+        (loop) {
+            (loop)
+        }
+    ]);
+    let limits = Limits {
+        inferences: Some(10_000),
+        ..Default::default()
+    };
+    for strategy in [
+        SearchStrategy::BreadthFirst,
+        SearchStrategy::IterativeDeepening { start: 1, step: 1 },
+    ] {
+        world.strategy = strategy;
+        let (answers, result) = run(&world, &[data! {(p {x})}], &limits);
+        assert_eq!(answers, ["(p found)"]);
+        assert!(matches!(
+            result,
+            Err(Error::LimitExceeded(Limit::Inferences))
+        ));
+    }
+}
+
+#[test]
+fn iterative_deepening_reports_each_answer_once() {
+    let mut world = World::new(rules![
+        (sel {x} (cons {x} {t}) {t})
+        (sel {x} (cons {h} {t}) (cons {h} {r})) {
+            (sel {x} {t} {r})
+        }
+        (perm nil nil)
+        (perm {l} (cons {x} {p})) {
+            (sel {x} {l} {r})
+            (perm {r} {p})
+        }
+    ]);
+    let query = [data! {(perm [a b c d] {p})}];
+    let (mut whole, _) = run(&world, &query, &Limits::default());
+    assert_eq!(whole.len(), 24);
+    whole.sort();
+    for (start, step) in [(1, 1), (2, 3), (5, 1)] {
+        world.strategy = SearchStrategy::IterativeDeepening { start, step };
+        let (mut answers, result) = run(&world, &query, &Limits::default());
+        result.unwrap();
+        answers.sort();
+        assert_eq!(answers, whole);
+    }
+}
+
+#[test]
+fn best_first_takes_the_cheapest_resolvent() {
+    let mut world = World::new(rules![
+        (opt one) {
+            (w)
+            (w)
+            (w)
+        }
+        (opt two) {
+            (w)
+        }
+        (opt three)
+        (opt four) {
+            (w)
+        }
+        (w)
+    ]);
+    world.strategy = SearchStrategy::best_first(|goals| goals.len() as u64);
+    let (answers, result) = run(&world, &[data! {(opt {x})}], &Limits::default());
+    result.unwrap();
+    // Ties go to the resolvent found first.
+    assert_eq!(
+        answers,
+        ["(opt three)", "(opt two)", "(opt four)", "(opt one)"]
+    );
+}
+
+#[test]
+fn interleaving_is_fair_to_later_clauses() {
+    let mut world = World::new(rules![
+        (r {x}) {
+            (num {x})
+        }
+        (r done)
+        (num z)
+        (num (s {x})) {
+            (num {x})
+        }
+    ]);
+    world.strategy = SearchStrategy::Interleaving { steps: 4 };
+    let limits = Limits {
+        answers: Some(4),
+        ..Default::default()
+    };
+    let (answers, result) = run(&world, &[data! {(r {x})}], &limits);
+    assert!(matches!(result, Err(Error::LimitExceeded(Limit::Answers))));
+    assert!(answers.contains(&"(r done)".to_string()));
+    assert!(answers.contains(&"(r z)".to_string()));
+
+    // A first clause with no answers at all does not hide the second.
+    let mut world = World::new(rules![
+        (p {x}) {
+            (loop)
+        }
+        (p found)
+        (loop) {
+            (loop)
+        }
+    ]);
+    world.strategy = SearchStrategy::Interleaving { steps: 4 };
+    let limits = Limits {
+        inferences: Some(10_000),
+        ..Default::default()
+    };
+    let (answers, result) = run(&world, &[data! {(p {x})}], &limits);
+    assert_eq!(answers, ["(p found)"]);
+    assert!(matches!(
+        result,
+        Err(Error::LimitExceeded(Limit::Inferences))
+    ));
+}
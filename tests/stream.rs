@@ -0,0 +1,192 @@
+#[macro_use]
+extern crate prlg;
+
+use std::{
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll, Wake, Waker},
+};
+
+use prlg::{
+    data::Data, foreign::block_on, limits::Limits, stream::Solutions, user_data::UserData,
+    world::Mode, Atom, World,
+};
+
+fn world() -> World {
+    World::new(rules![
+        (nat z)
+        (nat (s {x})) {
+            (nat {x})
+        }
+        (add z {y} {y})
+        (add (s {x}) {y} (s {z})) {
+            (add {x} {y} {z})
+        }
+    ])
+}
+
+#[derive(Default)]
+struct CountWakes(AtomicUsize);
+
+impl Wake for CountWakes {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Polls until an answer or the end, returning it with the number of `Pending` polls.
+fn poll_answer(stream: &mut Solutions, cx: &mut Context) -> (Option<String>, usize) {
+    let mut pending = 0;
+    loop {
+        match Pin::new(&mut *stream).poll_next(cx) {
+            Poll::Ready(answer) => return (answer.map(|a| a.unwrap()[0].to_string()), pending),
+            Poll::Pending => pending += 1,
+        }
+    }
+}
+
+#[test]
+fn yields_after_the_given_inferences() {
+    let world = world();
+    // `add` takes one inference per `s`, and one more for `z`.
+    let goal = data! {(add (s (s (s (s (s (s (s (s (s z))))))))) z {n})};
+    let wakes = Arc::new(CountWakes::default());
+    let waker = Waker::from(wakes.clone());
+    let mut cx = Context::from_waker(&waker);
+
+    let mut stream = world.stream(std::slice::from_ref(&goal), 100);
+    assert_eq!(poll_answer(&mut stream, &mut cx).1, 0);
+
+    let mut stream = world.stream(std::slice::from_ref(&goal), 3);
+    let (answer, pending) = poll_answer(&mut stream, &mut cx);
+    assert_eq!(pending, 3);
+    // The task is woken to be polled again.
+    assert_eq!(wakes.0.load(Ordering::SeqCst), 3);
+    let (answer_at_once, _) = poll_answer(&mut world.stream(&[goal], 100), &mut cx);
+    assert_eq!(answer, answer_at_once);
+    assert_eq!(poll_answer(&mut stream, &mut cx), (None, 0));
+}
+
+#[test]
+fn endless_search_makes_progress() {
+    let world = world();
+    let mut stream = world.stream(&[data! {(nat {n})}, data! {(add {n} z {m})}], 1);
+    let mut cx = Context::from_waker(Waker::noop());
+    let (mut answers, mut pending) = (vec![], vec![]);
+    for _ in 0..3 {
+        let (answer, n) = poll_answer(&mut stream, &mut cx);
+        answers.push(answer.unwrap());
+        pending.push(n);
+    }
+    assert_eq!(
+        answers,
+        [
+            "(add z z z)",
+            "(add (s z) z (s z))",
+            "(add (s (s z)) z (s (s z)))"
+        ]
+    );
+    // Each answer takes one more inference than the one before.
+    assert_eq!(pending, [1, 2, 3]);
+}
+
+#[test]
+fn prepared_query_stream() {
+    let world = world();
+    let query = world.prepare(&[data! {(add {x} {y} {z})}]);
+    let (x, y) = (query.param("x").unwrap(), query.param("y").unwrap());
+    let s = Data::Symbol(Atom::new("s"));
+    let z = Data::Symbol(Atom::new("z"));
+    let one = Data::Term(vec![s, z.clone()].into());
+    let params = [(x, one.clone()), (y, one)];
+    let mut stream = query.stream(&params, &Limits::default(), 1);
+    let mut answers = vec![];
+    block_on(async {
+        while let Some(answer) = stream.next_answer().await {
+            answers.push(answer.unwrap()[0].to_string());
+        }
+    });
+    assert_eq!(answers, ["(add (s z) (s z) (s (s z)))"]);
+
+    // Parameters that cannot be bound leave nothing to search.
+    let params = [(x, z.clone()), (x, Data::Term(vec![z].into()))];
+    let mut stream = query.stream(&params, &Limits::default(), 1);
+    assert!(block_on(stream.next_answer()).is_none());
+}
+
+/// `World::stream` builds its goals in the search's own bindings; answers are as `run`
+/// gives them.
+#[test]
+fn owned_goals_answer_as_run_does() {
+    let long: UserData = (0..1_000).fold(data! {nil}, |tail, _| term![sym!(s), tail]);
+    for mode in [Mode::Interpreted, Mode::Compiled] {
+        let mut rules = rules![
+            (first {x}) {
+                (nat {x})
+                cut
+            }
+            (eq {x} {x})
+            (len z)
+            (len (s {x})) {
+                (len {x})
+            }
+        ];
+        rules.extend(rules![
+            (nat z)
+            (nat (s {x})) {
+                (nat {x})
+            }
+            (add z {y} {y})
+            (add (s {x}) {y} (s {z})) {
+                (add {x} {y} {z})
+            }
+        ]);
+        let world = World::with_mode(rules, mode);
+        let queries = [
+            vec![data! {(add {x} {y} (s (s z)))}],
+            vec![data! {(first {x})}, data! {(add {x} {x} {y})}],
+            vec![data! {(par (add {x} z (s z)) (eq {y} (f {w} {w})))}],
+            vec![
+                data! {(unify_with_occurs_check {x} (f {y}))},
+                data! {(eq {y} a)},
+            ],
+            vec![data! {(eq {x} (f {y} {z}))}, data! {(eq {z} {y})}],
+            vec![term![sym!(len), long.clone()]],
+        ];
+        for query in queries {
+            let mut expected = vec![];
+            world
+                .run(&query, |c| expected.push(format!("{:?}", c)))
+                .unwrap();
+            let mut stream = world.stream(&query, 7);
+            let mut answers = vec![];
+            block_on(async {
+                while let Some(answer) = stream.next_answer().await {
+                    answers.push(format!("{:?}", answer.unwrap()));
+                }
+            });
+            assert_eq!(answers, expected, "{:?}", query);
+        }
+        // Cycles are named after the size of the store, which differs.
+        let mut stream = world.stream(&[data! {(eq {x} (f {x}))}], 7);
+        let answer = block_on(stream.next_answer()).unwrap().unwrap();
+        // `(@ (eq {n} (f {n})) ...)`
+        let name = match &answer[0] {
+            Data::Term(at) => match &at[1] {
+                Data::Term(eq) => eq[1].to_string(),
+                _ => panic!("not a cyclic answer: {}", answer[0]),
+            },
+            _ => panic!("not a cyclic answer: {}", answer[0]),
+        };
+        let expected = format!("(@ (eq {0} (f {0})) [(= {0} (f {0}))])", name);
+        assert_eq!(answer[0].to_string(), expected);
+        assert!(block_on(stream.next_answer()).is_none());
+    }
+}
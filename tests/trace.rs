@@ -0,0 +1,74 @@
+#[macro_use]
+extern crate prlg;
+
+use prlg::{trace::PrettyTracer, user_data::UserData, World};
+
+fn trace(world: &World, query: &[UserData]) -> Vec<String> {
+    let mut out = vec![];
+    let mut tracer = PrettyTracer::new(query, &mut out);
+    world.run_traced(query, &mut tracer, |_| {}).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    out.lines().map(|line| line.to_string()).collect()
+}
+
+#[test]
+fn ports_of_a_nested_call() {
+    let world = World::new(rules![
+        (q a)
+        (q b)
+        (p {y}) {
+            (q {y})
+        }
+    ]);
+    assert_eq!(
+        trace(&world, &[data! {(p {x})}]),
+        [
+            "Call: (0) (p x)",
+            "  Call: (1) (q x)",
+            "  Exit: (1) (q a)  [clause 0]",
+            "Exit: (0) (p a)  [clause 2]",
+            "Redo: (0) (p x)  [clause 2]",
+            "  Redo: (1) (q x)  [clause 0]",
+            "  Exit: (1) (q b)  [clause 1]",
+            "Exit: (0) (p b)  [clause 2]",
+            "Redo: (0) (p x)  [clause 2]",
+            "  Redo: (1) (q x)  [clause 1]",
+            "  Fail: (1) (q x)  [clause 1]",
+            "Fail: (0) (p x)  [clause 2]",
+        ]
+    );
+}
+
+#[test]
+fn exited_calls_are_retried_before_they_fail() {
+    let world = World::new(rules![
+        (q a)
+        (q b)
+        (r a)
+        (p {y}) {
+            (q {y})
+            (r {y})
+        }
+    ]);
+    assert_eq!(
+        trace(&world, &[data! {(p {x})}]),
+        [
+            "Call: (0) (p x)",
+            "  Call: (1) (q x)",
+            "  Exit: (1) (q a)  [clause 0]",
+            "  Call: (1) (r a)",
+            "  Exit: (1) (r a)  [clause 2]",
+            "Exit: (0) (p a)  [clause 3]",
+            "Redo: (0) (p x)  [clause 3]",
+            "  Redo: (1) (r a)  [clause 2]",
+            "  Fail: (1) (r a)  [clause 2]",
+            "  Redo: (1) (q x)  [clause 0]",
+            "  Exit: (1) (q b)  [clause 1]",
+            "  Call: (1) (r b)",
+            "  Fail: (1) (r b)",
+            "  Redo: (1) (q x)  [clause 1]",
+            "  Fail: (1) (q x)  [clause 1]",
+            "Fail: (0) (p x)  [clause 3]",
+        ]
+    );
+}
@@ -0,0 +1,69 @@
+#[macro_use]
+extern crate prlg;
+
+use prlg::{why_not::Failure, World};
+
+fn closest(failure: &Failure) -> Vec<(String, usize)> {
+    let closest = failure.closest.iter();
+    closest.map(|m| (m.head.to_string(), m.argument)).collect()
+}
+
+#[test]
+fn rows_matching_bound_arguments_come_first() {
+    let mut world = World::new(rules![]);
+    let rows = (0..50_000).map(|i| [format!("k{i}"), format!("v{i}"), format!("w{}", i % 7)]);
+    world.load_facts("p", rows).unwrap();
+    world
+        .load_facts("p", [["k40000", "v0", "w1"], ["k40000", "v40000", "w0"]])
+        .unwrap();
+
+    let failures = world.why_not(&[data! {(p k40000 v40000 w9)}]).unwrap();
+    assert_eq!(failures.len(), 1);
+    let mut found = closest(&failures[0]);
+    found.sort();
+    assert_eq!(
+        found,
+        [
+            ("(p k40000 v0 w1)".to_string(), 2),
+            ("(p k40000 v40000 w0)".to_string(), 3),
+            ("(p k40000 v40000 w2)".to_string(), 3),
+        ]
+    );
+
+    // No row has the first argument.
+    let failures = world.why_not(&[data! {(p k {x} w1)}]).unwrap();
+    let found = closest(&failures[0]);
+    assert_eq!(found.len(), 3);
+    assert!(found.iter().all(|(_, argument)| *argument == 1));
+}
+
+#[test]
+fn clauses_of_the_predicate() {
+    let world = World::new(rules![
+        (q a b)
+        (q a c)
+        (r a b)
+        (q {x} d) {
+            (r {x} d)
+        }
+    ]);
+    let failures = world.why_not(&[data! {(q a e)}]).unwrap();
+    let mut found = closest(&failures[0]);
+    found.sort();
+    assert_eq!(
+        found,
+        [
+            ("(q a b)".to_string(), 2),
+            ("(q a c)".to_string(), 2),
+            ("(q {0} d)".to_string(), 2),
+        ]
+    );
+}
+
+#[test]
+fn empty_goal() {
+    let world = World::new(rules![(q a)]);
+    let failures = world.why_not(&[data! {()}]).unwrap();
+    assert_eq!(failures.len(), 1);
+    assert!(failures[0].closest.is_empty());
+}